@@ -0,0 +1,64 @@
+//! Compares sequential execution of a block of transfers with the parallel executor, for
+//! transfers between distinct accounts and transfers between a few contended accounts.
+use anunaya_rollup_core::parallel::ParallelExecutor;
+use anunaya_rollup_core::test_utils::{
+    TestAccounts, TestBlock, TestTransfers, child_block, test_chain, transfer,
+};
+use anunaya_rollup_core::traits::StateTransitionFunction;
+use criterion::{BatchSize, BenchmarkId, Criterion, criterion_group, criterion_main};
+
+const TRANSACTIONS: u16 = 2_000;
+
+fn block(transfers: impl Iterator<Item = (u16, u16)>) -> TestBlock {
+    let transactions = transfers.map(|(from, to)| transfer(from, to, 1)).collect();
+    child_block(&test_chain(1)[0].header, b"transfers", transactions)
+}
+
+/// Transfers between distinct pairs of accounts, none of which conflict.
+fn independent() -> (TestAccounts, TestBlock) {
+    let balances: Vec<_> = (0..2 * TRANSACTIONS)
+        .map(|account| (account, 1_000))
+        .collect();
+    let block = block((0..TRANSACTIONS).map(|i| (2 * i, 2 * i + 1)));
+    (TestAccounts::with_balances(&balances), block)
+}
+
+/// Transfers between 8 accounts, most of which conflict with a preceding one.
+fn contended() -> (TestAccounts, TestBlock) {
+    let balances: Vec<_> = (0..8).map(|account| (account, 1_000_000)).collect();
+    let block = block((0..TRANSACTIONS).map(|i| (i % 8, (i * 5 + 3) % 8)));
+    (TestAccounts::with_balances(&balances), block)
+}
+
+fn bench_execution(c: &mut Criterion) {
+    let executor = ParallelExecutor::default();
+    let mut group = c.benchmark_group("execution");
+    for (name, (ledger, block)) in [("independent", independent()), ("contended", contended())] {
+        group.bench_with_input(BenchmarkId::new("sequential", name), &block, |b, block| {
+            b.iter_batched(
+                || ledger.clone(),
+                |mut state| TestTransfers::apply_block(&mut state, block).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+        group.bench_with_input(
+            BenchmarkId::new(format!("parallel/{}", executor.threads()), name),
+            &block,
+            |b, block| {
+                b.iter_batched(
+                    || ledger.clone(),
+                    |mut state| {
+                        executor
+                            .apply_block::<TestTransfers>(&mut state, block)
+                            .unwrap()
+                    },
+                    BatchSize::LargeInput,
+                )
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_execution);
+criterion_main!(benches);
@@ -0,0 +1,459 @@
+//! Interactive bisection over the intermediate state roots of a disputed block.
+//!
+//! The defender of a block reveals the state root after a given number of its transactions,
+//! with a proof against the intermediate roots root of the header, and the challenger states
+//! whether it agrees. Each round halves the range of transactions between the last agreed
+//! root and the first disputed one, until a single transaction remains, which is settled by
+//! re-executing it with a [`OneStepProof`].
+//!
+//! Revealed roots also carry the gas used by the block so far, so that the disputed
+//! transaction runs with the gas the block gas limit leaves to it.
+//!
+//! The dispute starts from the parent state root and ends at the state root of the header,
+//! so it only covers blocks whose `begin_block` and `end_block` leave the state root
+//! unchanged, which the block builder and the validators require. A block without
+//! transactions must therefore keep the parent state root, and is settled without bisection,
+//! as is a header that does not commit to intermediate roots, which decides for the
+//! challenger.
+use crate::codec::Encode;
+use crate::fraud_proof::{
+    FraudProofError, IntermediateRoot, OneStepProof, intermediate_roots_root, verify_one_step,
+};
+use crate::merkle::MerkleProof;
+use crate::metering::Gas;
+use crate::traits::{BlockHeaderT, BlockT, MerkleizedState, ParallelTransition};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum BisectionError {
+    /// Revealed root is not the committed root after the given number of transactions
+    #[error("Invalid state root revealed after {} transactions", _0)]
+    InvalidReveal(usize),
+    /// Bisection has narrowed the dispute to a single transaction
+    #[error("Bisection is already narrowed to a single transaction")]
+    Concluded,
+    /// Dispute still spans several transactions
+    #[error("Bisection is not narrowed to a single transaction")]
+    Unresolved,
+    /// One-step proof of the disputed transaction is invalid
+    #[error(transparent)]
+    FraudProof(#[from] FraudProofError),
+}
+
+/// Party a settled dispute is decided for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// The block is valid at the disputed transaction
+    Defender,
+    /// The disputed transaction does not produce the committed state root
+    Challenger,
+}
+
+type HashOf<B> = <<B as BlockT>::BlockHeader as BlockHeaderT>::Hash;
+type HashingOf<B> = <<B as BlockT>::BlockHeader as BlockHeaderT>::Hashing;
+
+/// Dispute opened over a block.
+#[derive(Debug, Clone)]
+pub enum Dispute<'a, B: BlockT> {
+    /// Dispute to narrow down to a single transaction
+    Open(Bisection<'a, B>),
+    /// Dispute decided without bisection
+    Settled(Verdict),
+}
+
+/// State of a dispute over a block, as tracked by the referee.
+///
+/// Steps count the transactions applied: step 0 is the parent state root with no gas used
+/// and step `n` the state after the `n`th transaction.
+#[derive(Debug, Clone)]
+pub struct Bisection<'a, B: BlockT> {
+    block: &'a B,
+    commitment: [u8; 32],
+    gas_limit: Gas,
+    agreed: (usize, IntermediateRoot),
+    disputed: (usize, IntermediateRoot),
+}
+
+impl<'a, B: BlockT> Bisection<'a, B> {
+    /// Opens a dispute over `block`, applied on top of `parent_root` with a block gas limit
+    /// of `gas_limit`. The defender reveals the final state of the block with its proof
+    /// against the header commitment.
+    ///
+    /// A header without intermediate roots commitment cannot be defended, and a block
+    /// without transactions is valid only if it keeps the parent state root, so both are
+    /// settled at once.
+    pub fn open(
+        parent_root: [u8; 32],
+        gas_limit: Gas,
+        block: &'a B,
+        final_root: IntermediateRoot,
+        proof: &MerkleProof<HashOf<B>>,
+    ) -> Result<Dispute<'a, B>, BisectionError> {
+        let Some(commitment) = block.header().intermediate_roots_root() else {
+            return Ok(Dispute::Settled(Verdict::Challenger));
+        };
+        let steps = block.transactions().len();
+        if steps == 0 {
+            let valid = block.header().state_root().as_ref() == parent_root.as_slice()
+                && intermediate_roots_root::<HashingOf<B>>(&[]).as_ref() == commitment.as_slice();
+            let verdict = if valid {
+                Verdict::Defender
+            } else {
+                Verdict::Challenger
+            };
+            return Ok(Dispute::Settled(verdict));
+        }
+        // The final root is the state root of the header
+        if block.header().state_root().as_ref() != final_root.state_root.as_slice() {
+            return Err(BisectionError::InvalidReveal(steps));
+        }
+        let bisection = Self {
+            block,
+            commitment: commitment.0,
+            gas_limit,
+            agreed: (
+                0,
+                IntermediateRoot {
+                    state_root: parent_root,
+                    gas_used: 0,
+                },
+            ),
+            disputed: (steps, final_root),
+        };
+        bisection.check_reveal(steps, &final_root, proof)?;
+        Ok(Dispute::Open(bisection))
+    }
+
+    fn check_reveal(
+        &self,
+        step: usize,
+        root: &IntermediateRoot,
+        proof: &MerkleProof<HashOf<B>>,
+    ) -> Result<(), BisectionError> {
+        let valid = proof.index == (step - 1) as u64
+            && proof.leaf_count == self.block.transactions().len() as u64
+            && proof
+                .compute_root::<HashingOf<B>>(&root.to_bytes())
+                .is_some_and(|computed| computed.as_ref() == self.commitment.as_slice());
+        if !valid {
+            return Err(BisectionError::InvalidReveal(step));
+        }
+        Ok(())
+    }
+
+    /// Last step both parties agree on, with its state.
+    pub fn agreed(&self) -> (usize, IntermediateRoot) {
+        self.agreed
+    }
+
+    /// First step the challenger disputes, with the state claimed by the defender.
+    pub fn disputed(&self) -> (usize, IntermediateRoot) {
+        self.disputed
+    }
+
+    /// Step whose state the defender must reveal next, or `None` once the dispute is
+    /// narrowed to a single transaction.
+    pub fn next_step(&self) -> Option<usize> {
+        let (agreed, disputed) = (self.agreed.0, self.disputed.0);
+        (disputed - agreed > 1).then(|| agreed + (disputed - agreed) / 2)
+    }
+
+    /// Records the state revealed by the defender at [`Self::next_step`] and whether the
+    /// challenger agrees with it.
+    pub fn bisect(
+        &mut self,
+        root: IntermediateRoot,
+        proof: &MerkleProof<HashOf<B>>,
+        challenger_agrees: bool,
+    ) -> Result<(), BisectionError> {
+        let step = self.next_step().ok_or(BisectionError::Concluded)?;
+        self.check_reveal(step, &root, proof)?;
+        if challenger_agrees {
+            self.agreed = (step, root);
+        } else {
+            self.disputed = (step, root);
+        }
+        Ok(())
+    }
+
+    /// Index and transaction the dispute is narrowed to, if it is.
+    pub fn disputed_transaction(&self) -> Option<(usize, &'a B::Transaction)> {
+        if self.next_step().is_some() {
+            return None;
+        }
+        let index = self.agreed.0;
+        Some((index, &self.block.transactions()[index]))
+    }
+
+    /// Settles the dispute by re-executing the disputed transaction from the agreed state,
+    /// with the gas left in the block. A transaction that cannot be applied decides for the
+    /// challenger.
+    pub fn resolve<S>(&self, proof: &OneStepProof) -> Result<Verdict, BisectionError>
+    where
+        S: ParallelTransition<Block = B>,
+        S::State: MerkleizedState,
+    {
+        let (_, transaction) = self
+            .disputed_transaction()
+            .ok_or(BisectionError::Unresolved)?;
+        match verify_one_step::<S>(&self.agreed.1, self.gas_limit, transaction, proof)? {
+            Some(root) if root == self.disputed.1 => Ok(Verdict::Defender),
+            _ => Ok(Verdict::Challenger),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{BlockBuilder, BlockBuilderConfig};
+    use crate::fraud_proof::{
+        intermediate_root_proof, intermediate_roots_root, prove_transaction, trace_block,
+    };
+    use crate::hasher::KeccakHasher;
+    use crate::test_utils::{
+        TRANSFER_GAS, TestAccounts, TestBlock, TestTransaction, TestTransfers, test_chain, transfer,
+    };
+    use crate::traits::{AppState, HasherT};
+    use alloy::primitives::B256;
+
+    const GAS_LIMIT: Gas = 100;
+
+    fn accounts() -> TestAccounts {
+        TestAccounts::with_balances(&[(1, 100), (2, 50), (3, 7), (4, 20)]).with_gas_limit(GAS_LIMIT)
+    }
+
+    fn transfers() -> Vec<TestTransaction> {
+        (0..7u16)
+            .map(|i| transfer(1 + i % 4, 1 + (i + 1) % 4, 1 + i as u64))
+            .collect()
+    }
+
+    /// Defender of a block, revealing the intermediate roots it was built with.
+    struct Defender {
+        block: TestBlock,
+        roots: Vec<IntermediateRoot>,
+    }
+
+    impl Defender {
+        fn honest() -> Self {
+            let parent = test_chain(1).remove(0).header;
+            let mut state = accounts();
+            let built = BlockBuilder::<TestTransfers>::new(BlockBuilderConfig::default())
+                .build(&parent, &mut state, &transfers(), None)
+                .unwrap();
+            assert_eq!(built.block.transactions.len(), 7);
+            Self {
+                block: built.block,
+                roots: built.intermediate_roots,
+            }
+        }
+
+        /// Claims wrong state roots from transaction `index` on, committed to by the header.
+        fn dishonest(index: usize) -> Self {
+            let Self {
+                mut block,
+                mut roots,
+            } = Self::honest();
+            for root in &mut roots[index..] {
+                root.state_root = KeccakHasher::hash(&root.state_root);
+            }
+            block.header.state_root = roots[roots.len() - 1].state_root;
+            block.header.extension.intermediate_roots_root =
+                Some(B256::from(intermediate_roots_root::<KeccakHasher>(&roots)));
+            Self { block, roots }
+        }
+
+        fn reveal(&self, step: usize) -> (IntermediateRoot, MerkleProof<[u8; 32]>) {
+            let proof = intermediate_root_proof::<KeccakHasher>(&self.roots, step - 1).unwrap();
+            (self.roots[step - 1], proof)
+        }
+    }
+
+    /// Opens a dispute that needs bisection.
+    fn open<'a>(
+        parent_root: [u8; 32],
+        gas_limit: Gas,
+        block: &'a TestBlock,
+        final_root: IntermediateRoot,
+        proof: &MerkleProof<[u8; 32]>,
+    ) -> Bisection<'a, TestBlock> {
+        match Bisection::open(parent_root, gas_limit, block, final_root, proof).unwrap() {
+            Dispute::Open(bisection) => bisection,
+            Dispute::Settled(verdict) => panic!("Dispute settled for the {verdict:?}"),
+        }
+    }
+
+    /// Plays the dispute to the end under `gas_limit`, with the challenger agreeing with the
+    /// roots it accepts. Returns the disputed transaction and the verdict.
+    fn play(
+        defender: &Defender,
+        gas_limit: Gas,
+        agrees: impl Fn(usize, &IntermediateRoot) -> bool,
+    ) -> (usize, Verdict) {
+        let parent = accounts().with_gas_limit(gas_limit);
+        let steps = defender.roots.len();
+        let (final_root, proof) = defender.reveal(steps);
+        let mut bisection = open(
+            parent.state_root(),
+            gas_limit,
+            &defender.block,
+            final_root,
+            &proof,
+        );
+        while let Some(step) = bisection.next_step() {
+            let (root, proof) = defender.reveal(step);
+            bisection.bisect(root, &proof, agrees(step, &root)).unwrap();
+        }
+        let (index, _) = bisection.disputed_transaction().unwrap();
+        let proof = prove_transaction::<TestTransfers>(&parent, &defender.block, index)
+            .unwrap()
+            .unwrap();
+        (index, bisection.resolve::<TestTransfers>(&proof).unwrap())
+    }
+
+    #[test]
+    fn test_honest_defender_wins() {
+        let defender = Defender::honest();
+        assert_eq!(
+            play(&defender, GAS_LIMIT, |_, _| false),
+            (0, Verdict::Defender)
+        );
+    }
+
+    #[test]
+    fn test_challenger_finds_invalid_transaction() {
+        for index in 0..7 {
+            let defender = Defender::dishonest(index);
+            // The challenger replays the block to check the revealed roots
+            let trace = trace_block::<TestTransfers>(&mut accounts(), &defender.block).unwrap();
+            let outcome = play(&defender, GAS_LIMIT, |step, root| {
+                trace.intermediate_roots[step - 1] == *root
+            });
+            assert_eq!(outcome, (index, Verdict::Challenger));
+        }
+    }
+
+    #[test]
+    fn test_challenger_finds_understated_gas() {
+        let mut defender = Defender::honest();
+        let honest = defender.roots.clone();
+        for root in &mut defender.roots[3..] {
+            root.gas_used -= TRANSFER_GAS;
+        }
+        defender.block.header.extension.intermediate_roots_root = Some(B256::from(
+            intermediate_roots_root::<KeccakHasher>(&defender.roots),
+        ));
+        let outcome = play(&defender, GAS_LIMIT, |step, root| honest[step - 1] == *root);
+        assert_eq!(outcome, (3, Verdict::Challenger));
+    }
+
+    #[test]
+    fn test_challenger_finds_block_over_gas_limit() {
+        // The block of seven transfers only has gas for five
+        let defender = Defender::honest();
+        let outcome = play(&defender, 5 * TRANSFER_GAS, |step, _| step <= 5);
+        assert_eq!(outcome, (5, Verdict::Challenger));
+    }
+
+    #[test]
+    fn test_rejects_invalid_reveals() {
+        let defender = Defender::honest();
+        let parent_root = accounts().state_root();
+        let (final_root, proof) = defender.reveal(7);
+        let other_root = IntermediateRoot {
+            state_root: [1; 32],
+            ..final_root
+        };
+        assert_eq!(
+            Bisection::open(parent_root, GAS_LIMIT, &defender.block, other_root, &proof)
+                .unwrap_err(),
+            BisectionError::InvalidReveal(7)
+        );
+        // The gas used is committed to as well
+        let other_gas = IntermediateRoot {
+            gas_used: 0,
+            ..final_root
+        };
+        assert_eq!(
+            Bisection::open(parent_root, GAS_LIMIT, &defender.block, other_gas, &proof)
+                .unwrap_err(),
+            BisectionError::InvalidReveal(7)
+        );
+
+        let mut bisection = open(parent_root, GAS_LIMIT, &defender.block, final_root, &proof);
+        assert_eq!(bisection.next_step(), Some(3));
+        // A root revealed for another step is rejected
+        let (root, proof) = defender.reveal(2);
+        assert_eq!(
+            bisection.bisect(root, &proof, true),
+            Err(BisectionError::InvalidReveal(3))
+        );
+        assert_eq!(
+            bisection.resolve::<TestTransfers>(
+                &prove_transaction::<TestTransfers>(&accounts(), &defender.block, 0)
+                    .unwrap()
+                    .unwrap()
+            ),
+            Err(BisectionError::Unresolved)
+        );
+    }
+
+    #[test]
+    fn test_block_without_commitment_is_invalid() {
+        let defender = Defender::honest();
+        let (final_root, proof) = defender.reveal(7);
+        let mut block = defender.block.clone();
+        block.header.extension.intermediate_roots_root = None;
+        assert!(matches!(
+            Bisection::open(
+                accounts().state_root(),
+                GAS_LIMIT,
+                &block,
+                final_root,
+                &proof
+            ),
+            Ok(Dispute::Settled(Verdict::Challenger))
+        ));
+    }
+
+    #[test]
+    fn test_empty_block_is_settled_directly() {
+        let parent = test_chain(1).remove(0).header;
+        let mut state = accounts();
+        let parent_root = state.state_root();
+        let mut block = BlockBuilder::<TestTransfers>::new(BlockBuilderConfig::default())
+            .build(&parent, &mut state, &[], None)
+            .unwrap()
+            .block;
+        let final_root = IntermediateRoot {
+            state_root: parent_root,
+            gas_used: 0,
+        };
+        let proof = MerkleProof {
+            index: 0,
+            leaf_count: 0,
+            siblings: vec![],
+        };
+        let verdict = |block: &TestBlock| match Bisection::open(
+            parent_root,
+            GAS_LIMIT,
+            block,
+            final_root,
+            &proof,
+        )
+        .unwrap()
+        {
+            Dispute::Settled(verdict) => verdict,
+            Dispute::Open(_) => panic!("Empty block needs no bisection"),
+        };
+        assert_eq!(verdict(&block), Verdict::Defender);
+
+        // A state root the block cannot have reached decides for the challenger
+        block.header.state_root = KeccakHasher::hash(b"minted");
+        assert_eq!(verdict(&block), Verdict::Challenger);
+        // So does a commitment to roots an empty block has none of
+        block.header.state_root = parent_root;
+        block.header.extension.intermediate_roots_root = Some(B256::repeat_byte(1));
+        assert_eq!(verdict(&block), Verdict::Challenger);
+    }
+}
@@ -1,32 +1,184 @@
+use crate::codec::{CodecError, Decode, Encode};
+use crate::hasher::HasherKind;
+use crate::merkle;
 use crate::traits::{BlockHeaderT, BlockT, HasherT, SignedTransactionT};
+use alloy::primitives::{Address, B256};
 use serde::{Deserialize, Serialize};
 use std::fmt::Debug;
-use hex::decode;
+
+/// Header layout with only the core fields.
+pub const HEADER_VERSION_V1: u8 = 1;
+/// Header layout that also carries a [`HeaderExtension`].
+pub const HEADER_VERSION_V2: u8 = 2;
+/// Header layout that records the hasher, and whose extension also carries the history,
+/// receipts, intermediate and withdrawals roots. Headers of earlier versions are hashed with Keccak.
+pub const HEADER_VERSION_V3: u8 = 3;
+/// Header layout whose extension also carries the hash chain of the applied L1 deposits.
+pub const HEADER_VERSION_V4: u8 = 4;
+/// Layout of the headers built with an extension.
+pub const HEADER_VERSION: u8 = HEADER_VERSION_V4;
+/// Maximum length of [`HeaderExtension::extra_data`].
+pub const MAX_EXTRA_DATA_LEN: usize = 32;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum HeaderError {
+    /// Extra data exceeds `MAX_EXTRA_DATA_LEN`
+    #[error("Extra data is {} bytes, at most {} allowed", _0, MAX_EXTRA_DATA_LEN)]
+    ExtraDataTooLong(usize),
+}
+
+/// L1 block the rollup block was derived from.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct L1Origin {
+    pub number: u64,
+    pub hash: B256,
+}
+
+/// Optional standard header fields.
+///
+/// Fields are encoded in the order of the header version that introduced them, so that the
+/// headers of an earlier version keep their encoding and hash.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HeaderExtension {
+    // Unix timestamp in seconds
+    pub timestamp: Option<u64>,
+    // Address of the block proposer
+    pub proposer: Option<Address>,
+    // L1 block the block was derived from
+    pub l1_origin: Option<L1Origin>,
+    // Commitment to the block data on the data availability layer
+    pub da_commitment: Option<B256>,
+    // MMR root of the hashes of all preceding blocks, from version 3
+    pub history_root: Option<B256>,
+    // Merkle root of the transaction receipts, from version 3
+    pub receipts_root: Option<B256>,
+    // Arbitrary data, at most `MAX_EXTRA_DATA_LEN` bytes
+    pub extra_data: Vec<u8>,
+    // Merkle root of the state roots after each transaction, from version 3
+    pub intermediate_roots_root: Option<B256>,
+    // Merkle root of the withdrawals to L1 made by the transactions, from version 3
+    pub withdrawals_root: Option<B256>,
+    // Hash chain of the L1 deposits applied up to the block, from version 4
+    pub deposits_hash: Option<B256>,
+}
+
+impl Encode for L1Origin {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.number.encode_to(out);
+        self.hash.encode_to(out);
+    }
+}
+
+impl Decode for L1Origin {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            number: u64::decode_from(input)?,
+            hash: B256::decode_from(input)?,
+        })
+    }
+}
+
+impl HeaderExtension {
+    /// Encodes the fields known to header `version`.
+    fn encode_versioned(&self, version: u8, out: &mut Vec<u8>) {
+        if version < HEADER_VERSION_V2 {
+            return;
+        }
+        self.timestamp.encode_to(out);
+        self.proposer.encode_to(out);
+        self.l1_origin.encode_to(out);
+        self.da_commitment.encode_to(out);
+        // Version 3 placed the history and receipts roots ahead of the extra data
+        if version >= HEADER_VERSION_V3 {
+            self.history_root.encode_to(out);
+            self.receipts_root.encode_to(out);
+        }
+        self.extra_data.encode_to(out);
+        if version >= HEADER_VERSION_V3 {
+            self.intermediate_roots_root.encode_to(out);
+            self.withdrawals_root.encode_to(out);
+        }
+        if version >= HEADER_VERSION_V4 {
+            self.deposits_hash.encode_to(out);
+        }
+    }
+
+    /// Decodes the fields known to header `version`, the others are left empty.
+    fn decode_versioned(input: &mut &[u8], version: u8) -> Result<Self, CodecError> {
+        let mut extension = Self::default();
+        if version < HEADER_VERSION_V2 {
+            return Ok(extension);
+        }
+        extension.timestamp = Option::decode_from(input)?;
+        extension.proposer = Option::decode_from(input)?;
+        extension.l1_origin = Option::decode_from(input)?;
+        extension.da_commitment = Option::decode_from(input)?;
+        if version >= HEADER_VERSION_V3 {
+            extension.history_root = Option::decode_from(input)?;
+            extension.receipts_root = Option::decode_from(input)?;
+        }
+        extension.extra_data = Vec::decode_from(input)?;
+        if version >= HEADER_VERSION_V3 {
+            extension.intermediate_roots_root = Option::decode_from(input)?;
+            extension.withdrawals_root = Option::decode_from(input)?;
+        }
+        if version >= HEADER_VERSION_V4 {
+            extension.deposits_hash = Option::decode_from(input)?;
+        }
+        if extension.extra_data.len() > MAX_EXTRA_DATA_LEN {
+            return Err(CodecError::InvalidValue(
+                HeaderError::ExtraDataTooLong(extension.extra_data.len()).to_string(),
+            ));
+        }
+        Ok(extension)
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct BlockHeader<Number: Copy + Into<u64>, Hash: HasherT> {
+    pub version: u8,
     pub parent_hash: Hash::Output,
     pub number: Number,
     pub state_root: Hash::Output,
+    pub transactions_root: Hash::Output,
+    pub extension: HeaderExtension,
 }
 
 impl<Number, Hash> BlockHeaderT for BlockHeader<Number, Hash>
 where
-    Number: Copy + Into<u64> + Send + Sync + Debug + Clone + 'static,
+    Number: Copy + Into<u64> + TryFrom<u64> + Send + Sync + Debug + Clone + 'static,
     Hash: HasherT + Debug + Clone + 'static,
 {
     type Number = Number;
     type Hash = Hash::Output;
     type Hashing = Hash;
 
-    fn new(number: Number, state_root: Hash::Output, parent_hash: Hash::Output) -> Self {
+    fn new(
+        number: Number,
+        state_root: Hash::Output,
+        parent_hash: Hash::Output,
+        transactions_root: Hash::Output,
+    ) -> Self {
+        // Only headers from version 3 on record a hasher other than Keccak
+        let version = if Hash::KIND == HasherKind::Keccak {
+            HEADER_VERSION_V1
+        } else {
+            HEADER_VERSION_V3
+        };
         Self {
+            version,
             number,
             state_root,
             parent_hash,
+            transactions_root,
+            extension: HeaderExtension::default(),
         }
     }
 
+    fn version(&self) -> u8 {
+        self.version
+    }
+
     fn number(&self) -> &Self::Number {
         &self.number
     }
@@ -39,13 +191,97 @@ where
         &self.parent_hash
     }
 
-    fn encode(&self) -> Vec<u8> {
-        let mut encoded = Vec::with_capacity(68);
-        encoded.extend_from_slice(self.parent_hash.as_ref());
-        let number_le: u32 = self.number.into() as u32;
-        encoded.extend_from_slice(&number_le.to_le_bytes());
-        encoded.extend_from_slice(self.state_root.as_ref());
-        encoded
+    fn transactions_root(&self) -> &Self::Hash {
+        &self.transactions_root
+    }
+
+    fn extension(&self) -> &HeaderExtension {
+        &self.extension
+    }
+
+    fn with_extension(mut self, extension: HeaderExtension) -> Result<Self, HeaderError> {
+        if extension.extra_data.len() > MAX_EXTRA_DATA_LEN {
+            return Err(HeaderError::ExtraDataTooLong(extension.extra_data.len()));
+        }
+        self.version = HEADER_VERSION;
+        self.extension = extension;
+        Ok(self)
+    }
+
+    fn unsealed(&self) -> Self {
+        let empty_root = merkle::empty_root::<Hash>();
+        Self {
+            state_root: empty_root,
+            transactions_root: empty_root,
+            extension: HeaderExtension {
+                receipts_root: None,
+                intermediate_roots_root: None,
+                withdrawals_root: None,
+                deposits_hash: None,
+                ..self.extension.clone()
+            },
+            ..self.clone()
+        }
+    }
+}
+
+impl<Number, Hash> Encode for BlockHeader<Number, Hash>
+where
+    Number: Copy + Into<u64>,
+    Hash: HasherT,
+{
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.version.encode_to(out);
+        if self.version >= HEADER_VERSION_V3 {
+            Hash::KIND.encode_to(out);
+        }
+        self.parent_hash.encode_to(out);
+        self.number.into().encode_to(out);
+        self.state_root.encode_to(out);
+        self.transactions_root.encode_to(out);
+        self.extension.encode_versioned(self.version, out);
+    }
+}
+
+impl<Number, Hash> Decode for BlockHeader<Number, Hash>
+where
+    Number: Copy + Into<u64> + TryFrom<u64>,
+    Hash: HasherT,
+{
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let version = u8::decode_from(input)?;
+        if !(HEADER_VERSION_V1..=HEADER_VERSION).contains(&version) {
+            return Err(CodecError::InvalidValue(format!(
+                "Unsupported header version {version}"
+            )));
+        }
+        let hasher = if version >= HEADER_VERSION_V3 {
+            HasherKind::decode_from(input)?
+        } else {
+            HasherKind::Keccak
+        };
+        if hasher != Hash::KIND {
+            return Err(CodecError::InvalidValue(format!(
+                "Header hashed with {hasher}, expected {}",
+                Hash::KIND
+            )));
+        }
+        let parent_hash = Hash::Output::decode_from(input)?;
+        let number = u64::decode_from(input)?;
+        let number = Number::try_from(number)
+            .map_err(|_| CodecError::InvalidValue(format!("Block number {number} out of range")))?;
+        let state_root = Hash::Output::decode_from(input)?;
+        let transactions_root = Hash::Output::decode_from(input)?;
+        // Headers of an earlier version decode with the fields they predate empty
+        let extension = HeaderExtension::decode_versioned(input, version)?;
+        Ok(Self {
+            version,
+            parent_hash,
+            number,
+            state_root,
+            transactions_root,
+            extension,
+        })
     }
 }
 
@@ -81,11 +317,27 @@ where
     }
 }
 
+impl<BlockHeader: Encode, Transaction: Encode> Encode for Block<BlockHeader, Transaction> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.transactions.encode_to(out);
+    }
+}
+
+impl<BlockHeader: Decode, Transaction: Decode> Decode for Block<BlockHeader, Transaction> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Block {
+            header: BlockHeader::decode_from(input)?,
+            transactions: Vec::decode_from(input)?,
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::merkle::empty_root;
     use alloy::primitives::B256;
-    use anyhow::Ok;
 
     // Test implementation of SignedTransactionT
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -98,6 +350,21 @@ mod tests {
             &self.data
         }
     }
+
+    impl Encode for TestTransaction {
+        fn encode_to(&self, out: &mut Vec<u8>) {
+            self.data.encode_to(out);
+        }
+    }
+
+    impl Decode for TestTransaction {
+        fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+            Ok(Self {
+                data: Vec::decode_from(input)?,
+            })
+        }
+    }
+
     impl SignedTransactionT for TestTransaction {}
 
     #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -105,6 +372,7 @@ mod tests {
 
     impl HasherT for KeccakHasher {
         type Output = B256;
+        const KIND: crate::hasher::HasherKind = crate::hasher::HasherKind::Keccak;
 
         fn hash(s: &[u8]) -> Self::Output {
             alloy::primitives::keccak256(s)
@@ -119,7 +387,12 @@ mod tests {
         let number = 1u64;
 
         // Create a block header
-        let header = BlockHeader::<u64, KeccakHasher>::new(number, state_root, parent_hash);
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            number,
+            state_root,
+            parent_hash,
+            empty_root::<KeccakHasher>(),
+        );
 
         // Create some transactions
         let transactions = vec![
@@ -161,15 +434,281 @@ mod tests {
         let parent_hash = KeccakHasher::hash(b"parent");
         let state_root = KeccakHasher::hash(b"state");
         let number = 1u64;
-        let header = BlockHeader::<u64, KeccakHasher>::new(number, state_root, parent_hash);
-
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            number,
+            state_root,
+            parent_hash,
+            empty_root::<KeccakHasher>(),
+        );
 
         let encoded = header.encode();
-        assert_eq!(encoded.len(), 68);
+        // codec version, header version, parent hash, number, state root, transactions root
+        assert_eq!(encoded.len(), 1 + 1 + 32 + 8 + 32 + 32);
+        assert_eq!(encoded[0], crate::codec::CODEC_VERSION);
+        assert_eq!(encoded[1], HEADER_VERSION_V1);
 
-        // Check parent_hash (first 32 bytes)
-        let expected = hex::decode("ff483e972a04a9a62bb4b7d04ae403c615604e4090521ecc5bb7af67f71be09c")?;
-        assert_eq!(&encoded[..32], expected.as_slice());
+        // Check parent_hash (32 bytes after the version bytes)
+        let expected =
+            hex::decode("ff483e972a04a9a62bb4b7d04ae403c615604e4090521ecc5bb7af67f71be09c")?;
+        assert_eq!(&encoded[2..34], expected.as_slice());
+        assert_eq!(header.hash(), KeccakHasher::hash(&encoded));
         Ok(())
     }
+
+    #[test]
+    fn test_header_keeps_full_u64_number() {
+        let number = u64::from(u32::MAX) + 1;
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            number,
+            KeccakHasher::hash(b"state"),
+            KeccakHasher::hash(b"parent"),
+            empty_root::<KeccakHasher>(),
+        );
+        let decoded = BlockHeader::<u64, KeccakHasher>::from_bytes(&header.encode()).unwrap();
+        assert_eq!(decoded.number, number);
+
+        // A number that does not fit the header's number type is rejected
+        assert!(matches!(
+            BlockHeader::<u32, KeccakHasher>::from_bytes(&header.encode()),
+            Err(CodecError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_block_codec_round_trip() {
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            7,
+            KeccakHasher::hash(b"state"),
+            KeccakHasher::hash(b"parent"),
+            empty_root::<KeccakHasher>(),
+        );
+        let transactions = vec![
+            TestTransaction { data: vec![1, 2] },
+            TestTransaction { data: vec![] },
+        ];
+        let block = Block::new(header, transactions);
+
+        let mut bytes = block.to_bytes();
+        let decoded =
+            Block::<BlockHeader<u64, KeccakHasher>, TestTransaction>::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.header.hash(), block.header.hash());
+        assert_eq!(decoded.to_bytes(), bytes);
+
+        bytes.push(0);
+        assert_eq!(
+            Block::<BlockHeader<u64, KeccakHasher>, TestTransaction>::from_bytes(&bytes).err(),
+            Some(CodecError::TrailingBytes(1))
+        );
+    }
+
+    #[test]
+    fn test_transaction_inclusion_proof() {
+        let transactions: Vec<_> = (0..5u8)
+            .map(|i| TestTransaction { data: vec![i; 3] })
+            .collect();
+        let mut block = Block::new(
+            BlockHeader::<u64, KeccakHasher>::new(
+                1,
+                KeccakHasher::hash(b"state"),
+                KeccakHasher::hash(b"parent"),
+                empty_root::<KeccakHasher>(),
+            ),
+            transactions,
+        );
+        block.header.transactions_root = block.compute_transactions_root();
+
+        // Only the header and the proof are needed to check inclusion
+        let header = block.header().clone();
+        for (index, tx) in block.transactions().iter().enumerate() {
+            let proof = block.transaction_proof(index).unwrap();
+            assert!(header.verify_transaction(tx, &proof));
+        }
+        let proof = block.transaction_proof(1).unwrap();
+        assert!(!header.verify_transaction(&block.transactions()[2], &proof));
+        assert!(block.transaction_proof(5).is_none());
+
+        // Changing the transactions changes the root
+        block.transactions.pop();
+        assert_ne!(block.compute_transactions_root(), header.transactions_root);
+    }
+
+    #[test]
+    fn test_header_extension() {
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            3,
+            KeccakHasher::hash(b"state"),
+            KeccakHasher::hash(b"parent"),
+            empty_root::<KeccakHasher>(),
+        );
+        let extension = HeaderExtension {
+            timestamp: Some(1_700_000_000),
+            proposer: Some(Address::repeat_byte(0xaa)),
+            l1_origin: Some(L1Origin {
+                number: 19_000_000,
+                hash: B256::repeat_byte(0xbb),
+            }),
+            da_commitment: Some(B256::repeat_byte(0xcc)),
+            history_root: Some(B256::repeat_byte(0xdd)),
+            receipts_root: Some(B256::repeat_byte(0xee)),
+            intermediate_roots_root: Some(B256::repeat_byte(0xff)),
+            withdrawals_root: Some(B256::repeat_byte(0x11)),
+            deposits_hash: Some(B256::repeat_byte(0x22)),
+            extra_data: b"anunaya".to_vec(),
+        };
+        let extended = header.clone().with_extension(extension.clone()).unwrap();
+        assert_eq!(extended.version(), HEADER_VERSION);
+        assert_eq!(extended.timestamp(), Some(1_700_000_000));
+        assert_ne!(extended.hash(), header.hash());
+
+        let decoded = BlockHeader::<u64, KeccakHasher>::from_bytes(&extended.encode()).unwrap();
+        assert_eq!(decoded.extension, extension);
+        assert_eq!(decoded.hash(), extended.hash());
+
+        // Every extension field is covered by the hash
+        let mut other = extension.clone();
+        other.extra_data.push(0);
+        let other = header.clone().with_extension(other).unwrap();
+        assert_ne!(other.hash(), extended.hash());
+
+        let too_long = HeaderExtension {
+            extra_data: vec![0; MAX_EXTRA_DATA_LEN + 1],
+            ..Default::default()
+        };
+        assert_eq!(
+            header.with_extension(too_long).err(),
+            Some(HeaderError::ExtraDataTooLong(MAX_EXTRA_DATA_LEN + 1))
+        );
+    }
+
+    #[test]
+    fn test_v2_header_keeps_its_encoding() {
+        // Version 2 header in the layout it was introduced with
+        let mut encoded = vec![crate::codec::CODEC_VERSION, HEADER_VERSION_V2];
+        KeccakHasher::hash(b"parent").encode_to(&mut encoded);
+        3u64.encode_to(&mut encoded);
+        KeccakHasher::hash(b"state").encode_to(&mut encoded);
+        empty_root::<KeccakHasher>().encode_to(&mut encoded);
+        Some(1_700_000_000u64).encode_to(&mut encoded);
+        Some(Address::repeat_byte(0xaa)).encode_to(&mut encoded);
+        None::<L1Origin>.encode_to(&mut encoded);
+        Some(B256::repeat_byte(0xcc)).encode_to(&mut encoded);
+        b"anunaya".to_vec().encode_to(&mut encoded);
+
+        let decoded = BlockHeader::<u64, KeccakHasher>::from_bytes(&encoded).unwrap();
+        assert_eq!(decoded.version(), HEADER_VERSION_V2);
+        assert_eq!(
+            decoded.extension,
+            HeaderExtension {
+                timestamp: Some(1_700_000_000),
+                proposer: Some(Address::repeat_byte(0xaa)),
+                da_commitment: Some(B256::repeat_byte(0xcc)),
+                extra_data: b"anunaya".to_vec(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(decoded.encode(), encoded);
+        assert_eq!(decoded.hash(), KeccakHasher::hash(&encoded));
+
+        // Version 3 adds the hasher and the roots, and version 4 the deposits hash
+        let mut v3 = decoded.clone();
+        v3.version = HEADER_VERSION_V3;
+        assert_eq!(v3.encode().len(), encoded.len() + 1 + 4);
+        let mut v4 = v3.clone();
+        v4.version = HEADER_VERSION_V4;
+        assert_eq!(v4.encode().len(), encoded.len() + 1 + 5);
+        let decoded_v3 = BlockHeader::<u64, KeccakHasher>::from_bytes(&v3.encode()).unwrap();
+        assert_eq!(decoded_v3.hash(), v3.hash());
+    }
+
+    #[test]
+    fn test_v1_header_decodes_without_extension() {
+        let header = BlockHeader::<u64, KeccakHasher>::new(
+            3,
+            KeccakHasher::hash(b"state"),
+            KeccakHasher::hash(b"parent"),
+            empty_root::<KeccakHasher>(),
+        );
+        let decoded = BlockHeader::<u64, KeccakHasher>::from_bytes(&header.encode()).unwrap();
+        assert_eq!(decoded.version(), HEADER_VERSION_V1);
+        assert_eq!(decoded.extension, HeaderExtension::default());
+        assert_eq!(decoded.hash(), header.hash());
+
+        let mut bytes = header.encode();
+        bytes[1] = HEADER_VERSION + 1;
+        assert!(matches!(
+            BlockHeader::<u64, KeccakHasher>::from_bytes(&bytes),
+            Err(CodecError::InvalidValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_poseidon_header() {
+        use crate::hasher::PoseidonHasher;
+
+        let transactions = vec![TestTransaction {
+            data: b"transfer".to_vec(),
+        }];
+        let mut block = Block::new(
+            BlockHeader::<u64, PoseidonHasher>::new(
+                1,
+                PoseidonHasher::hash(b"state"),
+                PoseidonHasher::hash(b"parent"),
+                [0; 32],
+            ),
+            transactions,
+        );
+        block.header.transactions_root = block.compute_transactions_root();
+
+        let proof = block.transaction_proof(0).unwrap();
+        assert!(
+            block
+                .header
+                .verify_transaction(&block.transactions[0], &proof)
+        );
+        let decoded = Block::<BlockHeader<u64, PoseidonHasher>, TestTransaction>::from_bytes(
+            &block.to_bytes(),
+        )
+        .unwrap();
+        assert_eq!(decoded.header.hash(), block.header.hash());
+        assert_ne!(
+            block.header.hash(),
+            KeccakHasher::hash(&block.header.encode()).0
+        );
+    }
+
+    #[test]
+    fn test_rejects_header_of_other_hasher() {
+        use crate::hasher::Sha256Hasher;
+
+        let header = BlockHeader::<u64, Sha256Hasher>::new(
+            1,
+            Sha256Hasher::hash(b"state"),
+            Sha256Hasher::hash(b"parent"),
+            empty_root::<Sha256Hasher>(),
+        );
+        let encoded = header.encode();
+        assert_eq!(header.version(), HEADER_VERSION_V3);
+        assert_eq!(encoded[2], HasherKind::Sha256.id());
+        assert!(BlockHeader::<u64, Sha256Hasher>::from_bytes(&encoded).is_ok());
+        assert_eq!(
+            BlockHeader::<u64, KeccakHasher>::from_bytes(&encoded).err(),
+            Some(CodecError::InvalidValue(
+                "Header hashed with sha256, expected keccak".to_string()
+            ))
+        );
+
+        // Headers of earlier versions are hashed with Keccak
+        let v1 = BlockHeader::<u64, KeccakHasher>::new(
+            1,
+            KeccakHasher::hash(b"state"),
+            KeccakHasher::hash(b"parent"),
+            empty_root::<KeccakHasher>(),
+        );
+        assert_eq!(
+            BlockHeader::<u64, Sha256Hasher>::from_bytes(&v1.encode()).err(),
+            Some(CodecError::InvalidValue(
+                "Header hashed with keccak, expected sha256".to_string()
+            ))
+        );
+    }
 }
@@ -0,0 +1,528 @@
+//! Storage of the canonical chain of blocks, indexed by number and hash.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::hasher::KeccakHasher;
+use crate::receipt::Receipt;
+use crate::traits::{BlockHeaderT, BlockT, HasherT};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+
+type HashOf<B> = <<B as BlockT>::BlockHeader as BlockHeaderT>::Hash;
+/// Block stored together with the receipts of its transactions.
+type Record<B> = (B, Vec<Receipt>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockStoreError {
+    /// An Io error occurred.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Stored block could not be decoded
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    /// Inserted block does not extend the stored head
+    #[error("Expected block number {}, found {}", expected, found)]
+    NonContiguous { expected: u64, found: u64 },
+    /// Inserted block parent hash does not match the stored head
+    #[error("Block parent hash does not match the stored head")]
+    ParentMismatch,
+    /// Encoded block and receipts do not fit a record
+    #[error("Record of {} bytes is too large", _0)]
+    RecordTooLarge(usize),
+    /// A record in the middle of the log is corrupted
+    #[error("Corrupted record at offset {}", _0)]
+    Corrupted(u64),
+    /// Failed to acquire lock
+    #[error("Failed to acquire lock")]
+    LockError,
+}
+
+/// Canonical chain storage. Blocks are inserted in order, each extending the current head.
+pub trait BlockStore: Send + Sync {
+    type Block: BlockT;
+
+    /// Appends `block` and the receipts of its transactions on top of the current head.
+    fn insert(&self, block: Self::Block, receipts: Vec<Receipt>) -> Result<(), BlockStoreError>;
+
+    /// Returns the block with the given number.
+    fn block_by_number(&self, number: u64) -> Result<Option<Self::Block>, BlockStoreError>;
+
+    /// Returns the block with the given header hash.
+    fn block_by_hash(
+        &self,
+        hash: &HashOf<Self::Block>,
+    ) -> Result<Option<Self::Block>, BlockStoreError>;
+
+    /// Returns the latest block.
+    fn head(&self) -> Result<Option<Self::Block>, BlockStoreError>;
+
+    /// Returns the stored blocks with numbers in `range`, in ascending order.
+    fn range(&self, range: Range<u64>) -> Result<Vec<Self::Block>, BlockStoreError>;
+
+    /// Returns the receipts of the block with the given number.
+    fn receipts(&self, number: u64) -> Result<Option<Vec<Receipt>>, BlockStoreError>;
+}
+
+/// Checks that `block` extends `head`. Any block may start an empty store.
+fn check_extends<B: BlockT>(
+    head: Option<(u64, HashOf<B>)>,
+    block: &B,
+) -> Result<(), BlockStoreError> {
+    let Some((number, hash)) = head else {
+        return Ok(());
+    };
+    let found: u64 = (*block.header().number()).into();
+    if found != number + 1 {
+        return Err(BlockStoreError::NonContiguous {
+            expected: number + 1,
+            found,
+        });
+    }
+    if *block.header().parent_hash() != hash {
+        return Err(BlockStoreError::ParentMismatch);
+    }
+    Ok(())
+}
+
+struct MemoryInner<B: BlockT> {
+    blocks: BTreeMap<u64, B>,
+    receipts: HashMap<u64, Vec<Receipt>>,
+    by_hash: HashMap<HashOf<B>, u64>,
+}
+
+/// Block store kept in memory.
+pub struct InMemoryBlockStore<B: BlockT> {
+    inner: RwLock<MemoryInner<B>>,
+}
+
+impl<B: BlockT> Default for InMemoryBlockStore<B> {
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(MemoryInner {
+                blocks: BTreeMap::new(),
+                receipts: HashMap::new(),
+                by_hash: HashMap::new(),
+            }),
+        }
+    }
+}
+
+impl<B: BlockT> InMemoryBlockStore<B> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl<B: BlockT> BlockStore for InMemoryBlockStore<B> {
+    type Block = B;
+
+    fn insert(&self, block: B, receipts: Vec<Receipt>) -> Result<(), BlockStoreError> {
+        let mut inner = self.inner.write().map_err(|_| BlockStoreError::LockError)?;
+        let head = inner
+            .blocks
+            .last_key_value()
+            .map(|(number, block)| (*number, block.header().hash()));
+        check_extends(head, &block)?;
+
+        let number = (*block.header().number()).into();
+        inner.by_hash.insert(block.header().hash(), number);
+        inner.blocks.insert(number, block);
+        inner.receipts.insert(number, receipts);
+        Ok(())
+    }
+
+    fn block_by_number(&self, number: u64) -> Result<Option<B>, BlockStoreError> {
+        let inner = self.inner.read().map_err(|_| BlockStoreError::LockError)?;
+        Ok(inner.blocks.get(&number).cloned())
+    }
+
+    fn block_by_hash(&self, hash: &HashOf<B>) -> Result<Option<B>, BlockStoreError> {
+        let inner = self.inner.read().map_err(|_| BlockStoreError::LockError)?;
+        Ok(inner
+            .by_hash
+            .get(hash)
+            .and_then(|number| inner.blocks.get(number))
+            .cloned())
+    }
+
+    fn head(&self) -> Result<Option<B>, BlockStoreError> {
+        let inner = self.inner.read().map_err(|_| BlockStoreError::LockError)?;
+        Ok(inner
+            .blocks
+            .last_key_value()
+            .map(|(_, block)| block.clone()))
+    }
+
+    fn range(&self, range: Range<u64>) -> Result<Vec<B>, BlockStoreError> {
+        let inner = self.inner.read().map_err(|_| BlockStoreError::LockError)?;
+        Ok(inner.blocks.range(range).map(|(_, b)| b.clone()).collect())
+    }
+
+    fn receipts(&self, number: u64) -> Result<Option<Vec<Receipt>>, BlockStoreError> {
+        let inner = self.inner.read().map_err(|_| BlockStoreError::LockError)?;
+        Ok(inner.receipts.get(&number).cloned())
+    }
+}
+
+/// Name of the append-only log inside the store directory.
+const LOG_FILE: &str = "blocks.log";
+/// Length prefix and checksum around every record.
+const RECORD_OVERHEAD: usize = 4 + 4;
+
+struct FileIndex<B: BlockT> {
+    // Log opened for appending
+    writer: File,
+    // Offset of the record of every block number
+    offsets: BTreeMap<u64, u64>,
+    by_hash: HashMap<HashOf<B>, u64>,
+    head: Option<(u64, HashOf<B>)>,
+    end: u64,
+}
+
+impl<B: BlockT> FileIndex<B> {
+    fn index(&mut self, block: &B, offset: u64) {
+        let number = (*block.header().number()).into();
+        let hash = block.header().hash();
+        self.offsets.insert(number, offset);
+        self.by_hash.insert(hash, number);
+        self.head = Some((number, hash));
+    }
+}
+
+/// Block store persisted to an append-only log on disk.
+///
+/// Every record is `len (u32) || (block, receipts) || checksum (4 bytes)`. The index is rebuilt when the
+/// store is opened, and a torn record at the end of the log is discarded. An invalid record
+/// followed by valid ones is reported as corrupted rather than discarded.
+pub struct FileBlockStore<B: BlockT> {
+    index: RwLock<FileIndex<B>>,
+    reader: Mutex<File>,
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = KeccakHasher::hash(payload);
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+impl<B: BlockT> FileBlockStore<B> {
+    /// Opens the store in `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, BlockStoreError> {
+        std::fs::create_dir_all(dir.as_ref())?;
+        let path = dir.as_ref().join(LOG_FILE);
+        let writer = OpenOptions::new().append(true).create(true).open(&path)?;
+        let mut reader = File::open(&path)?;
+
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        let mut index = FileIndex {
+            writer,
+            offsets: BTreeMap::new(),
+            by_hash: HashMap::new(),
+            head: None,
+            end: 0,
+        };
+        let mut offset = 0;
+        while offset < data.len() {
+            match Self::parse_record(&data[offset..]) {
+                Some(record) => {
+                    let ((block, _), len) = record?;
+                    index.index(&block, offset as u64);
+                    offset += len;
+                }
+                None => {
+                    // Only the last record may be torn by a crash during append, a corrupted
+                    // length may also claim to run past the end of the log
+                    let rest = &data[offset..];
+                    if Self::record_end(rest) < rest.len() || Self::contains_record(&rest[1..]) {
+                        return Err(BlockStoreError::Corrupted(offset as u64));
+                    }
+                    index.writer.set_len(offset as u64)?;
+                    index.writer.sync_all()?;
+                    break;
+                }
+            }
+        }
+        index.end = offset as u64;
+        Ok(Self {
+            index: RwLock::new(index),
+            reader: Mutex::new(reader),
+        })
+    }
+
+    /// Parses the record at the start of `data`. Returns `None` if it is incomplete or its
+    /// checksum does not match.
+    fn parse_record(data: &[u8]) -> Option<Result<(Record<B>, usize), BlockStoreError>> {
+        let len = u32::from_le_bytes(data.get(..4)?.try_into().ok()?) as usize;
+        let payload = data.get(4..4 + len)?;
+        let sum = data.get(4 + len..8 + len)?;
+        if sum != checksum(payload) {
+            return None;
+        }
+        Some(
+            Record::<B>::from_bytes(payload)
+                .map(|record| (record, len + RECORD_OVERHEAD))
+                .map_err(Into::into),
+        )
+    }
+
+    /// Returns whether a valid record starts anywhere in `data`.
+    fn contains_record(data: &[u8]) -> bool {
+        (0..data.len()).any(|start| matches!(Self::parse_record(&data[start..]), Some(Ok(_))))
+    }
+
+    /// Returns the length the record at the start of `data` claims to have.
+    fn record_end(data: &[u8]) -> usize {
+        data.get(..4)
+            .map(|len| u32::from_le_bytes(len.try_into().expect("4 bytes")) as usize)
+            .map_or(data.len(), |len| len + RECORD_OVERHEAD)
+    }
+
+    fn read_at(&self, offset: u64) -> Result<Record<B>, BlockStoreError> {
+        let mut file = self.reader.lock().map_err(|_| BlockStoreError::LockError)?;
+        file.seek(SeekFrom::Start(offset))?;
+        let mut len = [0u8; 4];
+        file.read_exact(&mut len)?;
+        let mut payload = vec![0u8; u32::from_le_bytes(len) as usize];
+        file.read_exact(&mut payload)?;
+        Ok(Record::<B>::from_bytes(&payload)?)
+    }
+}
+
+impl<B: BlockT> FileBlockStore<B> {
+    fn record(&self, number: u64) -> Result<Option<Record<B>>, BlockStoreError> {
+        let offset = {
+            let index = self.index.read().map_err(|_| BlockStoreError::LockError)?;
+            index.offsets.get(&number).copied()
+        };
+        offset.map(|offset| self.read_at(offset)).transpose()
+    }
+}
+
+impl<B: BlockT> BlockStore for FileBlockStore<B> {
+    type Block = B;
+
+    fn insert(&self, block: B, receipts: Vec<Receipt>) -> Result<(), BlockStoreError> {
+        let mut index = self.index.write().map_err(|_| BlockStoreError::LockError)?;
+        check_extends(index.head, &block)?;
+
+        let payload = (&block, &receipts).to_bytes();
+        let len = u32::try_from(payload.len())
+            .map_err(|_| BlockStoreError::RecordTooLarge(payload.len()))?;
+        let mut record = Vec::with_capacity(payload.len() + RECORD_OVERHEAD);
+        len.encode_to(&mut record);
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&checksum(&payload));
+        let written = index
+            .writer
+            .write_all(&record)
+            .and_then(|()| index.writer.sync_data());
+        if let Err(e) = written {
+            // A partly written record is cut off, so that the next one starts at the end of
+            // the log the index knows
+            let end = index.end;
+            index.writer.set_len(end)?;
+            index.writer.seek(SeekFrom::Start(end))?;
+            return Err(e.into());
+        }
+
+        let offset = index.end;
+        index.end += record.len() as u64;
+        index.index(&block, offset);
+        Ok(())
+    }
+
+    fn block_by_number(&self, number: u64) -> Result<Option<B>, BlockStoreError> {
+        let record = self.record(number)?;
+        Ok(record.map(|(block, _)| block))
+    }
+
+    fn block_by_hash(&self, hash: &HashOf<B>) -> Result<Option<B>, BlockStoreError> {
+        let number = {
+            let index = self.index.read().map_err(|_| BlockStoreError::LockError)?;
+            index.by_hash.get(hash).copied()
+        };
+        match number {
+            Some(number) => self.block_by_number(number),
+            None => Ok(None),
+        }
+    }
+
+    fn head(&self) -> Result<Option<B>, BlockStoreError> {
+        let head = {
+            let index = self.index.read().map_err(|_| BlockStoreError::LockError)?;
+            index.head.map(|(number, _)| number)
+        };
+        match head {
+            Some(number) => self.block_by_number(number),
+            None => Ok(None),
+        }
+    }
+
+    fn range(&self, range: Range<u64>) -> Result<Vec<B>, BlockStoreError> {
+        let offsets: Vec<u64> = {
+            let index = self.index.read().map_err(|_| BlockStoreError::LockError)?;
+            index.offsets.range(range).map(|(_, o)| *o).collect()
+        };
+        offsets
+            .into_iter()
+            .map(|o| self.read_at(o).map(|(block, _)| block))
+            .collect()
+    }
+
+    fn receipts(&self, number: u64) -> Result<Option<Vec<Receipt>>, BlockStoreError> {
+        let record = self.record(number)?;
+        Ok(record.map(|(_, receipts)| receipts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestBlock, child_block, test_chain};
+
+    fn check_store(store: &impl BlockStore<Block = TestBlock>) {
+        assert!(store.head().unwrap().is_none());
+        let chain = test_chain(5);
+        for block in &chain {
+            let receipts = vec![Receipt::success(block.header.number)];
+            store.insert(block.clone(), receipts).unwrap();
+        }
+
+        assert_eq!(
+            store.head().unwrap().unwrap().header.hash(),
+            chain[4].header.hash()
+        );
+        for block in &chain {
+            let hash = block.header.hash();
+            let by_number = store.block_by_number(block.header.number).unwrap().unwrap();
+            let by_hash = store.block_by_hash(&hash).unwrap().unwrap();
+            assert_eq!(by_number.header.hash(), hash);
+            assert_eq!(by_hash.to_bytes(), block.to_bytes());
+        }
+        assert!(store.block_by_number(5).unwrap().is_none());
+        assert_eq!(store.receipts(3).unwrap(), Some(vec![Receipt::success(3)]));
+        assert!(store.receipts(5).unwrap().is_none());
+        assert!(store.block_by_hash(&[1; 32]).unwrap().is_none());
+
+        let range: Vec<u64> = store
+            .range(1..3)
+            .unwrap()
+            .iter()
+            .map(|b| b.header.number)
+            .collect();
+        assert_eq!(range, vec![1, 2]);
+        assert_eq!(store.range(3..100).unwrap().len(), 2);
+
+        // Blocks must extend the head
+        assert!(matches!(
+            store.insert(chain[2].clone(), vec![]),
+            Err(BlockStoreError::NonContiguous {
+                expected: 5,
+                found: 2
+            })
+        ));
+        let orphan = child_block(&chain[3].header, b"fork", vec![]);
+        let mut orphan = child_block(&orphan.header, b"orphan", vec![]);
+        orphan.header.number = 5;
+        assert!(matches!(
+            store.insert(orphan, vec![]),
+            Err(BlockStoreError::ParentMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_in_memory_store() {
+        check_store(&InMemoryBlockStore::new());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir = tempfile::tempdir().unwrap();
+        check_store(&FileBlockStore::open(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn test_file_store_survives_reopen() {
+        let dir = tempfile::tempdir().unwrap();
+        let chain = test_chain(4);
+        {
+            let store = FileBlockStore::<TestBlock>::open(dir.path()).unwrap();
+            for block in &chain[..3] {
+                store.insert(block.clone(), vec![]).unwrap();
+            }
+        }
+
+        let store = FileBlockStore::<TestBlock>::open(dir.path()).unwrap();
+        assert_eq!(store.head().unwrap().unwrap().header.number, 2);
+        assert_eq!(
+            store
+                .block_by_hash(&chain[1].header.hash())
+                .unwrap()
+                .unwrap()
+                .header
+                .number,
+            1
+        );
+        store.insert(chain[3].clone(), vec![]).unwrap();
+        assert_eq!(store.range(0..10).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_file_store_discards_torn_record() {
+        let dir = tempfile::tempdir().unwrap();
+        let chain = test_chain(3);
+        {
+            let store = FileBlockStore::<TestBlock>::open(dir.path()).unwrap();
+            for block in &chain[..2] {
+                store.insert(block.clone(), vec![]).unwrap();
+            }
+        }
+        // Simulate a crash in the middle of appending the third block
+        let path = dir.path().join(LOG_FILE);
+        let len = std::fs::metadata(&path).unwrap().len();
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(&[0xff, 0, 0, 0, 1, 2]).unwrap();
+        drop(file);
+
+        let store = FileBlockStore::<TestBlock>::open(dir.path()).unwrap();
+        assert_eq!(store.head().unwrap().unwrap().header.number, 1);
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), len);
+        store.insert(chain[2].clone(), vec![]).unwrap();
+        assert_eq!(store.head().unwrap().unwrap().header.number, 2);
+    }
+
+    #[test]
+    fn test_file_store_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let store = FileBlockStore::<TestBlock>::open(dir.path()).unwrap();
+            for block in test_chain(3) {
+                store.insert(block, vec![]).unwrap();
+            }
+        }
+        // Flip a byte inside the first record
+        let path = dir.path().join(LOG_FILE);
+        let mut data = std::fs::read(&path).unwrap();
+        data[10] ^= 0xff;
+        std::fs::write(&path, &data).unwrap();
+
+        assert!(matches!(
+            FileBlockStore::<TestBlock>::open(dir.path()),
+            Err(BlockStoreError::Corrupted(0))
+        ));
+
+        // A corrupted length running past the end of the log does not discard the records
+        // after it
+        data[10] ^= 0xff;
+        data[..4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            FileBlockStore::<TestBlock>::open(dir.path()),
+            Err(BlockStoreError::Corrupted(0))
+        ));
+        assert_eq!(std::fs::metadata(&path).unwrap().len(), data.len() as u64);
+    }
+}
@@ -0,0 +1,542 @@
+//! Block production on top of a [`StateTransitionFunction`].
+use crate::block::{HeaderError, HeaderExtension};
+use crate::codec::Encode;
+use crate::fraud_proof::{IntermediateRoot, intermediate_roots_root};
+use crate::merkle;
+use crate::metering::GasMeter;
+use crate::receipt::{Receipt, receipts_root};
+use crate::traits::{
+    AppState, BlockHeaderT, BlockT, RevertibleState, StateTransitionFunction, TransactionOf,
+};
+use alloy::primitives::B256;
+use std::marker::PhantomData;
+
+/// Sealed block together with the receipts of its transactions.
+#[derive(Debug)]
+pub struct BuiltBlock<S: StateTransitionFunction> {
+    pub block: S::Block,
+    pub receipts: Vec<Receipt>,
+    /// State after each transaction
+    pub intermediate_roots: Vec<IntermediateRoot>,
+    /// Candidates rejected by the state transition function, by index, with their errors
+    pub rejected: Vec<(usize, S::Error)>,
+    /// Number of leading candidates either included or rejected
+    pub consumed: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct BlockBuilderConfig {
+    /// Maximum number of transactions in a block
+    pub max_transactions: usize,
+    /// Maximum total size of the encoded transactions in a block
+    pub max_block_bytes: usize,
+}
+
+impl Default for BlockBuilderConfig {
+    fn default() -> Self {
+        Self {
+            max_transactions: 1_000,
+            max_block_bytes: 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BlockBuilderError<E> {
+    /// Next block number does not fit the header number type
+    #[error("Block number overflow after {}", _0)]
+    NumberOverflow(u64),
+    /// A single transaction is larger than the block size limit
+    #[error(
+        "Transaction of {} bytes exceeds the block limit of {} bytes",
+        size,
+        max
+    )]
+    TransactionTooLarge { size: usize, max: usize },
+    /// Header extension is invalid
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+    /// A block hook changed the state root, which fraud proofs cannot account for
+    #[error("Block hook changed the state root")]
+    StateChangedByHook,
+    /// The state transition function rejected the block
+    #[error(transparent)]
+    StateTransition(E),
+}
+
+/// Builds sealed blocks from pending transactions.
+#[derive(Debug, Clone)]
+pub struct BlockBuilder<S> {
+    config: BlockBuilderConfig,
+    _stf: PhantomData<S>,
+}
+
+impl<S> BlockBuilder<S>
+where
+    S: StateTransitionFunction,
+    S::State: RevertibleState,
+    <S::BlockHeader as BlockHeaderT>::Hash: From<[u8; 32]> + Into<[u8; 32]>,
+{
+    pub fn new(config: BlockBuilderConfig) -> Self {
+        Self {
+            config,
+            _stf: PhantomData,
+        }
+    }
+
+    pub fn config(&self) -> &BlockBuilderConfig {
+        &self.config
+    }
+
+    /// Builds the child block of `parent` from `candidates`, applied in order until the
+    /// configured limits or the block gas limit are reached.
+    ///
+    /// Each candidate is applied under its own checkpoint. Candidates rejected by the state
+    /// transition function are left out of the block and their changes reverted, except for
+    /// a candidate running out of the block gas, which ends the block and is not consumed.
+    /// `state` is rolled back if the block cannot be built.
+    ///
+    /// The block hooks see the unsealed header, as they do when the block is applied, see
+    /// [`BlockHeaderT::unsealed`], and may not change the state root. The sealed header
+    /// commits to the receipts of the block and to the state after each transaction, which
+    /// are returned with it, and to the withdrawals of the block if the application has any.
+    pub fn build(
+        &self,
+        parent: &S::BlockHeader,
+        state: &mut S::State,
+        candidates: &[TransactionOf<S>],
+        extension: Option<HeaderExtension>,
+    ) -> Result<BuiltBlock<S>, BlockBuilderError<S::Error>> {
+        let checkpoint = state.checkpoint();
+        let built = self.build_on(parent, state, candidates, extension);
+        match built {
+            Ok(_) => state.commit(checkpoint),
+            Err(_) => state.revert(checkpoint),
+        }
+        built
+    }
+
+    fn build_on(
+        &self,
+        parent: &S::BlockHeader,
+        state: &mut S::State,
+        candidates: &[TransactionOf<S>],
+        extension: Option<HeaderExtension>,
+    ) -> Result<BuiltBlock<S>, BlockBuilderError<S::Error>> {
+        let parent_number: u64 = (*parent.number()).into();
+        let number = parent_number
+            .checked_add(1)
+            .and_then(|n| <S::BlockHeader as BlockHeaderT>::Number::try_from(n).ok())
+            .ok_or(BlockBuilderError::NumberOverflow(parent_number))?;
+        let extension = extension.unwrap_or_default();
+        let empty_root = merkle::empty_root::<<S::BlockHeader as BlockHeaderT>::Hashing>();
+        let unsealed = S::BlockHeader::new(number, empty_root, parent.hash(), empty_root)
+            .with_extension(extension.clone())?
+            .unsealed();
+
+        let mut meter = GasMeter::new(S::block_gas_limit(state));
+        let parent_root = state.state_root();
+        S::begin_block(state, &unsealed).map_err(BlockBuilderError::StateTransition)?;
+        if state.state_root() != parent_root {
+            return Err(BlockBuilderError::StateChangedByHook);
+        }
+
+        let mut transactions = Vec::new();
+        let mut receipts = Vec::new();
+        let mut intermediate_roots = Vec::new();
+        let mut rejected = Vec::new();
+        let mut consumed = 0;
+        let mut size = 0;
+        for (index, tx) in candidates.iter().enumerate() {
+            if transactions.len() == self.config.max_transactions {
+                break;
+            }
+            let tx_size = tx.to_bytes().len();
+            if tx_size > self.config.max_block_bytes && transactions.is_empty() {
+                return Err(BlockBuilderError::TransactionTooLarge {
+                    size: tx_size,
+                    max: self.config.max_block_bytes,
+                });
+            }
+            if size + tx_size > self.config.max_block_bytes {
+                break;
+            }
+
+            let checkpoint = state.checkpoint();
+            let mut attempt_meter = meter;
+            let (receipt, used) =
+                attempt_meter.scoped(|meter| S::apply_transaction(state, tx, meter));
+            match receipt {
+                Ok(receipt) => {
+                    state.commit(checkpoint);
+                    meter = attempt_meter;
+                    size += tx_size;
+                    transactions.push(tx.clone());
+                    receipts.push(Receipt {
+                        resources_used: used,
+                        ..receipt
+                    });
+                    intermediate_roots.push(IntermediateRoot {
+                        state_root: state.state_root(),
+                        gas_used: meter.used(),
+                    });
+                }
+                // The candidate ran out of gas and may fit in a later block
+                Err(_) if attempt_meter.is_out_of_gas() && !transactions.is_empty() => {
+                    state.revert(checkpoint);
+                    break;
+                }
+                Err(error) => {
+                    state.revert(checkpoint);
+                    rejected.push((index, error));
+                }
+            }
+            consumed = index + 1;
+        }
+        let last_root = state.state_root();
+        S::end_block(state, &unsealed).map_err(BlockBuilderError::StateTransition)?;
+        if state.state_root() != last_root {
+            return Err(BlockBuilderError::StateChangedByHook);
+        }
+
+        let leaves: Vec<Vec<u8>> = transactions.iter().map(|tx| tx.to_bytes()).collect();
+        let transactions_root =
+            merkle::merkle_root::<<S::BlockHeader as BlockHeaderT>::Hashing, _>(&leaves);
+        let receipts_root: [u8; 32] =
+            receipts_root::<<S::BlockHeader as BlockHeaderT>::Hashing>(&receipts).into();
+        let intermediate_roots_root: [u8; 32] = intermediate_roots_root::<
+            <S::BlockHeader as BlockHeaderT>::Hashing,
+        >(&intermediate_roots)
+        .into();
+        let header = S::BlockHeader::new(
+            number,
+            state.state_root().into(),
+            parent.hash(),
+            transactions_root,
+        )
+        .with_extension(HeaderExtension {
+            receipts_root: Some(B256::from(receipts_root)),
+            intermediate_roots_root: Some(B256::from(intermediate_roots_root)),
+            withdrawals_root: S::withdrawals_root(&transactions),
+            deposits_hash: S::deposits_hash(state),
+            ..extension
+        })?;
+        Ok(BuiltBlock {
+            block: S::Block::new(header, transactions),
+            receipts,
+            intermediate_roots,
+            rejected,
+            consumed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::{Block, BlockHeader};
+    use crate::codec::{CodecError, Decode};
+    use crate::hasher::KeccakHasher;
+    use crate::merkle::empty_root;
+    use crate::metering::{Gas, OutOfGas};
+    use crate::test_utils::TestState;
+    use crate::traits::SignedTransactionT;
+
+    #[derive(Clone, Debug)]
+    struct Add(u64);
+
+    impl Encode for Add {
+        fn encode_to(&self, out: &mut Vec<u8>) {
+            self.0.encode_to(out);
+        }
+    }
+
+    impl Decode for Add {
+        fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+            Ok(Add(u64::decode_from(input)?))
+        }
+    }
+
+    impl SignedTransactionT for Add {}
+
+    /// Single counter, stored at key 0.
+    type Counter = TestState<u8, u64>;
+
+    fn counter(total: u64) -> Counter {
+        TestState([(0, total)].into())
+    }
+
+    fn total(state: &Counter) -> u64 {
+        state.0.get(&0).copied().unwrap_or_default()
+    }
+
+    #[derive(Debug, thiserror::Error)]
+    enum CounterError {
+        #[error("Counter overflow")]
+        Overflow,
+        #[error(transparent)]
+        OutOfGas(#[from] OutOfGas),
+    }
+
+    struct CounterStf;
+
+    thread_local! {
+        /// Hashes of the headers passed to `end_block` on the test thread
+        static HOOK_HEADERS: std::cell::RefCell<Vec<[u8; 32]>> = const { std::cell::RefCell::new(Vec::new()) };
+    }
+
+    impl StateTransitionFunction for CounterStf {
+        type State = Counter;
+        type Error = CounterError;
+        type BlockHeader = BlockHeader<u64, KeccakHasher>;
+        type Block = Block<Self::BlockHeader, Add>;
+
+        fn validate_block(_state: &Self::State, _block: &Self::Block) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn block_gas_limit(_state: &Self::State) -> Gas {
+            10
+        }
+
+        // Adding `n` costs `n` gas
+        fn apply_transaction(
+            state: &mut Self::State,
+            transaction: &Add,
+            meter: &mut GasMeter,
+        ) -> Result<Receipt, Self::Error> {
+            meter.consume(transaction.0)?;
+            let total = total(state)
+                .checked_add(transaction.0)
+                .ok_or(CounterError::Overflow)?;
+            state.0.insert(0, total);
+            Ok(Receipt::success(transaction.0))
+        }
+
+        // Records the unsealed header, and writes to the state if its extra data asks to
+        fn end_block(
+            state: &mut Self::State,
+            header: &Self::BlockHeader,
+        ) -> Result<(), Self::Error> {
+            HOOK_HEADERS.with_borrow_mut(|headers| headers.push(header.hash()));
+            if header.extension.extra_data == b"write" {
+                state.0.insert(1, 1);
+            }
+            Ok(())
+        }
+    }
+
+    fn genesis(state: &Counter) -> BlockHeader<u64, KeccakHasher> {
+        BlockHeader::new(0, state.state_root(), [0; 32], empty_root::<KeccakHasher>())
+    }
+
+    #[test]
+    fn test_build_links_and_seals_block() {
+        let mut state = Counter::default();
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+
+        let BuiltBlock {
+            block, receipts, ..
+        } = builder
+            .build(&parent, &mut state, &[Add(1), Add(2)], None)
+            .unwrap();
+        assert_eq!(total(&state), 3);
+        assert_eq!(receipts.len(), 2);
+        assert_eq!(receipts[1].resources_used, 2);
+        assert_eq!(
+            crate::validation::validate_receipts(&block.header, &receipts),
+            Ok(())
+        );
+        assert_eq!(block.header.number, 1);
+        assert_eq!(block.header.parent_hash, parent.hash());
+        assert_eq!(block.header.state_root, state.state_root());
+        assert_eq!(
+            block.header.transactions_root,
+            block.compute_transactions_root()
+        );
+
+        let child = builder
+            .build(&block.header, &mut state, &[], None)
+            .unwrap()
+            .block;
+        assert_eq!(child.header.number, 2);
+        assert_eq!(child.header.parent_hash, block.header.hash());
+    }
+
+    #[test]
+    fn test_build_enforces_limits() {
+        let mut state = Counter::default();
+        let parent = genesis(&state);
+        let candidates: Vec<_> = (0..10).map(Add).collect();
+
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig {
+            max_transactions: 4,
+            ..Default::default()
+        });
+        let block = builder
+            .build(&parent, &mut state, &candidates, None)
+            .unwrap()
+            .block;
+        assert_eq!(block.transactions.len(), 4);
+
+        // Each encoded transaction is 9 bytes
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig {
+            max_transactions: 10,
+            max_block_bytes: 20,
+        });
+        let block = builder
+            .build(&parent, &mut state, &candidates, None)
+            .unwrap()
+            .block;
+        assert_eq!(block.transactions.len(), 2);
+
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig {
+            max_transactions: 10,
+            max_block_bytes: 8,
+        });
+        assert!(matches!(
+            builder.build(&parent, &mut state, &candidates, None),
+            Err(BlockBuilderError::TransactionTooLarge { size: 9, max: 8 })
+        ));
+    }
+
+    #[test]
+    fn test_build_skips_rejected_transactions() {
+        let mut state = counter(u64::MAX - 2);
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+
+        let built = builder
+            .build(&parent, &mut state, &[Add(1), Add(2), Add(1)], None)
+            .unwrap();
+        assert_eq!(built.block.transactions.len(), 2);
+        assert_eq!(built.consumed, 3);
+        assert!(matches!(
+            built.rejected.as_slice(),
+            [(1, CounterError::Overflow)]
+        ));
+        assert_eq!(total(&state), u64::MAX);
+        // The rejected transaction is not charged to the block
+        let used: Gas = built.receipts.iter().map(|r| r.resources_used).sum();
+        assert_eq!(used, 2);
+    }
+
+    #[test]
+    fn test_build_enforces_gas_limit() {
+        let mut state = Counter::default();
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+
+        // The third candidate does not fit in the 10 gas left by the first two
+        let built = builder
+            .build(&parent, &mut state, &[Add(4), Add(5), Add(2), Add(1)], None)
+            .unwrap();
+        assert_eq!(built.block.transactions.len(), 2);
+        assert_eq!(built.consumed, 2);
+        assert!(built.rejected.is_empty());
+
+        // A candidate exceeding the limit of an empty block never fits
+        let built = builder
+            .build(&parent, &mut state, &[Add(11), Add(1)], None)
+            .unwrap();
+        assert_eq!(built.block.transactions.len(), 1);
+        assert!(matches!(
+            built.rejected.as_slice(),
+            [(0, CounterError::OutOfGas(_))]
+        ));
+    }
+
+    #[test]
+    fn test_build_rejects_failure_using_all_gas() {
+        let mut state = counter(u64::MAX - 4);
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+
+        // The second candidate uses the last of the gas but fails on its own
+        let built = builder
+            .build(&parent, &mut state, &[Add(4), Add(6), Add(0)], None)
+            .unwrap();
+        assert_eq!(built.block.transactions.len(), 2);
+        assert_eq!(built.consumed, 3);
+        assert!(matches!(
+            built.rejected.as_slice(),
+            [(1, CounterError::Overflow)]
+        ));
+    }
+
+    #[test]
+    fn test_apply_block_meters_transactions() {
+        let mut state = Counter::default();
+        let parent = genesis(&state);
+        let block = |transactions| {
+            Block::new(
+                BlockHeader::new(1, [0; 32], parent.hash(), [0; 32]),
+                transactions,
+            )
+        };
+
+        let receipts = CounterStf::apply_block(&mut state, &block(vec![Add(3), Add(7)])).unwrap();
+        assert_eq!(receipts[0].resources_used, 3);
+        assert_eq!(receipts[1].resources_used, 7);
+        assert!(matches!(
+            CounterStf::apply_block(&mut state, &block(vec![Add(3), Add(8)])),
+            Err(CounterError::OutOfGas(_))
+        ));
+    }
+
+    #[test]
+    fn test_hooks_see_the_same_header_when_applied() {
+        let mut state = counter(1);
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+        let extension = HeaderExtension {
+            timestamp: Some(42),
+            ..Default::default()
+        };
+
+        let mut applied = state.clone();
+        let block = builder
+            .build(&parent, &mut state, &[Add(1), Add(2)], Some(extension))
+            .unwrap()
+            .block;
+        CounterStf::apply_block(&mut applied, &block).unwrap();
+        let headers = HOOK_HEADERS.take();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0], headers[1]);
+    }
+
+    #[test]
+    fn test_build_refuses_hooks_changing_state() {
+        let mut state = counter(1);
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+        let extension = HeaderExtension {
+            extra_data: b"write".to_vec(),
+            ..Default::default()
+        };
+
+        assert!(matches!(
+            builder.build(&parent, &mut state, &[Add(1)], Some(extension)),
+            Err(BlockBuilderError::StateChangedByHook)
+        ));
+        assert_eq!(state, counter(1));
+    }
+
+    #[test]
+    fn test_build_with_extension() {
+        let mut state = Counter::default();
+        let parent = genesis(&state);
+        let builder = BlockBuilder::<CounterStf>::new(BlockBuilderConfig::default());
+        let extension = HeaderExtension {
+            timestamp: Some(42),
+            ..Default::default()
+        };
+
+        let block = builder
+            .build(&parent, &mut state, &[Add(1)], Some(extension))
+            .unwrap()
+            .block;
+        assert_eq!(block.header.timestamp(), Some(42));
+    }
+}
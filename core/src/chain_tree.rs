@@ -0,0 +1,568 @@
+//! Tree of block headers tracking competing branches above a finalized root.
+use crate::block::L1Origin;
+use crate::traits::BlockHeaderT;
+use std::collections::{HashMap, HashSet, VecDeque};
+
+type HashOf<H> = <H as BlockHeaderT>::Hash;
+
+/// Default maximum number of headers waiting for their parent.
+pub const DEFAULT_MAX_ORPHANS: usize = 1024;
+/// Default maximum distance of a header waiting for its parent past the best tip.
+pub const DEFAULT_MAX_ORPHAN_DISTANCE: u64 = 256;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ChainTreeError {
+    /// Header number does not follow its parent
+    #[error("Expected block number {}, found {}", expected, found)]
+    InvalidNumber { expected: u64, found: u64 },
+    /// Header is not above the finalized root
+    #[error("Block #{} is not above the finalized root", _0)]
+    BelowRoot(u64),
+    /// Block is not part of the tree
+    #[error("Unknown block")]
+    UnknownBlock,
+    /// Header with an unknown parent is too far past the best tip to be kept
+    #[error("Block #{} is too far past the best tip", _0)]
+    TooFarAhead(u64),
+}
+
+/// Rule selecting the canonical tip among the branches of the tree.
+pub trait ForkChoice<H: BlockHeaderT> {
+    type Score: Ord;
+
+    /// Scores the branch `headers`, ordered from the root to the tip. The branch with the
+    /// highest score is canonical; `None` marks the branch as ineligible.
+    fn score(&self, headers: &[&H]) -> Option<Self::Score>;
+}
+
+/// Prefers the branch with the highest tip number.
+#[derive(Debug, Clone, Default)]
+pub struct LongestChain;
+
+impl<H: BlockHeaderT> ForkChoice<H> for LongestChain {
+    type Score = u64;
+
+    fn score(&self, headers: &[&H]) -> Option<u64> {
+        headers.last().map(|tip| (*tip.number()).into())
+    }
+}
+
+/// Only considers branches whose L1 origins are all canonical on L1, and prefers the most
+/// recent L1 origin, then the highest tip number.
+///
+/// `is_canonical` reflects the current L1 view; call [`ChainTree::reevaluate`] when it
+/// changes, e.g. after an L1 reorg.
+#[derive(Debug, Clone)]
+pub struct L1Anchored<F> {
+    is_canonical: F,
+}
+
+impl<F: Fn(&L1Origin) -> bool> L1Anchored<F> {
+    pub fn new(is_canonical: F) -> Self {
+        Self { is_canonical }
+    }
+}
+
+impl<H: BlockHeaderT, F: Fn(&L1Origin) -> bool> ForkChoice<H> for L1Anchored<F> {
+    type Score = (u64, u64);
+
+    fn score(&self, headers: &[&H]) -> Option<(u64, u64)> {
+        let mut l1_number = 0;
+        for header in headers {
+            if let Some(origin) = header.l1_origin() {
+                if !(self.is_canonical)(origin) {
+                    return None;
+                }
+                l1_number = l1_number.max(origin.number);
+            }
+        }
+        let tip = headers.last()?;
+        Some((l1_number, (*tip.number()).into()))
+    }
+}
+
+/// Change of the canonical chain. A plain extension of the chain has no reverted blocks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReorgEvent<Hash> {
+    /// Blocks removed from the canonical chain, from the old tip downwards
+    pub reverted: Vec<Hash>,
+    /// Blocks added to the canonical chain, from the common ancestor upwards
+    pub applied: Vec<Hash>,
+}
+
+impl<Hash> ReorgEvent<Hash> {
+    /// Returns whether the event only extends the previous canonical chain.
+    pub fn is_extension(&self) -> bool {
+        self.reverted.is_empty()
+    }
+}
+
+/// Headers connected to the finalized root, plus headers still waiting for their parent.
+pub struct ChainTree<H: BlockHeaderT, F> {
+    fork_choice: F,
+    root: HashOf<H>,
+    headers: HashMap<HashOf<H>, H>,
+    children: HashMap<HashOf<H>, Vec<HashOf<H>>>,
+    // Headers whose parent is unknown, keyed by parent hash
+    orphans: HashMap<HashOf<H>, Vec<H>>,
+    // Parent and hash of every orphan, oldest first
+    orphan_order: VecDeque<(HashOf<H>, HashOf<H>)>,
+    max_orphans: usize,
+    max_orphan_distance: u64,
+    tips: HashSet<HashOf<H>>,
+    best: HashOf<H>,
+}
+
+impl<H, F> ChainTree<H, F>
+where
+    H: BlockHeaderT,
+    F: ForkChoice<H>,
+{
+    /// Creates a tree rooted at the finalized header `root`.
+    pub fn new(root: H, fork_choice: F) -> Self {
+        let hash = root.hash();
+        Self {
+            fork_choice,
+            root: hash,
+            headers: HashMap::from([(hash, root)]),
+            children: HashMap::new(),
+            orphans: HashMap::new(),
+            orphan_order: VecDeque::new(),
+            max_orphans: DEFAULT_MAX_ORPHANS,
+            max_orphan_distance: DEFAULT_MAX_ORPHAN_DISTANCE,
+            tips: HashSet::from([hash]),
+            best: hash,
+        }
+    }
+
+    /// Keeps at most `max_orphans` headers waiting for their parent, evicting the oldest
+    /// ones, and none more than `max_distance` blocks past the best tip.
+    pub fn with_orphan_limits(mut self, max_orphans: usize, max_distance: u64) -> Self {
+        self.max_orphans = max_orphans;
+        self.max_orphan_distance = max_distance;
+        self
+    }
+
+    /// Returns the finalized root header.
+    pub fn root(&self) -> &H {
+        &self.headers[&self.root]
+    }
+
+    /// Returns the tip of the canonical chain.
+    pub fn best(&self) -> &H {
+        &self.headers[&self.best]
+    }
+
+    /// Returns the tips of all known branches.
+    pub fn tips(&self) -> impl Iterator<Item = &H> {
+        self.tips.iter().map(|hash| &self.headers[hash])
+    }
+
+    /// Returns a header connected to the tree.
+    pub fn get(&self, hash: &HashOf<H>) -> Option<&H> {
+        self.headers.get(hash)
+    }
+
+    /// Returns the number of headers waiting for their parent.
+    pub fn orphan_count(&self) -> usize {
+        self.orphan_order.len()
+    }
+
+    /// Returns whether `hash` is on the canonical chain.
+    pub fn is_canonical(&self, hash: &HashOf<H>) -> bool {
+        let Some(header) = self.headers.get(hash) else {
+            return false;
+        };
+        let number: u64 = (*header.number()).into();
+        self.ancestors(&self.best)
+            .find(|h| (*h.number()).into() <= number)
+            .is_some_and(|h| h.hash() == *hash)
+    }
+
+    /// Inserts `header`. Headers whose parent is not known yet are kept until it arrives,
+    /// within the orphan limits of the tree.
+    ///
+    /// Returns the change of the canonical chain caused by the insertion, if any.
+    pub fn insert(&mut self, header: H) -> Result<Option<ReorgEvent<HashOf<H>>>, ChainTreeError> {
+        let hash = header.hash();
+        let number: u64 = (*header.number()).into();
+        let root_number: u64 = (*self.root().number()).into();
+        if number <= root_number {
+            return Err(ChainTreeError::BelowRoot(number));
+        }
+        if self.headers.contains_key(&hash) {
+            return Ok(None);
+        }
+
+        let Some(parent) = self.headers.get(header.parent_hash()) else {
+            self.insert_orphan(header)?;
+            return Ok(None);
+        };
+        let expected = (*parent.number()).into() + 1;
+        if number != expected {
+            return Err(ChainTreeError::InvalidNumber {
+                expected,
+                found: number,
+            });
+        }
+
+        self.connect(header);
+        Ok(self.reevaluate())
+    }
+
+    /// Keeps `header`, whose parent is unknown, evicting the oldest orphans past the limit.
+    fn insert_orphan(&mut self, header: H) -> Result<(), ChainTreeError> {
+        let number: u64 = (*header.number()).into();
+        let best_number: u64 = (*self.best().number()).into();
+        if number > best_number.saturating_add(self.max_orphan_distance) {
+            return Err(ChainTreeError::TooFarAhead(number));
+        }
+        let hash = header.hash();
+        let parent_hash = *header.parent_hash();
+        let waiting = self.orphans.entry(parent_hash).or_default();
+        if waiting.iter().any(|h| h.hash() == hash) {
+            return Ok(());
+        }
+        waiting.push(header);
+        self.orphan_order.push_back((parent_hash, hash));
+        while self.orphan_order.len() > self.max_orphans {
+            let (parent_hash, hash) = self.orphan_order.pop_front().expect("over the limit");
+            if let Some(waiting) = self.orphans.get_mut(&parent_hash) {
+                waiting.retain(|h| h.hash() != hash);
+                if waiting.is_empty() {
+                    self.orphans.remove(&parent_hash);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Connects `header` and every orphan descending from it.
+    fn connect(&mut self, header: H) {
+        let mut pending = vec![header];
+        while let Some(header) = pending.pop() {
+            let hash = header.hash();
+            let parent_hash = *header.parent_hash();
+            let expected = (*self.headers[&parent_hash].number()).into() + 1;
+            if (*header.number()).into() != expected {
+                // Orphans are checked once their parent is known
+                continue;
+            }
+            self.tips.remove(&parent_hash);
+            self.tips.insert(hash);
+            self.children.entry(parent_hash).or_default().push(hash);
+            self.headers.insert(hash, header);
+            if let Some(orphans) = self.orphans.remove(&hash) {
+                self.orphan_order.retain(|(parent, _)| *parent != hash);
+                pending.extend(orphans);
+            }
+        }
+    }
+
+    /// Re-applies the fork choice rule to all tips, e.g. after the L1 view changed.
+    pub fn reevaluate(&mut self) -> Option<ReorgEvent<HashOf<H>>> {
+        let mut best = (self.branch_score(&self.root), self.root);
+        for tip in &self.tips {
+            let candidate = (self.branch_score(tip), *tip);
+            let better = match (&candidate.0, &best.0) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                // Equal scores are broken by the lowest hash, so every node picks the same tip
+                (Some(c), Some(b)) => c > b || (c == b && tip.as_ref() < best.1.as_ref()),
+            };
+            if better {
+                best = candidate;
+            }
+        }
+
+        let new_best = best.1;
+        if new_best == self.best {
+            return None;
+        }
+        let event = self.route(&self.best, &new_best);
+        self.best = new_best;
+        Some(event)
+    }
+
+    fn branch_score(&self, tip: &HashOf<H>) -> Option<F::Score> {
+        let mut branch: Vec<&H> = self.ancestors(tip).collect();
+        branch.reverse();
+        self.fork_choice.score(&branch)
+    }
+
+    /// Iterates from `hash` down to the root.
+    fn ancestors<'a>(&'a self, hash: &HashOf<H>) -> impl Iterator<Item = &'a H> + 'a {
+        let mut next = self.headers.get(hash);
+        let root = self.root;
+        std::iter::from_fn(move || {
+            let header = next?;
+            next = if header.hash() == root {
+                None
+            } else {
+                self.headers.get(header.parent_hash())
+            };
+            Some(header)
+        })
+    }
+
+    /// Computes the blocks to revert and apply to move the canonical tip from `from` to `to`.
+    fn route(&self, from: &HashOf<H>, to: &HashOf<H>) -> ReorgEvent<HashOf<H>> {
+        let mut reverted = Vec::new();
+        let mut applied = Vec::new();
+        let mut old = &self.headers[from];
+        let mut new = &self.headers[to];
+        while (*old.number()).into() > (*new.number()).into() {
+            reverted.push(old.hash());
+            old = &self.headers[old.parent_hash()];
+        }
+        while (*new.number()).into() > (*old.number()).into() {
+            applied.push(new.hash());
+            new = &self.headers[new.parent_hash()];
+        }
+        while old.hash() != new.hash() {
+            reverted.push(old.hash());
+            applied.push(new.hash());
+            old = &self.headers[old.parent_hash()];
+            new = &self.headers[new.parent_hash()];
+        }
+        applied.reverse();
+        ReorgEvent { reverted, applied }
+    }
+
+    /// Makes the canonical block `hash` the new root, dropping every branch that does not
+    /// descend from it.
+    pub fn finalize(&mut self, hash: &HashOf<H>) -> Result<(), ChainTreeError> {
+        if !self.is_canonical(hash) {
+            return Err(ChainTreeError::UnknownBlock);
+        }
+        let mut keep = HashSet::new();
+        let mut stack = vec![*hash];
+        while let Some(next) = stack.pop() {
+            keep.insert(next);
+            if let Some(children) = self.children.get(&next) {
+                stack.extend(children.iter().copied());
+            }
+        }
+        self.headers.retain(|h, _| keep.contains(h));
+        self.children.retain(|h, _| keep.contains(h));
+        self.tips.retain(|h| keep.contains(h));
+        let root_number: u64 = (*self.headers[hash].number()).into();
+        self.orphans.retain(|_, waiting| {
+            waiting.retain(|h| (*h.number()).into() > root_number);
+            !waiting.is_empty()
+        });
+        let orphans = &self.orphans;
+        self.orphan_order.retain(|(parent, hash)| {
+            orphans
+                .get(parent)
+                .is_some_and(|waiting| waiting.iter().any(|h| h.hash() == *hash))
+        });
+        self.root = *hash;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestHeader, child_block, test_chain};
+    use alloy::primitives::B256;
+
+    fn fork(parent: &TestHeader, seed: &[u8], len: usize) -> Vec<TestHeader> {
+        let mut headers = vec![child_block(parent, seed, vec![]).header];
+        while headers.len() < len {
+            let next = child_block(&headers[headers.len() - 1], seed, vec![]).header;
+            headers.push(next);
+        }
+        headers
+    }
+
+    fn hashes(headers: &[TestHeader]) -> Vec<[u8; 32]> {
+        headers.iter().map(|h| h.hash()).collect()
+    }
+
+    #[test]
+    fn test_extension_and_reorg() {
+        let chain: Vec<_> = test_chain(4).into_iter().map(|b| b.header).collect();
+        let mut tree = ChainTree::new(chain[0].clone(), LongestChain);
+
+        for header in &chain[1..] {
+            let event = tree.insert(header.clone()).unwrap().unwrap();
+            assert!(event.is_extension());
+            assert_eq!(event.applied, vec![header.hash()]);
+        }
+        assert_eq!(tree.best().hash(), chain[3].hash());
+
+        // A shorter competing branch does not change the canonical chain
+        let branch = fork(&chain[1], b"fork", 3);
+        assert!(tree.insert(branch[0].clone()).unwrap().is_none());
+        assert!(tree.insert(branch[2].clone()).unwrap().is_none());
+        assert_eq!(tree.tips().count(), 2);
+
+        // Once it is longer, the canonical chain switches over
+        let event = tree.insert(branch[1].clone()).unwrap().unwrap();
+        assert_eq!(event.reverted, vec![chain[3].hash(), chain[2].hash()]);
+        assert_eq!(event.applied, hashes(&branch));
+        assert!(tree.is_canonical(&branch[0].hash()));
+        assert!(!tree.is_canonical(&chain[2].hash()));
+        assert!(tree.is_canonical(&chain[1].hash()));
+    }
+
+    #[test]
+    fn test_out_of_order_headers() {
+        let chain: Vec<_> = test_chain(5).into_iter().map(|b| b.header).collect();
+        let mut tree = ChainTree::new(chain[0].clone(), LongestChain);
+
+        assert!(tree.insert(chain[4].clone()).unwrap().is_none());
+        assert!(tree.insert(chain[2].clone()).unwrap().is_none());
+        assert!(tree.insert(chain[3].clone()).unwrap().is_none());
+        assert_eq!(tree.orphan_count(), 3);
+
+        let event = tree.insert(chain[1].clone()).unwrap().unwrap();
+        assert_eq!(event.applied, hashes(&chain[1..]));
+        assert_eq!(tree.orphan_count(), 0);
+        assert_eq!(tree.best().hash(), chain[4].hash());
+    }
+
+    #[test]
+    fn test_orphan_limits() {
+        let chain: Vec<_> = test_chain(6).into_iter().map(|b| b.header).collect();
+        let mut tree = ChainTree::new(chain[0].clone(), LongestChain).with_orphan_limits(2, 3);
+
+        assert_eq!(
+            tree.insert(chain[4].clone()),
+            Err(ChainTreeError::TooFarAhead(4))
+        );
+        assert_eq!(tree.orphan_count(), 0);
+
+        // The oldest orphan is evicted once the pool is full
+        let branch = fork(&chain[1], b"fork", 1);
+        tree.insert(chain[3].clone()).unwrap();
+        tree.insert(chain[2].clone()).unwrap();
+        tree.insert(branch[0].clone()).unwrap();
+        assert_eq!(tree.orphan_count(), 2);
+
+        tree.insert(chain[1].clone()).unwrap();
+        assert_eq!(tree.orphan_count(), 0);
+        assert_eq!(tree.tips().count(), 2);
+        assert!(tree.get(&chain[3].hash()).is_none());
+
+        // Orphans are bounded by the new best tip
+        assert!(tree.insert(chain[5].clone()).unwrap().is_none());
+        assert!(tree.insert(chain[3].clone()).unwrap().is_some());
+        assert!(tree.insert(chain[4].clone()).unwrap().is_some());
+        assert_eq!(tree.best().hash(), chain[5].hash());
+    }
+
+    #[test]
+    fn test_rejects_invalid_headers() {
+        let chain: Vec<_> = test_chain(3).into_iter().map(|b| b.header).collect();
+        let mut tree = ChainTree::new(chain[1].clone(), LongestChain);
+
+        assert_eq!(
+            tree.insert(chain[0].clone()),
+            Err(ChainTreeError::BelowRoot(0))
+        );
+        let mut wrong_number = chain[2].clone();
+        wrong_number.number = 5;
+        assert_eq!(
+            tree.insert(wrong_number),
+            Err(ChainTreeError::InvalidNumber {
+                expected: 2,
+                found: 5
+            })
+        );
+        // Inserting twice is a no-op
+        assert!(tree.insert(chain[2].clone()).unwrap().is_some());
+        assert!(tree.insert(chain[2].clone()).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_equivocation_is_resolved_deterministically() {
+        let chain: Vec<_> = test_chain(2).into_iter().map(|b| b.header).collect();
+        let a = child_block(&chain[1], b"a", vec![]).header;
+        let b = child_block(&chain[1], b"b", vec![]).header;
+        let expected = if a.hash() < b.hash() {
+            a.hash()
+        } else {
+            b.hash()
+        };
+
+        for order in [[&a, &b], [&b, &a]] {
+            let mut tree = ChainTree::new(chain[0].clone(), LongestChain);
+            tree.insert(chain[1].clone()).unwrap();
+            for header in order {
+                tree.insert(header.clone()).unwrap();
+            }
+            assert_eq!(tree.best().hash(), expected);
+        }
+    }
+
+    #[test]
+    fn test_l1_anchored_fork_choice() {
+        use crate::block::HeaderExtension;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let with_origin = |header: TestHeader, l1: u64| {
+            header
+                .with_extension(HeaderExtension {
+                    l1_origin: Some(L1Origin {
+                        number: l1,
+                        hash: B256::repeat_byte(l1 as u8),
+                    }),
+                    ..Default::default()
+                })
+                .unwrap()
+        };
+        let root = test_chain(1).remove(0).header;
+        // Long branch anchored to L1 block 1, short branch anchored to L1 block 2
+        let mut long = vec![with_origin(child_block(&root, b"long", vec![]).header, 1)];
+        for _ in 0..2 {
+            let next = child_block(&long[long.len() - 1], b"long", vec![]).header;
+            long.push(with_origin(next, 1));
+        }
+        let short = with_origin(child_block(&root, b"short", vec![]).header, 2);
+
+        let reorged_out = Rc::new(RefCell::new(HashSet::<u64>::new()));
+        let view = reorged_out.clone();
+        let rule =
+            L1Anchored::new(move |origin: &L1Origin| !view.borrow().contains(&origin.number));
+        let mut tree = ChainTree::new(root, rule);
+        for header in &long {
+            tree.insert(header.clone()).unwrap();
+        }
+        let event = tree.insert(short.clone()).unwrap().unwrap();
+        assert_eq!(event.applied, vec![short.hash()]);
+        assert_eq!(event.reverted.len(), 3);
+
+        // L1 block 2 is reorged out, so the branch anchored to it is abandoned
+        reorged_out.borrow_mut().insert(2);
+        let event = tree.reevaluate().unwrap();
+        assert_eq!(event.reverted, vec![short.hash()]);
+        assert_eq!(event.applied, hashes(&long));
+    }
+
+    #[test]
+    fn test_finalize_prunes_branches() {
+        let chain: Vec<_> = test_chain(4).into_iter().map(|b| b.header).collect();
+        let mut tree = ChainTree::new(chain[0].clone(), LongestChain);
+        for header in &chain[1..] {
+            tree.insert(header.clone()).unwrap();
+        }
+        let branch = fork(&chain[1], b"fork", 1);
+        tree.insert(branch[0].clone()).unwrap();
+        assert_eq!(tree.tips().count(), 2);
+
+        assert_eq!(
+            tree.finalize(&branch[0].hash()),
+            Err(ChainTreeError::UnknownBlock)
+        );
+        tree.finalize(&chain[2].hash()).unwrap();
+        assert_eq!(tree.root().hash(), chain[2].hash());
+        assert_eq!(tree.tips().count(), 1);
+        assert!(tree.get(&branch[0].hash()).is_none());
+        assert!(tree.get(&chain[1].hash()).is_none());
+        assert_eq!(tree.best().hash(), chain[3].hash());
+    }
+}
@@ -0,0 +1,307 @@
+//! Deterministic binary codec used for hashing, signing and persisting rollup types.
+//!
+//! Integers are fixed-width little-endian, variable length values carry a `u32`
+//! length prefix and optional values a one byte tag. Top level encodings produced by
+//! [`Encode::to_bytes`] are prefixed with [`CODEC_VERSION`].
+use alloy::primitives::{Address, FixedBytes, Signature};
+
+/// Version byte prepended to every top level encoding.
+pub const CODEC_VERSION: u8 = 1;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum CodecError {
+    /// Input ended before the value was fully decoded
+    #[error("Unexpected end of input")]
+    UnexpectedEof,
+    /// Version byte is not supported by this decoder
+    #[error("Unsupported codec version {}", _0)]
+    UnsupportedVersion(u8),
+    /// Input contains bytes after the decoded value
+    #[error("{} trailing bytes after decoded value", _0)]
+    TrailingBytes(usize),
+    /// Decoded bytes do not form a valid value
+    #[error("{}", _0)]
+    InvalidValue(String),
+}
+
+pub trait Encode {
+    /// Appends the encoding of `self` to `out`.
+    fn encode_to(&self, out: &mut Vec<u8>);
+
+    /// Returns the versioned encoding of `self`.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![CODEC_VERSION];
+        self.encode_to(&mut out);
+        out
+    }
+}
+
+pub trait Decode: Sized {
+    /// Decodes a value from the front of `input`, advancing it past the consumed bytes.
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError>;
+
+    /// Decodes a value produced by [`Encode::to_bytes`], rejecting unknown versions and
+    /// trailing bytes.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, CodecError> {
+        let mut input = bytes;
+        let version = u8::decode_from(&mut input)?;
+        if version != CODEC_VERSION {
+            return Err(CodecError::UnsupportedVersion(version));
+        }
+        let value = Self::decode_from(&mut input)?;
+        if !input.is_empty() {
+            return Err(CodecError::TrailingBytes(input.len()));
+        }
+        Ok(value)
+    }
+}
+
+/// Splits `len` bytes off the front of `input`.
+pub fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], CodecError> {
+    if input.len() < len {
+        return Err(CodecError::UnexpectedEof);
+    }
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+/// Encodes a length prefix.
+pub fn encode_len(len: usize, out: &mut Vec<u8>) {
+    let len = u32::try_from(len).expect("Length should fit in u32");
+    len.encode_to(out);
+}
+
+/// Decodes a length prefix.
+pub fn decode_len(input: &mut &[u8]) -> Result<usize, CodecError> {
+    Ok(u32::decode_from(input)? as usize)
+}
+
+macro_rules! impl_codec_for_int {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode_to(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+
+            impl Decode for $t {
+                fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+                    let bytes = take(input, std::mem::size_of::<$t>())?;
+                    Ok(<$t>::from_le_bytes(bytes.try_into().expect("Length checked above")))
+                }
+            }
+        )*
+    };
+}
+
+impl_codec_for_int!(u8, u16, u32, u64, u128);
+
+impl Encode for bool {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (*self as u8).encode_to(out);
+    }
+}
+
+impl Decode for bool {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        match u8::decode_from(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            v => Err(CodecError::InvalidValue(format!("Invalid bool tag {v}"))),
+        }
+    }
+}
+
+impl<const N: usize> Encode for [u8; N] {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(self);
+    }
+}
+
+impl<const N: usize> Decode for [u8; N] {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(take(input, N)?.try_into().expect("Length checked above"))
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        self.iter().for_each(|item| item.encode_to(out));
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let len = decode_len(input)?;
+        // Every item takes at least one byte, which bounds the allocation by the input size.
+        let mut items = Vec::with_capacity(len.min(input.len()));
+        for _ in 0..len {
+            items.push(T::decode_from(input)?);
+        }
+        Ok(items)
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            None => 0u8.encode_to(out),
+            Some(value) => {
+                1u8.encode_to(out);
+                value.encode_to(out);
+            }
+        }
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        match u8::decode_from(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode_from(input)?)),
+            v => Err(CodecError::InvalidValue(format!("Invalid option tag {v}"))),
+        }
+    }
+}
+
+impl Encode for String {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Decode for String {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let len = decode_len(input)?;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|e| CodecError::InvalidValue(e.to_string()))
+    }
+}
+
+impl<T: Encode> Encode for &T {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (*self).encode_to(out);
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+        self.1.encode_to(out);
+    }
+}
+
+impl<A: Decode, B: Decode> Decode for (A, B) {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok((A::decode_from(input)?, B::decode_from(input)?))
+    }
+}
+
+impl<const N: usize> Encode for FixedBytes<N> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl<const N: usize> Decode for FixedBytes<N> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(FixedBytes(<[u8; N]>::decode_from(input)?))
+    }
+}
+
+impl Encode for Address {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl Decode for Address {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Address(FixedBytes::decode_from(input)?))
+    }
+}
+
+impl Encode for Signature {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.as_bytes().encode_to(out);
+    }
+}
+
+impl Decode for Signature {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let bytes = <[u8; 65]>::decode_from(input)?;
+        Signature::from_raw_array(&bytes).map_err(|e| CodecError::InvalidValue(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let value: (u64, Vec<Option<String>>) = (u64::MAX, vec![Some("anunaya".to_string()), None]);
+        let bytes = value.to_bytes();
+        assert_eq!(bytes[0], CODEC_VERSION);
+        assert_eq!(
+            <(u64, Vec<Option<String>>)>::from_bytes(&bytes).unwrap(),
+            value
+        );
+    }
+
+    #[test]
+    fn test_integer_layout() {
+        let mut out = vec![];
+        0x0102_0304_0506_0708u64.encode_to(&mut out);
+        assert_eq!(out, vec![8, 7, 6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn test_rejects_trailing_bytes() {
+        let mut bytes = 7u32.to_bytes();
+        bytes.push(0);
+        assert_eq!(u32::from_bytes(&bytes), Err(CodecError::TrailingBytes(1)));
+    }
+
+    #[test]
+    fn test_rejects_unknown_version() {
+        let mut bytes = 7u32.to_bytes();
+        bytes[0] = CODEC_VERSION + 1;
+        assert_eq!(
+            u32::from_bytes(&bytes),
+            Err(CodecError::UnsupportedVersion(CODEC_VERSION + 1))
+        );
+    }
+
+    #[test]
+    fn test_rejects_truncated_input() {
+        let bytes = vec![1u8, 2, 3].to_bytes();
+        assert_eq!(
+            Vec::<u8>::from_bytes(&bytes[..bytes.len() - 1]),
+            Err(CodecError::UnexpectedEof)
+        );
+        // Length prefix larger than the remaining input must not allocate or panic
+        let mut bytes = vec![CODEC_VERSION];
+        u32::MAX.encode_to(&mut bytes);
+        assert_eq!(
+            Vec::<u64>::from_bytes(&bytes),
+            Err(CodecError::UnexpectedEof)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_tags() {
+        assert!(matches!(
+            Option::<u8>::from_bytes(&[CODEC_VERSION, 2]),
+            Err(CodecError::InvalidValue(_))
+        ));
+        assert!(matches!(
+            bool::from_bytes(&[CODEC_VERSION, 2]),
+            Err(CodecError::InvalidValue(_))
+        ));
+    }
+}
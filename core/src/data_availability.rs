@@ -0,0 +1,306 @@
+//! Publication of block data to a data availability layer.
+//!
+//! Blobs are addressed by their commitment, the Keccak hash of their content, and included
+//! at increasing heights of the layer.
+use crate::hasher::KeccakHasher;
+use crate::traits::HasherT;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Commitment to the content of a blob.
+pub type BlobCommitment = [u8; 32];
+
+#[derive(Debug, thiserror::Error)]
+pub enum DataAvailabilityError {
+    /// An Io error occurred.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Stored blob does not match its commitment
+    #[error("Corrupted blob 0x{}", hex::encode(.0))]
+    Corrupted(BlobCommitment),
+    /// Failed to acquire lock
+    #[error("Failed to acquire lock")]
+    LockError,
+}
+
+/// Inclusion of a blob in the layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlobReceipt {
+    pub commitment: BlobCommitment,
+    /// Height of the layer the blob was included at
+    pub height: u64,
+}
+
+/// Computes the commitment to `blob`.
+pub fn blob_commitment(blob: &[u8]) -> BlobCommitment {
+    KeccakHasher::hash(blob)
+}
+
+/// Layer blobs are published to and retrieved from. A blob submitted again keeps the height
+/// it was first included at.
+pub trait DataAvailabilityLayer: Send + Sync {
+    /// Publishes `blob`, returning its commitment and inclusion height.
+    fn submit(&self, blob: &[u8]) -> Result<BlobReceipt, DataAvailabilityError>;
+
+    /// Returns the blob with the given commitment.
+    fn retrieve(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<Vec<u8>>, DataAvailabilityError>;
+
+    /// Returns the inclusion height of the blob with the given commitment, if it is available.
+    fn availability(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<u64>, DataAvailabilityError>;
+}
+
+#[derive(Default)]
+struct MemoryInner {
+    blobs: HashMap<BlobCommitment, (u64, Vec<u8>)>,
+    height: u64,
+}
+
+/// Layer kept in memory.
+#[derive(Default)]
+pub struct InMemoryDataAvailability {
+    inner: RwLock<MemoryInner>,
+}
+
+impl InMemoryDataAvailability {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl DataAvailabilityLayer for InMemoryDataAvailability {
+    fn submit(&self, blob: &[u8]) -> Result<BlobReceipt, DataAvailabilityError> {
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| DataAvailabilityError::LockError)?;
+        let commitment = blob_commitment(blob);
+        if let Some((height, _)) = inner.blobs.get(&commitment) {
+            return Ok(BlobReceipt {
+                commitment,
+                height: *height,
+            });
+        }
+        let height = inner.height;
+        inner.height += 1;
+        inner.blobs.insert(commitment, (height, blob.to_vec()));
+        Ok(BlobReceipt { commitment, height })
+    }
+
+    fn retrieve(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<Vec<u8>>, DataAvailabilityError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| DataAvailabilityError::LockError)?;
+        Ok(inner.blobs.get(commitment).map(|(_, blob)| blob.clone()))
+    }
+
+    fn availability(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<u64>, DataAvailabilityError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| DataAvailabilityError::LockError)?;
+        Ok(inner.blobs.get(commitment).map(|(height, _)| *height))
+    }
+}
+
+/// Extension of the blob files inside the layer directory.
+const BLOB_EXTENSION: &str = "blob";
+
+/// Layer persisted to a local directory, for running a rollup without an external layer.
+///
+/// Every blob is stored in its own file named after its commitment, holding the inclusion
+/// height (u64) followed by the blob. Files are written under a temporary name and renamed
+/// once complete, so a crash never leaves a partial blob behind.
+pub struct FileDataAvailability {
+    dir: PathBuf,
+    // Inclusion height of every stored blob, and the next height
+    index: RwLock<(HashMap<BlobCommitment, u64>, u64)>,
+}
+
+impl FileDataAvailability {
+    /// Opens the layer in `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, DataAvailabilityError> {
+        fs::create_dir_all(dir.as_ref())?;
+        let mut heights = HashMap::new();
+        let mut next = 0;
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != BLOB_EXTENSION) {
+                continue;
+            }
+            let Some(commitment) = path
+                .file_stem()
+                .and_then(|stem| hex::decode(stem.as_encoded_bytes()).ok())
+                .and_then(|bytes| BlobCommitment::try_from(bytes).ok())
+            else {
+                continue;
+            };
+            let (height, _) = Self::read(&path, &commitment)?;
+            heights.insert(commitment, height);
+            next = next.max(height + 1);
+        }
+        Ok(Self {
+            dir: dir.as_ref().to_path_buf(),
+            index: RwLock::new((heights, next)),
+        })
+    }
+
+    fn path(&self, commitment: &BlobCommitment) -> PathBuf {
+        self.dir
+            .join(hex::encode(commitment))
+            .with_extension(BLOB_EXTENSION)
+    }
+
+    /// Reads the blob file at `path`, checking it against `commitment`.
+    fn read(
+        path: &Path,
+        commitment: &BlobCommitment,
+    ) -> Result<(u64, Vec<u8>), DataAvailabilityError> {
+        let data = fs::read(path)?;
+        let (height, blob) = data
+            .split_first_chunk::<8>()
+            .ok_or(DataAvailabilityError::Corrupted(*commitment))?;
+        if blob_commitment(blob) != *commitment {
+            return Err(DataAvailabilityError::Corrupted(*commitment));
+        }
+        Ok((u64::from_le_bytes(*height), blob.to_vec()))
+    }
+}
+
+impl DataAvailabilityLayer for FileDataAvailability {
+    fn submit(&self, blob: &[u8]) -> Result<BlobReceipt, DataAvailabilityError> {
+        let mut index = self
+            .index
+            .write()
+            .map_err(|_| DataAvailabilityError::LockError)?;
+        let commitment = blob_commitment(blob);
+        if let Some(height) = index.0.get(&commitment) {
+            return Ok(BlobReceipt {
+                commitment,
+                height: *height,
+            });
+        }
+
+        let height = index.1;
+        let path = self.path(&commitment);
+        let partial = path.with_extension("partial");
+        let mut file = File::create(&partial)?;
+        file.write_all(&height.to_le_bytes())?;
+        file.write_all(blob)?;
+        file.sync_all()?;
+        fs::rename(&partial, &path)?;
+
+        index.0.insert(commitment, height);
+        index.1 += 1;
+        Ok(BlobReceipt { commitment, height })
+    }
+
+    fn retrieve(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<Vec<u8>>, DataAvailabilityError> {
+        if self.availability(commitment)?.is_none() {
+            return Ok(None);
+        }
+        let (_, blob) = Self::read(&self.path(commitment), commitment)?;
+        Ok(Some(blob))
+    }
+
+    fn availability(
+        &self,
+        commitment: &BlobCommitment,
+    ) -> Result<Option<u64>, DataAvailabilityError> {
+        let index = self
+            .index
+            .read()
+            .map_err(|_| DataAvailabilityError::LockError)?;
+        Ok(index
+            .0
+            .get(commitment)
+            .copied()
+            .filter(|_| self.path(commitment).is_file()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check_layer(layer: &impl DataAvailabilityLayer) {
+        let first = layer.submit(b"first").unwrap();
+        let second = layer.submit(b"second").unwrap();
+        assert_eq!(first.commitment, blob_commitment(b"first"));
+        assert!(first.height < second.height);
+        // Submitting a blob again does not include it twice
+        assert_eq!(layer.submit(b"first").unwrap(), first);
+
+        assert_eq!(
+            layer.retrieve(&second.commitment).unwrap(),
+            Some(b"second".to_vec())
+        );
+        assert_eq!(
+            layer.availability(&first.commitment).unwrap(),
+            Some(first.height)
+        );
+        assert_eq!(layer.retrieve(&[1; 32]).unwrap(), None);
+        assert_eq!(layer.availability(&[1; 32]).unwrap(), None);
+
+        let empty = layer.submit(b"").unwrap();
+        assert_eq!(layer.retrieve(&empty.commitment).unwrap(), Some(vec![]));
+    }
+
+    #[test]
+    fn test_in_memory_layer() {
+        check_layer(&InMemoryDataAvailability::new());
+    }
+
+    #[test]
+    fn test_file_layer() {
+        let dir = tempfile::tempdir().unwrap();
+        let (first, last) = {
+            let layer = FileDataAvailability::open(dir.path()).unwrap();
+            check_layer(&layer);
+            (
+                layer.submit(b"first").unwrap(),
+                layer.submit(b"last").unwrap(),
+            )
+        };
+
+        // Blobs and heights are restored when the layer is reopened
+        let layer = FileDataAvailability::open(dir.path()).unwrap();
+        assert_eq!(layer.availability(&first.commitment).unwrap(), Some(0));
+        assert_eq!(
+            layer.retrieve(&last.commitment).unwrap(),
+            Some(b"last".to_vec())
+        );
+        assert_eq!(layer.submit(b"next").unwrap().height, last.height + 1);
+
+        // A tampered blob is detected, a removed one is unavailable
+        let path = layer.path(&first.commitment);
+        let mut data = fs::read(&path).unwrap();
+        data.push(0);
+        fs::write(&path, data).unwrap();
+        assert!(matches!(
+            layer.retrieve(&first.commitment),
+            Err(DataAvailabilityError::Corrupted(_))
+        ));
+        fs::remove_file(&path).unwrap();
+        assert_eq!(layer.availability(&first.commitment).unwrap(), None);
+    }
+}
@@ -0,0 +1,525 @@
+//! Forced inclusion of transactions submitted on L1.
+//!
+//! A user censored by the sequencer submits their transaction to the L1 inbox, which queues
+//! it with the L1 block it was submitted in. Rollup blocks state the L1 block they follow in
+//! their L1 origin and may include the queued transactions submitted up to it. A forced
+//! transaction is overdue once the L1 origin is `window` blocks past its submission: a block
+//! leaving an overdue transaction out is invalid.
+//!
+//! Forced transactions are included in queue order. One that appears out of order counts as
+//! an ordinary transaction of the block.
+//!
+//! Every node admits the same submissions to the queue: those within the size limit, up to a
+//! number per L1 block, that the application accepts. Blocks must have room for the admitted
+//! transactions of a full L1 block, and the sequencer holds its L1 origin back so that the
+//! transactions overdue at it fit in a block, so an admitted transaction never stays due.
+//! The origin must be the L1 block of its number, and may only lag the L1 head by more than
+//! the window while blocks drain the queue.
+use crate::block::L1Origin;
+use crate::codec::Encode;
+use crate::hasher::KeccakHasher;
+use crate::traits::HasherT;
+use alloy::primitives::B256;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::RwLock;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ForcedInclusionError {
+    /// Block does not state the L1 block it follows
+    #[error("Block has no L1 origin")]
+    MissingL1Origin,
+    /// L1 origin is behind the one of the previous block
+    #[error("L1 origin #{} precedes the previous origin #{}", origin, previous)]
+    L1OriginRegression { previous: u64, origin: u64 },
+    /// L1 origin is not the L1 block of its number
+    #[error("L1 origin #{0} is not on L1")]
+    L1OriginMismatch(u64),
+    /// L1 origin lags the L1 head by more than the window
+    #[error("L1 origin #{} lags the L1 head #{}", origin, head)]
+    StaleL1Origin { origin: u64, head: u64 },
+    /// Block leaves out a forced transaction past its inclusion window
+    #[error(
+        "Forced transaction {} submitted at L1 block #{} is overdue",
+        index,
+        l1_block
+    )]
+    Overdue { index: u64, l1_block: u64 },
+    /// Failed to acquire lock
+    #[error("Failed to acquire lock")]
+    LockError,
+}
+
+/// Bounds on the submissions admitted to the queue, fixed for the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ForcedInclusionLimits {
+    /// Largest encoded transaction admitted
+    pub max_transaction_bytes: usize,
+    /// Most transactions admitted per L1 block, the later submissions of the block are dropped
+    pub max_per_l1_block: usize,
+}
+
+impl Default for ForcedInclusionLimits {
+    fn default() -> Self {
+        Self {
+            max_transaction_bytes: 16 * 1024,
+            max_per_l1_block: 16,
+        }
+    }
+}
+
+/// Transaction submitted to the L1 inbox.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForcedTransaction<T> {
+    /// Position of the transaction among all submissions to the inbox
+    pub index: u64,
+    /// L1 block the transaction was submitted in
+    pub l1_block: u64,
+    pub transaction: T,
+}
+
+/// L1 inbox forced transactions are submitted to.
+pub trait L1Inbox<T>: Send + Sync {
+    /// Returns the latest L1 block.
+    fn head(&self) -> Result<L1Origin, ForcedInclusionError>;
+
+    /// Returns L1 block `number`, if L1 has reached it.
+    fn block(&self, number: u64) -> Result<Option<L1Origin>, ForcedInclusionError>;
+
+    /// Returns the transactions submitted up to L1 block `l1_block`, from index `from` on.
+    fn forced_transactions(
+        &self,
+        from: u64,
+        l1_block: u64,
+    ) -> Result<Vec<ForcedTransaction<T>>, ForcedInclusionError>;
+}
+
+/// Forced transactions not included in the chain yet, as tracked by the sequencer and its
+/// followers while they process blocks.
+#[derive(Clone, Debug)]
+pub struct ForcedInclusionQueue<T> {
+    window: u64,
+    limits: ForcedInclusionLimits,
+    queue: VecDeque<ForcedTransaction<T>>,
+    // Index of the next submission to queue
+    next_index: u64,
+    // L1 block of the last queued submission, and the number queued from it
+    last_queued: (u64, usize),
+    // L1 origin of the last processed block
+    l1_origin: u64,
+}
+
+impl<T: Encode + Clone> ForcedInclusionQueue<T> {
+    /// Queue whose transactions must be included within `window` L1 blocks, admitting the
+    /// submissions within `limits`.
+    pub fn new(window: u64, limits: ForcedInclusionLimits) -> Self {
+        Self {
+            window,
+            limits,
+            queue: VecDeque::new(),
+            next_index: 0,
+            last_queued: (0, 0),
+            l1_origin: 0,
+        }
+    }
+
+    pub fn window(&self) -> u64 {
+        self.window
+    }
+
+    pub fn limits(&self) -> ForcedInclusionLimits {
+        self.limits
+    }
+
+    /// L1 origin of the last processed block.
+    pub fn l1_origin(&self) -> u64 {
+        self.l1_origin
+    }
+
+    /// Forced transactions not included yet, in order.
+    pub fn pending(&self) -> impl Iterator<Item = &ForcedTransaction<T>> {
+        self.queue.iter()
+    }
+
+    /// Queues `forced` unless it was queued before or is beyond the limits. Submissions must
+    /// be queued in order.
+    pub fn enqueue(&mut self, forced: ForcedTransaction<T>) {
+        if forced.index < self.next_index {
+            return;
+        }
+        self.next_index = forced.index + 1;
+        let (last_block, queued) = self.last_queued;
+        let queued = if forced.l1_block == last_block {
+            queued
+        } else {
+            0
+        };
+        if queued == self.limits.max_per_l1_block
+            || forced.transaction.to_bytes().len() > self.limits.max_transaction_bytes
+        {
+            return;
+        }
+        self.last_queued = (forced.l1_block, queued + 1);
+        self.queue.push_back(forced);
+    }
+
+    /// Queues the transactions submitted to `inbox` up to L1 block `l1_block`, keeping those
+    /// `accept` passes. Every node of the chain must filter with the same predicate, which
+    /// is meant to drop the transactions that can never be applied.
+    pub fn sync(
+        &mut self,
+        inbox: &dyn L1Inbox<T>,
+        l1_block: u64,
+        accept: impl Fn(&T) -> bool,
+    ) -> Result<(), ForcedInclusionError> {
+        for forced in inbox.forced_transactions(self.next_index, l1_block)? {
+            let index = forced.index;
+            if accept(&forced.transaction) {
+                self.enqueue(forced);
+            } else {
+                self.next_index = self.next_index.max(index + 1);
+            }
+        }
+        Ok(())
+    }
+
+    /// Latest L1 origin at which at most `capacity` queued transactions are overdue, if the
+    /// queue bounds it. A block including `capacity` forced transactions may follow it.
+    pub fn max_l1_origin(&self, capacity: usize) -> Option<u64> {
+        self.queue
+            .get(capacity)
+            .map(|forced| (forced.l1_block + self.window).saturating_sub(1))
+    }
+
+    /// Forced transactions a block following L1 block `l1_origin` may include, in order.
+    pub fn includable(&self, l1_origin: u64) -> impl Iterator<Item = &ForcedTransaction<T>> {
+        self.queue
+            .iter()
+            .take_while(move |forced| forced.l1_block <= l1_origin)
+    }
+
+    fn is_overdue(&self, forced: &ForcedTransaction<T>, l1_origin: u64) -> bool {
+        forced.l1_block.saturating_add(self.window) <= l1_origin
+    }
+
+    /// Checks that a block following L1 block `l1_origin` keeps up with the L1 head `l1_head`.
+    /// Its origin may lag the head by more than the window only while the queue has more
+    /// transactions includable at it than a block holds, and the block holds nothing else.
+    pub fn check_lag(
+        &self,
+        l1_origin: u64,
+        l1_head: u64,
+        transactions: &[T],
+    ) -> Result<(), ForcedInclusionError> {
+        if l1_head.saturating_sub(l1_origin) <= self.window {
+            return Ok(());
+        }
+        let draining = !transactions.is_empty()
+            && self
+                .queue
+                .get(transactions.len())
+                .is_some_and(|next| next.l1_block <= l1_origin)
+            && self
+                .queue
+                .iter()
+                .zip(transactions)
+                .all(|(forced, transaction)| {
+                    forced.transaction.to_bytes() == transaction.to_bytes()
+                });
+        if draining {
+            Ok(())
+        } else {
+            Err(ForcedInclusionError::StaleL1Origin {
+                origin: l1_origin,
+                head: l1_head,
+            })
+        }
+    }
+
+    /// Processes a block following L1 block `l1_origin`, dequeuing the forced transactions
+    /// it includes. Returns their number, or fails without changing the queue if the block
+    /// leaves out an overdue transaction.
+    pub fn process_block(
+        &mut self,
+        l1_origin: u64,
+        transactions: &[T],
+    ) -> Result<usize, ForcedInclusionError> {
+        if l1_origin < self.l1_origin {
+            return Err(ForcedInclusionError::L1OriginRegression {
+                previous: self.l1_origin,
+                origin: l1_origin,
+            });
+        }
+        let mut included = 0;
+        for transaction in transactions {
+            let Some(forced) = self.queue.get(included) else {
+                break;
+            };
+            if forced.l1_block <= l1_origin
+                && forced.transaction.to_bytes() == transaction.to_bytes()
+            {
+                included += 1;
+            }
+        }
+        if let Some(forced) = self.queue.get(included)
+            && self.is_overdue(forced, l1_origin)
+        {
+            return Err(ForcedInclusionError::Overdue {
+                index: forced.index,
+                l1_block: forced.l1_block,
+            });
+        }
+        self.queue.drain(..included);
+        self.l1_origin = l1_origin;
+        Ok(included)
+    }
+}
+
+#[derive(Debug)]
+struct MockL1Inner<T> {
+    number: u64,
+    submitted: Vec<ForcedTransaction<T>>,
+}
+
+/// L1 kept in memory, whose blocks are mined on demand, for running a rollup locally.
+#[derive(Debug)]
+pub struct MockL1<T> {
+    inner: RwLock<MockL1Inner<T>>,
+}
+
+impl<T> Default for MockL1<T> {
+    fn default() -> Self {
+        Self {
+            inner: RwLock::new(MockL1Inner {
+                number: 0,
+                submitted: Vec::new(),
+            }),
+        }
+    }
+}
+
+impl<T> MockL1<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn origin(number: u64) -> L1Origin {
+        L1Origin {
+            number,
+            hash: B256::from(KeccakHasher::hash(&number.to_le_bytes())),
+        }
+    }
+
+    /// Mines `blocks` L1 blocks, returning the new head.
+    pub fn mine(&self, blocks: u64) -> Result<L1Origin, ForcedInclusionError> {
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| ForcedInclusionError::LockError)?;
+        inner.number += blocks;
+        Ok(Self::origin(inner.number))
+    }
+
+    /// Submits `transaction` to the inbox in the next L1 block, which is mined. Returns the
+    /// submission.
+    pub fn submit(&self, transaction: T) -> Result<ForcedTransaction<T>, ForcedInclusionError>
+    where
+        T: Clone,
+    {
+        let mut inner = self
+            .inner
+            .write()
+            .map_err(|_| ForcedInclusionError::LockError)?;
+        inner.number += 1;
+        let forced = ForcedTransaction {
+            index: inner.submitted.len() as u64,
+            l1_block: inner.number,
+            transaction,
+        };
+        inner.submitted.push(forced.clone());
+        Ok(forced)
+    }
+}
+
+impl<T: Clone + Send + Sync> L1Inbox<T> for MockL1<T> {
+    fn head(&self) -> Result<L1Origin, ForcedInclusionError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| ForcedInclusionError::LockError)?;
+        Ok(Self::origin(inner.number))
+    }
+
+    fn block(&self, number: u64) -> Result<Option<L1Origin>, ForcedInclusionError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| ForcedInclusionError::LockError)?;
+        Ok((number <= inner.number).then(|| Self::origin(number)))
+    }
+
+    fn forced_transactions(
+        &self,
+        from: u64,
+        l1_block: u64,
+    ) -> Result<Vec<ForcedTransaction<T>>, ForcedInclusionError> {
+        let inner = self
+            .inner
+            .read()
+            .map_err(|_| ForcedInclusionError::LockError)?;
+        Ok(inner
+            .submitted
+            .iter()
+            .skip(from as usize)
+            .take_while(|forced| forced.l1_block <= l1_block)
+            .cloned()
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::TestTransaction;
+
+    fn tx(byte: u8) -> TestTransaction {
+        TestTransaction(vec![byte])
+    }
+
+    #[test]
+    fn test_forced_transactions_are_included_within_window() {
+        let l1 = MockL1::new();
+        let mut queue = ForcedInclusionQueue::new(3, ForcedInclusionLimits::default());
+        let first = l1.submit(tx(1)).unwrap();
+        l1.submit(tx(2)).unwrap();
+        l1.submit(tx(3)).unwrap();
+        assert_eq!(l1.head().unwrap().number, 3);
+
+        // A sequencer following L1 block 2 has seen the first two submissions
+        queue.sync(&l1, 2, |_| true).unwrap();
+        assert_eq!(queue.pending().count(), 2);
+        assert_eq!(queue.includable(1).collect::<Vec<_>>(), [&first]);
+        queue.sync(&l1, 3, |_| true).unwrap();
+        queue.sync(&l1, 3, |_| true).unwrap();
+        assert_eq!(queue.pending().count(), 3);
+
+        // Forced transactions may wait until their window ends
+        assert_eq!(queue.process_block(3, &[tx(9)]), Ok(0));
+        // The first transaction is overdue once L1 reaches block 4
+        let mut follower = queue.clone();
+        assert_eq!(
+            follower.process_block(4, &[tx(2), tx(9)]),
+            Err(ForcedInclusionError::Overdue {
+                index: 0,
+                l1_block: 1
+            })
+        );
+        assert_eq!(follower.pending().count(), 3);
+        assert_eq!(queue.process_block(4, &[tx(1), tx(9), tx(2)]), Ok(2));
+        assert_eq!(queue.pending().next().unwrap().index, 2);
+
+        // The origin never goes back, and a transaction from a later L1 block does not count
+        assert_eq!(
+            queue.process_block(3, &[tx(3)]),
+            Err(ForcedInclusionError::L1OriginRegression {
+                previous: 4,
+                origin: 3
+            })
+        );
+        assert_eq!(queue.process_block(5, &[]), Ok(0));
+        assert_eq!(
+            queue.process_block(6, &[]),
+            Err(ForcedInclusionError::Overdue {
+                index: 2,
+                l1_block: 3
+            })
+        );
+        assert_eq!(queue.process_block(6, &[tx(3)]), Ok(1));
+        assert_eq!(queue.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_rejected_submissions_are_skipped() {
+        let l1 = MockL1::new();
+        let mut queue = ForcedInclusionQueue::new(1, ForcedInclusionLimits::default());
+        l1.submit(tx(0)).unwrap();
+        l1.submit(tx(1)).unwrap();
+        l1.mine(2).unwrap();
+
+        // Submissions no node accepts are never due
+        queue.sync(&l1, 4, |tx| tx.0 != [0]).unwrap();
+        assert_eq!(
+            queue
+                .pending()
+                .map(|forced| forced.index)
+                .collect::<Vec<_>>(),
+            [1]
+        );
+        assert_eq!(queue.process_block(4, &[tx(0), tx(1)]), Ok(1));
+        queue.sync(&l1, 4, |_| true).unwrap();
+        assert_eq!(queue.pending().count(), 0);
+    }
+
+    #[test]
+    fn test_stale_origin_is_rejected() {
+        let l1 = MockL1::new();
+        let mut queue = ForcedInclusionQueue::new(2, ForcedInclusionLimits::default());
+        for byte in 0..3 {
+            l1.submit(tx(byte)).unwrap();
+        }
+        queue.sync(&l1, 3, |_| true).unwrap();
+
+        assert_eq!(queue.check_lag(3, 5, &[tx(9)]), Ok(()));
+        assert_eq!(
+            queue.check_lag(2, 5, &[tx(9)]),
+            Err(ForcedInclusionError::StaleL1Origin { origin: 2, head: 5 })
+        );
+        // A lagging block draining the queue is not stale, unless the rest is not includable
+        assert_eq!(queue.check_lag(2, 5, &[tx(0)]), Ok(()));
+        assert_eq!(
+            queue.check_lag(2, 5, &[tx(0), tx(1)]),
+            Err(ForcedInclusionError::StaleL1Origin { origin: 2, head: 5 })
+        );
+        assert_eq!(
+            queue.check_lag(2, 5, &[]),
+            Err(ForcedInclusionError::StaleL1Origin { origin: 2, head: 5 })
+        );
+    }
+
+    #[test]
+    fn test_submissions_beyond_limits_are_dropped() {
+        let l1 = MockL1::new();
+        let limits = ForcedInclusionLimits {
+            max_transaction_bytes: 8,
+            max_per_l1_block: 2,
+        };
+        let mut queue = ForcedInclusionQueue::new(2, limits);
+        for forced in [tx(1), TestTransaction(vec![2; 16]), tx(3)] {
+            l1.submit(forced).unwrap();
+        }
+        // An oversized submission is dropped
+        queue.sync(&l1, 3, |_| true).unwrap();
+        // Submissions of an L1 block past the first two are dropped
+        for index in 3..7 {
+            queue.enqueue(ForcedTransaction {
+                index,
+                l1_block: 4,
+                transaction: tx(index as u8 + 1),
+            });
+        }
+        assert_eq!(
+            queue
+                .pending()
+                .map(|forced| forced.transaction.clone())
+                .collect::<Vec<_>>(),
+            [tx(1), tx(3), tx(4), tx(5)]
+        );
+
+        // The origin is held back so that at most two transactions are overdue
+        assert_eq!(queue.max_l1_origin(2), Some(5));
+        assert_eq!(queue.max_l1_origin(4), None);
+        assert_eq!(queue.process_block(5, &[tx(1), tx(3)]), Ok(2));
+        assert_eq!(queue.max_l1_origin(2), None);
+    }
+}
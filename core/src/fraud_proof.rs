@@ -0,0 +1,479 @@
+//! Fraud proofs over the state roots committed by a block after each of its transactions.
+//!
+//! A block commits to its intermediate state roots through their Merkle root. A transaction
+//! whose committed root is disputed is re-executed by the verifier from the preceding root
+//! with a one-step proof: the witness of the state tree paths of the keys the transaction
+//! accesses, and the values it reads. The verifier only needs to trust the preceding root,
+//! against which the witness is checked.
+//!
+//! Each intermediate root also commits to the gas the block has used so far, so that the
+//! disputed transaction is re-executed with the gas left to it by the block gas limit.
+//!
+//! The roots start from the state root of the parent block and end with the state root of
+//! the block, so `begin_block` and `end_block` must leave the committed state unchanged. The
+//! block builder and [`crate::validation::validate_intermediate_roots`] enforce it.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::journal::KeyValueStore;
+use crate::merkle::{self, MerkleProof};
+use crate::metering::{Gas, GasMeter};
+use crate::parallel::StateView;
+use crate::receipt::Receipt;
+use crate::smt::{Key, SparseMerkleWitness, UnwitnessedKey};
+use crate::traits::{
+    AppState, BlockHeaderT, BlockT, HasherT, MerkleizedState, ParallelTransition,
+    StateTransitionFunction, TransactionOf,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum FraudProofError {
+    /// Witness does not match the state root the transaction is executed from
+    #[error("Witness does not match the pre-state root")]
+    WitnessMismatch,
+    /// Transaction accesses a key the proof does not cover
+    #[error(transparent)]
+    Unwitnessed(#[from] UnwitnessedKey),
+}
+
+/// State of a block after one of its transactions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntermediateRoot {
+    pub state_root: [u8; 32],
+    /// Gas used by the transactions of the block so far
+    pub gas_used: Gas,
+}
+
+impl Encode for IntermediateRoot {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.state_root.encode_to(out);
+        self.gas_used.encode_to(out);
+    }
+}
+
+impl Decode for IntermediateRoot {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            state_root: <[u8; 32]>::decode_from(input)?,
+            gas_used: Gas::decode_from(input)?,
+        })
+    }
+}
+
+/// Computes the Merkle root committing to the state after each transaction.
+pub fn intermediate_roots_root<H: HasherT>(roots: &[IntermediateRoot]) -> H::Output {
+    let leaves: Vec<Vec<u8>> = roots.iter().map(Encode::to_bytes).collect();
+    merkle::merkle_root::<H, _>(&leaves)
+}
+
+/// Builds an inclusion proof of the state after transaction `index`.
+pub fn intermediate_root_proof<H: HasherT>(
+    roots: &[IntermediateRoot],
+    index: usize,
+) -> Option<MerkleProof<H::Output>> {
+    let leaves: Vec<Vec<u8>> = roots.iter().map(Encode::to_bytes).collect();
+    merkle::merkle_proof::<H, _>(&leaves, index)
+}
+
+/// Receipts and intermediate state roots of a block.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ExecutionTrace {
+    pub receipts: Vec<Receipt>,
+    /// State after each transaction
+    pub intermediate_roots: Vec<IntermediateRoot>,
+}
+
+/// Applies `block` to `state` like [`StateTransitionFunction::apply_block`], recording the
+/// state after each transaction.
+pub fn trace_block<S: StateTransitionFunction>(
+    state: &mut S::State,
+    block: &S::Block,
+) -> Result<ExecutionTrace, S::Error> {
+    S::validate_block(state, block)?;
+    let mut meter = GasMeter::new(S::block_gas_limit(state));
+    let header = block.header().unsealed();
+    S::begin_block(state, &header)?;
+    let mut trace = ExecutionTrace {
+        receipts: Vec::with_capacity(block.transactions().len()),
+        intermediate_roots: Vec::with_capacity(block.transactions().len()),
+    };
+    for transaction in block.transactions() {
+        let (receipt, used) = meter.scoped(|meter| S::apply_transaction(state, transaction, meter));
+        trace.receipts.push(Receipt {
+            resources_used: used,
+            ..receipt?
+        });
+        trace.intermediate_roots.push(IntermediateRoot {
+            state_root: state.state_root(),
+            gas_used: meter.used(),
+        });
+    }
+    S::end_block(state, &header)?;
+    Ok(trace)
+}
+
+/// Proof of the state transition of a single transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OneStepProof {
+    /// Paths of the keys the transaction accesses, in the state before it
+    pub witness: SparseMerkleWitness<[u8; 32]>,
+    /// Values of the keys the transaction reads, absent keys left out
+    pub values: Vec<(Key, Vec<u8>)>,
+}
+
+/// View of the state recording the keys a transaction accesses, without modifying it.
+struct RecordingView<'a, S> {
+    state: &'a S,
+    reads: BTreeSet<Key>,
+    writes: BTreeMap<Key, Option<Vec<u8>>>,
+}
+
+impl<S: MerkleizedState> StateView for RecordingView<'_, S> {
+    type Key = Key;
+    type Value = Vec<u8>;
+
+    fn read(&mut self, key: &Key) -> Option<Vec<u8>> {
+        if let Some(value) = self.writes.get(key) {
+            return value.clone();
+        }
+        self.reads.insert(*key);
+        KeyValueStore::read(self.state, key)
+    }
+
+    fn write(&mut self, key: Key, value: Option<Vec<u8>>) {
+        self.writes.insert(key, value);
+    }
+}
+
+/// Builds the proof of applying `transaction` to `state` with `gas` left in the block.
+pub fn prove_one_step<S>(state: &S::State, transaction: &TransactionOf<S>, gas: Gas) -> OneStepProof
+where
+    S: ParallelTransition,
+    S::State: MerkleizedState,
+{
+    let mut view = RecordingView {
+        state,
+        reads: BTreeSet::new(),
+        writes: BTreeMap::new(),
+    };
+    // A failing transaction is proven by the keys it accessed before failing
+    let _ = S::execute_transaction(&mut view, transaction, &mut GasMeter::new(gas));
+    let keys: BTreeSet<Key> = view
+        .reads
+        .iter()
+        .chain(view.writes.keys())
+        .copied()
+        .collect();
+    OneStepProof {
+        witness: state.tree().witness(&keys.into_iter().collect::<Vec<_>>()),
+        values: view
+            .reads
+            .iter()
+            .filter_map(|key| Some((*key, KeyValueStore::read(state, key)?)))
+            .collect(),
+    }
+}
+
+/// Builds the proof of transaction `index` of `block`, replaying the transactions before it
+/// on `state`, the state of the parent block. Returns `None` if there is no such transaction.
+pub fn prove_transaction<S>(
+    state: &S::State,
+    block: &S::Block,
+    index: usize,
+) -> Result<Option<OneStepProof>, S::Error>
+where
+    S: ParallelTransition,
+    S::State: MerkleizedState + Clone,
+{
+    let Some(transaction) = block.transactions().get(index) else {
+        return Ok(None);
+    };
+    let mut state = state.clone();
+    let mut meter = GasMeter::new(S::block_gas_limit(&state));
+    S::begin_block(&mut state, &block.header().unsealed())?;
+    for preceding in &block.transactions()[..index] {
+        meter
+            .scoped(|meter| S::apply_transaction(&mut state, preceding, meter))
+            .0?;
+    }
+    Ok(Some(prove_one_step::<S>(
+        &state,
+        transaction,
+        meter.remaining(),
+    )))
+}
+
+/// View of the state backed by a one-step proof.
+struct WitnessView<'a, H> {
+    witness: &'a SparseMerkleWitness<[u8; 32]>,
+    values: BTreeMap<Key, &'a [u8]>,
+    writes: BTreeMap<Key, Option<Vec<u8>>>,
+    // First key read outside the proof
+    unwitnessed: Option<Key>,
+    _hasher: std::marker::PhantomData<H>,
+}
+
+impl<H: HasherT<Output = [u8; 32]>> StateView for WitnessView<'_, H> {
+    type Key = Key;
+    type Value = Vec<u8>;
+
+    fn read(&mut self, key: &Key) -> Option<Vec<u8>> {
+        if let Some(value) = self.writes.get(key) {
+            return value.clone();
+        }
+        match self.witness.value_hash(key) {
+            Ok(None) => None,
+            Ok(Some(value_hash)) => match self.values.get(key) {
+                Some(value) if H::hash(value) == value_hash => Some(value.to_vec()),
+                _ => {
+                    self.unwitnessed.get_or_insert(*key);
+                    None
+                }
+            },
+            Err(_) => {
+                self.unwitnessed.get_or_insert(*key);
+                None
+            }
+        }
+    }
+
+    fn write(&mut self, key: Key, value: Option<Vec<u8>>) {
+        self.writes.insert(key, value);
+    }
+}
+
+/// Re-executes `transaction` from the state committed to by `pre`, in a block limited to
+/// `gas_limit`, with the state it accesses taken from `proof`. Returns the state after the
+/// transaction, or `None` if the transaction fails, in which case no block including it is
+/// valid.
+pub fn verify_one_step<S>(
+    pre: &IntermediateRoot,
+    gas_limit: Gas,
+    transaction: &TransactionOf<S>,
+    proof: &OneStepProof,
+) -> Result<Option<IntermediateRoot>, FraudProofError>
+where
+    S: ParallelTransition,
+    S::State: MerkleizedState,
+{
+    verify_with::<S, <S::State as MerkleizedState>::Hasher>(pre, gas_limit, transaction, proof)
+}
+
+fn verify_with<S, H>(
+    pre: &IntermediateRoot,
+    gas_limit: Gas,
+    transaction: &TransactionOf<S>,
+    proof: &OneStepProof,
+) -> Result<Option<IntermediateRoot>, FraudProofError>
+where
+    S: ParallelTransition,
+    S::State: MerkleizedState,
+    H: HasherT<Output = [u8; 32]>,
+{
+    if proof.witness.root::<H>() != pre.state_root {
+        return Err(FraudProofError::WitnessMismatch);
+    }
+    let mut view = WitnessView::<H> {
+        witness: &proof.witness,
+        values: proof
+            .values
+            .iter()
+            .map(|(key, value)| (*key, value.as_slice()))
+            .collect(),
+        writes: BTreeMap::new(),
+        unwitnessed: None,
+        _hasher: std::marker::PhantomData,
+    };
+    let mut meter = GasMeter::new(gas_limit.saturating_sub(pre.gas_used));
+    let result = S::execute_transaction(&mut view, transaction, &mut meter);
+    if let Some(key) = view.unwitnessed {
+        return Err(UnwitnessedKey(key).into());
+    }
+    if result.is_err() {
+        return Ok(None);
+    }
+    let mut witness = proof.witness.clone();
+    for (key, value) in view.writes {
+        witness.update(key, value.as_deref().map(H::hash))?;
+    }
+    Ok(Some(IntermediateRoot {
+        state_root: witness.root::<H>(),
+        gas_used: pre.gas_used + meter.used(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+    use crate::test_utils::{
+        TRANSFER_GAS, TestAccounts, TestTransfers, account_key, child_block, test_chain, transfer,
+    };
+
+    const GAS_LIMIT: Gas = 10;
+
+    fn accounts() -> TestAccounts {
+        TestAccounts::with_balances(&[(1, 100), (2, 50), (3, 7)]).with_gas_limit(GAS_LIMIT)
+    }
+
+    fn start(state: &TestAccounts) -> IntermediateRoot {
+        IntermediateRoot {
+            state_root: state.state_root(),
+            gas_used: 0,
+        }
+    }
+
+    #[test]
+    fn test_one_step_matches_execution() {
+        let transactions = [
+            // Credits an absent account
+            transfer(1, 9, 30),
+            // Empties account 3, which is removed from the state
+            transfer(3, 2, 7),
+            // Transfers to itself
+            transfer(2, 2, 5),
+        ];
+        for transaction in transactions {
+            let state = accounts();
+            let proof = prove_one_step::<TestTransfers>(&state, &transaction, GAS_LIMIT);
+
+            let mut expected = state.clone();
+            TestTransfers::apply_transaction(
+                &mut expected,
+                &transaction,
+                &mut GasMeter::unlimited(),
+            )
+            .unwrap();
+            assert_eq!(
+                verify_one_step::<TestTransfers>(&start(&state), GAS_LIMIT, &transaction, &proof),
+                Ok(Some(IntermediateRoot {
+                    state_root: expected.state_root(),
+                    gas_used: TRANSFER_GAS,
+                }))
+            );
+        }
+
+        // A transaction that cannot be applied has no post-state
+        let state = accounts();
+        let overdraft = transfer(3, 1, 8);
+        let proof = prove_one_step::<TestTransfers>(&state, &overdraft, GAS_LIMIT);
+        assert_eq!(
+            verify_one_step::<TestTransfers>(&start(&state), GAS_LIMIT, &overdraft, &proof),
+            Ok(None)
+        );
+
+        // Nor does a transaction exceeding the gas left in the block
+        let transaction = transfer(1, 2, 1);
+        let proof = prove_one_step::<TestTransfers>(&state, &transaction, 0);
+        let pre = IntermediateRoot {
+            gas_used: GAS_LIMIT,
+            ..start(&state)
+        };
+        assert_eq!(
+            verify_one_step::<TestTransfers>(&pre, GAS_LIMIT, &transaction, &proof),
+            Ok(None)
+        );
+    }
+
+    #[test]
+    fn test_rejects_invalid_proofs() {
+        let state = accounts();
+        let transaction = transfer(1, 2, 10);
+        let proof = prove_one_step::<TestTransfers>(&state, &transaction, GAS_LIMIT);
+        let pre = start(&state);
+
+        let other_root = IntermediateRoot {
+            state_root: [1; 32],
+            ..pre
+        };
+        assert_eq!(
+            verify_one_step::<TestTransfers>(&other_root, GAS_LIMIT, &transaction, &proof),
+            Err(FraudProofError::WitnessMismatch)
+        );
+
+        // A forged balance does not match the witness
+        let mut forged = proof.clone();
+        forged.values[0].1 = 1_000u64.to_le_bytes().to_vec();
+        assert!(matches!(
+            verify_one_step::<TestTransfers>(&pre, GAS_LIMIT, &transaction, &forged),
+            Err(FraudProofError::Unwitnessed(_))
+        ));
+
+        // The proof of another transaction does not cover the accessed keys
+        let other = transfer(3, 2, 1);
+        assert_eq!(
+            verify_one_step::<TestTransfers>(&pre, GAS_LIMIT, &other, &proof),
+            Err(FraudProofError::Unwitnessed(UnwitnessedKey(account_key(3))))
+        );
+    }
+
+    #[test]
+    fn test_trace_and_prove_transaction() {
+        let parent = test_chain(1).remove(0).header;
+        let transactions = vec![transfer(1, 2, 10), transfer(2, 3, 60), transfer(3, 1, 67)];
+        let block = child_block(&parent, b"transfers", transactions);
+        let mut state = accounts();
+        let trace = trace_block::<TestTransfers>(&mut state, &block).unwrap();
+        assert_eq!(trace.intermediate_roots.len(), 3);
+        assert_eq!(trace.intermediate_roots[2].state_root, state.state_root());
+        assert_eq!(trace.intermediate_roots[2].gas_used, 3 * TRANSFER_GAS);
+
+        let parent_state = accounts();
+        let mut pre = start(&parent_state);
+        for (index, root) in trace.intermediate_roots.iter().enumerate() {
+            let proof = prove_transaction::<TestTransfers>(&parent_state, &block, index)
+                .unwrap()
+                .unwrap();
+            let post = verify_one_step::<TestTransfers>(
+                &pre,
+                GAS_LIMIT,
+                &block.transactions[index],
+                &proof,
+            );
+            assert_eq!(post, Ok(Some(*root)));
+            pre = *root;
+        }
+        assert!(
+            prove_transaction::<TestTransfers>(&parent_state, &block, 3)
+                .unwrap()
+                .is_none()
+        );
+
+        let root = intermediate_roots_root::<KeccakHasher>(&trace.intermediate_roots);
+        let proof = intermediate_root_proof::<KeccakHasher>(&trace.intermediate_roots, 1).unwrap();
+        assert!(proof.verify::<KeccakHasher>(&root, &trace.intermediate_roots[1].to_bytes()));
+    }
+
+    #[test]
+    fn test_transaction_over_block_gas_limit_is_provable() {
+        let parent = test_chain(1).remove(0).header;
+        let transactions = vec![transfer(1, 2, 1), transfer(2, 3, 1), transfer(3, 1, 1)];
+        let block = child_block(&parent, b"over the limit", transactions);
+        let parent_state = accounts().with_gas_limit(2 * TRANSFER_GAS);
+        assert!(trace_block::<TestTransfers>(&mut parent_state.clone(), &block).is_err());
+
+        // The proposer claims the third transfer was applied after the first two
+        let mut state = parent_state.clone();
+        let mut pre = start(&state);
+        for transaction in &block.transactions[..2] {
+            TestTransfers::apply_transaction(&mut state, transaction, &mut GasMeter::unlimited())
+                .unwrap();
+            pre = IntermediateRoot {
+                state_root: state.state_root(),
+                gas_used: pre.gas_used + TRANSFER_GAS,
+            };
+        }
+        let proof = prove_transaction::<TestTransfers>(&parent_state, &block, 2)
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            verify_one_step::<TestTransfers>(
+                &pre,
+                2 * TRANSFER_GAS,
+                &block.transactions[2],
+                &proof
+            ),
+            Ok(None)
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! Genesis specification of a chain and construction of its first block.
+//!
+//! The specification is read from a JSON or TOML file. Block 0 has a zero parent hash,
+//! commits to the initial state through its state root, and carries the genesis timestamp
+//! and the chain id (big-endian, in the extra data) so that chains with the same initial
+//! state have different genesis hashes.
+use crate::block::{HeaderError, HeaderExtension};
+use crate::forced_inclusion::ForcedInclusionLimits;
+use crate::hasher::HasherKind;
+use crate::merkle;
+use crate::metering::Gas;
+use crate::traits::{BlockHeaderT, BlockT, GenesisState, HasherT};
+use alloy::primitives::Address;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GenesisError {
+    /// Genesis file could not be read
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Genesis file is not valid JSON
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    /// Genesis file is not valid TOML
+    #[error(transparent)]
+    Toml(#[from] toml::de::Error),
+    /// Genesis file extension is neither `json` nor `toml`
+    #[error("Unsupported genesis file {}", _0)]
+    UnsupportedFormat(String),
+    /// Chain is hashed with another hasher than the genesis specifies
+    #[error("Genesis specifies the {} hasher, chain uses {}", expected, found)]
+    HasherMismatch {
+        expected: HasherKind,
+        found: HasherKind,
+    },
+    /// Initial state is invalid
+    #[error("Invalid genesis state: {}", _0)]
+    InvalidState(String),
+    /// Genesis header is invalid
+    #[error(transparent)]
+    Header(#[from] HeaderError),
+}
+
+/// Chain parameters fixed at genesis.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GenesisParameters {
+    /// Hashing algorithm of the chain
+    pub hasher: HasherKind,
+    /// Gas available to the transactions of a block, left to the application if unset
+    pub block_gas_limit: Option<Gas>,
+    /// Addresses allowed to propose blocks
+    pub proposers: Vec<Address>,
+    /// Number of L1 blocks within which transactions submitted to the L1 inbox must be
+    /// included, forced inclusion is disabled if unset
+    pub forced_inclusion_window: Option<u64>,
+    /// Bounds on the transactions admitted to the forced inclusion queue
+    pub forced_inclusion_limits: ForcedInclusionLimits,
+}
+
+/// Genesis specification, `S` being the initial state of the application.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Genesis<S> {
+    pub chain_id: u64,
+    /// Unix timestamp of block 0, in seconds
+    #[serde(default)]
+    pub timestamp: u64,
+    #[serde(default)]
+    pub parameters: GenesisParameters,
+    pub state: S,
+}
+
+impl<S: DeserializeOwned> Genesis<S> {
+    pub fn from_json(json: &str) -> Result<Self, GenesisError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn from_toml(toml: &str) -> Result<Self, GenesisError> {
+        Ok(toml::from_str(toml)?)
+    }
+
+    /// Reads the specification at `path`, whose format is given by its extension.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, GenesisError> {
+        let path = path.as_ref();
+        let contents = std::fs::read_to_string(path)?;
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => Self::from_json(&contents),
+            Some("toml") => Self::from_toml(&contents),
+            _ => Err(GenesisError::UnsupportedFormat(path.display().to_string())),
+        }
+    }
+}
+
+impl<S> Genesis<S> {
+    /// Builds the initial state and block 0 of the chain.
+    pub fn build<B, St>(&self) -> Result<(St, B), GenesisError>
+    where
+        B: BlockT,
+        St: GenesisState<Genesis = S>,
+        <B::BlockHeader as BlockHeaderT>::Hash: From<[u8; 32]>,
+    {
+        let found = <<B::BlockHeader as BlockHeaderT>::Hashing as HasherT>::KIND;
+        if found != self.parameters.hasher {
+            return Err(GenesisError::HasherMismatch {
+                expected: self.parameters.hasher,
+                found,
+            });
+        }
+        let state = St::from_genesis(self)?;
+        let number = <B::BlockHeader as BlockHeaderT>::Number::try_from(0)
+            .map_err(|_| GenesisError::InvalidState("Block number 0 is invalid".to_string()))?;
+        let header = B::BlockHeader::new(
+            number,
+            state.state_root().into(),
+            [0; 32].into(),
+            merkle::empty_root::<<B::BlockHeader as BlockHeaderT>::Hashing>(),
+        )
+        .with_extension(HeaderExtension {
+            timestamp: Some(self.timestamp),
+            extra_data: self.chain_id.to_be_bytes().to_vec(),
+            ..Default::default()
+        })?;
+        Ok((state, B::new(header, vec![])))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{TestBlock, TestState};
+    use crate::traits::AppState;
+    use std::collections::BTreeMap;
+
+    #[derive(Debug, Default, Deserialize)]
+    pub struct Balances {
+        accounts: BTreeMap<Address, u64>,
+    }
+
+    type BalanceState = TestState<Address, u64>;
+
+    impl GenesisState for BalanceState {
+        type Genesis = Balances;
+
+        fn from_genesis(genesis: &Genesis<Balances>) -> Result<Self, GenesisError> {
+            if genesis.state.accounts.values().any(|balance| *balance == 0) {
+                return Err(GenesisError::InvalidState("Empty account".to_string()));
+            }
+            Ok(Self(genesis.state.accounts.clone()))
+        }
+    }
+
+    const JSON: &str = r#"{
+        "chain_id": 42,
+        "timestamp": 1700000000,
+        "parameters": { "hasher": "keccak", "block_gas_limit": 1000 },
+        "state": {
+            "accounts": { "0x0000000000000000000000000000000000000001": 100 }
+        }
+    }"#;
+
+    const TOML: &str = r#"
+        chain_id = 42
+        timestamp = 1700000000
+
+        [parameters]
+        hasher = "keccak"
+        block_gas_limit = 1000
+
+        [state.accounts]
+        0x0000000000000000000000000000000000000001 = 100
+    "#;
+
+    #[test]
+    fn test_json_and_toml_agree() {
+        let json = Genesis::<Balances>::from_json(JSON).unwrap();
+        let toml = Genesis::<Balances>::from_toml(TOML).unwrap();
+        assert_eq!(json.chain_id, 42);
+        assert_eq!(json.parameters.block_gas_limit, Some(1000));
+        assert_eq!(json.parameters, toml.parameters);
+        assert_eq!(json.state.accounts, toml.state.accounts);
+
+        let (_, json_block) = json.build::<TestBlock, BalanceState>().unwrap();
+        let (_, toml_block) = toml.build::<TestBlock, BalanceState>().unwrap();
+        assert_eq!(json_block.header.hash(), toml_block.header.hash());
+    }
+
+    #[test]
+    fn test_build_genesis_block() {
+        let genesis = Genesis::<Balances>::from_json(JSON).unwrap();
+        let (state, block) = genesis.build::<TestBlock, BalanceState>().unwrap();
+        assert_eq!(block.header.number, 0);
+        assert_eq!(block.header.parent_hash, [0; 32]);
+        assert_eq!(block.header.state_root, state.state_root());
+        assert_eq!(block.header.timestamp(), Some(1_700_000_000));
+        assert_eq!(block.header.extension.extra_data, 42u64.to_be_bytes());
+        assert!(block.transactions.is_empty());
+
+        // The chain id distinguishes chains with the same initial state
+        let other = Genesis {
+            chain_id: 43,
+            ..genesis
+        };
+        let (_, other_block) = other.build::<TestBlock, BalanceState>().unwrap();
+        assert_ne!(other_block.header.hash(), block.header.hash());
+    }
+
+    #[test]
+    fn test_rejects_invalid_genesis() {
+        let mut genesis = Genesis::<Balances>::from_json(JSON).unwrap();
+        genesis.parameters.hasher = HasherKind::Sha256;
+        assert!(matches!(
+            genesis.build::<TestBlock, BalanceState>(),
+            Err(GenesisError::HasherMismatch { .. })
+        ));
+
+        genesis.parameters.hasher = HasherKind::Keccak;
+        genesis.state.accounts.insert(Address::ZERO, 0);
+        assert!(matches!(
+            genesis.build::<TestBlock, BalanceState>(),
+            Err(GenesisError::InvalidState(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_by_extension() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("genesis.toml");
+        std::fs::write(&path, TOML).unwrap();
+        assert_eq!(Genesis::<Balances>::load(&path).unwrap().chain_id, 42);
+
+        let path = dir.path().join("genesis.yaml");
+        std::fs::write(&path, JSON).unwrap();
+        assert!(matches!(
+            Genesis::<Balances>::load(&path),
+            Err(GenesisError::UnsupportedFormat(_))
+        ));
+    }
+}
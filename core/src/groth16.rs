@@ -0,0 +1,708 @@
+//! Validity proofs of token transfers with Groth16 over BN254.
+//!
+//! Balances live in a binary Merkle tree of fixed depth hashed with Poseidon, with the leaf
+//! `poseidon([balance])` at the index of every account. The transfer circuit proves that a
+//! batch of transfers, given as public inputs along with the state roots before and after,
+//! moves the tree from one root to the other: each transfer opens the sender leaf against
+//! the current root and debits it, then opens the receiver leaf in the updated tree and
+//! credits it. Amounts and updated balances are range checked to 64 bits, so that a debit
+//! cannot wrap around the field.
+//!
+//! The circuit is specialized to a tree depth and a number of transfers. Blocks with fewer
+//! transfers are padded with empty transfers from account 0 to itself. Transfers carry no
+//! signature: authorizing them is left to the block producer.
+use crate::block::{Block, BlockHeader};
+use crate::codec::{CodecError, Decode, Encode};
+use crate::hasher::KeccakHasher;
+use crate::metering::{Gas, GasMeter, OutOfGas};
+use crate::poseidon::{self, poseidon, poseidon_var};
+use crate::receipt::Receipt;
+use crate::traits::{
+    AppState, BlockT, ProverT, SignedTransactionT, StateTransitionFunction, VerifierT,
+};
+use ark_bn254::{Bn254, Fr};
+use ark_ff::PrimeField;
+use ark_groth16::{Groth16, PreparedVerifyingKey, Proof, ProvingKey};
+use ark_r1cs_std::fields::fp::FpVar;
+use ark_r1cs_std::prelude::{AllocVar, Boolean, EqGadget};
+use ark_relations::r1cs::{ConstraintSynthesizer, ConstraintSystemRef, SynthesisError};
+use ark_snark::SNARK;
+use ark_std::rand::{CryptoRng, RngCore, rngs::OsRng};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Gas charged for every transfer
+pub const TRANSFER_GAS: Gas = 21_000;
+/// Largest supported tree depth, for account indices to fit in a `u32`
+pub const MAX_DEPTH: usize = 32;
+/// Bits of balances and amounts
+const BALANCE_BITS: usize = 64;
+
+/// Transfer of `amount` from account `from` to account `to`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Transfer {
+    pub from: u32,
+    pub to: u32,
+    pub amount: u64,
+}
+
+impl Encode for Transfer {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.from.encode_to(out);
+        self.to.encode_to(out);
+        self.amount.encode_to(out);
+    }
+}
+
+impl Decode for Transfer {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            from: u32::decode_from(input)?,
+            to: u32::decode_from(input)?,
+            amount: u64::decode_from(input)?,
+        })
+    }
+}
+
+impl SignedTransactionT for Transfer {}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TransferError {
+    /// Tree depth is outside of `1..=MAX_DEPTH`
+    #[error("Unsupported tree depth {}", _0)]
+    UnsupportedDepth(usize),
+    /// Account index is outside of the tree
+    #[error("Unknown account {}", _0)]
+    UnknownAccount(u32),
+    /// Sender balance is lower than the amount
+    #[error("Insufficient balance in account {}", _0)]
+    InsufficientBalance(u32),
+    /// Receiver balance would exceed `u64::MAX`
+    #[error("Balance overflow in account {}", _0)]
+    BalanceOverflow(u32),
+    #[error(transparent)]
+    OutOfGas(#[from] OutOfGas),
+}
+
+/// Balances and Merkle paths a transfer is proven with.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TransferStep {
+    pub transfer: Transfer,
+    /// Sender balance before the transfer
+    pub sender_balance: u64,
+    /// Siblings of the sender leaf, from the leaf up, before the transfer
+    pub sender_path: Vec<Fr>,
+    /// Receiver balance after the sender was debited
+    pub receiver_balance: u64,
+    /// Siblings of the receiver leaf after the sender was debited
+    pub receiver_path: Vec<Fr>,
+}
+
+/// Account balances in a Poseidon Merkle tree of fixed depth.
+#[derive(Clone, Debug)]
+pub struct PoseidonLedger {
+    depth: usize,
+    balances: BTreeMap<u32, u64>,
+    // Non-empty nodes by level, leaves first, and position
+    nodes: BTreeMap<(usize, u64), Fr>,
+    // Root of an empty subtree by level
+    empty: Vec<Fr>,
+}
+
+fn leaf(balance: u64) -> Fr {
+    poseidon(&[Fr::from(balance)])
+}
+
+impl PoseidonLedger {
+    /// Creates a ledger of `2^depth` accounts with no balance.
+    pub fn new(depth: usize) -> Result<Self, TransferError> {
+        if !(1..=MAX_DEPTH).contains(&depth) {
+            return Err(TransferError::UnsupportedDepth(depth));
+        }
+        let mut empty = vec![leaf(0)];
+        for level in 0..depth {
+            empty.push(poseidon(&[empty[level], empty[level]]));
+        }
+        Ok(Self {
+            depth,
+            balances: BTreeMap::new(),
+            nodes: BTreeMap::new(),
+            empty,
+        })
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Returns the balance of `account`, or `None` if it is outside of the tree.
+    pub fn balance(&self, account: u32) -> Option<u64> {
+        self.contains(account)
+            .then(|| self.balances.get(&account).copied().unwrap_or_default())
+    }
+
+    fn contains(&self, account: u32) -> bool {
+        (account as u64) < 1 << self.depth
+    }
+
+    pub fn set_balance(&mut self, account: u32, balance: u64) -> Result<(), TransferError> {
+        if !self.contains(account) {
+            return Err(TransferError::UnknownAccount(account));
+        }
+        self.balances.insert(account, balance);
+        let mut position = account as u64;
+        let mut node = leaf(balance);
+        for level in 0..self.depth {
+            self.nodes.insert((level, position), node);
+            let sibling = self.node(level, position ^ 1);
+            node = match position % 2 {
+                0 => poseidon(&[node, sibling]),
+                _ => poseidon(&[sibling, node]),
+            };
+            position /= 2;
+        }
+        self.nodes.insert((self.depth, 0), node);
+        Ok(())
+    }
+
+    fn node(&self, level: usize, position: u64) -> Fr {
+        self.nodes
+            .get(&(level, position))
+            .copied()
+            .unwrap_or(self.empty[level])
+    }
+
+    pub fn root(&self) -> Fr {
+        self.node(self.depth, 0)
+    }
+
+    /// Returns the siblings of the leaf of `account`, from the leaf up.
+    pub fn path(&self, account: u32) -> Vec<Fr> {
+        (0..self.depth)
+            .map(|level| self.node(level, (account as u64 >> level) ^ 1))
+            .collect()
+    }
+
+    /// Applies `transfer`, returning the witness of its proof. The ledger is left unchanged
+    /// if the transfer fails.
+    pub fn transfer(&mut self, transfer: &Transfer) -> Result<TransferStep, TransferError> {
+        let Transfer { from, to, amount } = *transfer;
+        let sender_balance = self
+            .balance(from)
+            .ok_or(TransferError::UnknownAccount(from))?;
+        let receiver_balance = self.balance(to).ok_or(TransferError::UnknownAccount(to))?;
+        let debited = sender_balance
+            .checked_sub(amount)
+            .ok_or(TransferError::InsufficientBalance(from))?;
+        let receiver_balance = if from == to {
+            debited
+        } else {
+            receiver_balance
+        };
+        let credited = receiver_balance
+            .checked_add(amount)
+            .ok_or(TransferError::BalanceOverflow(to))?;
+
+        let sender_path = self.path(from);
+        self.set_balance(from, debited)?;
+        let receiver_path = self.path(to);
+        self.set_balance(to, credited)?;
+        Ok(TransferStep {
+            transfer: *transfer,
+            sender_balance,
+            sender_path,
+            receiver_balance,
+            receiver_path,
+        })
+    }
+}
+
+impl AppState for PoseidonLedger {
+    fn state_root(&self) -> [u8; 32] {
+        poseidon::to_bytes(&self.root())
+    }
+
+    fn previous_state_root(&self) -> Option<[u8; 32]> {
+        None
+    }
+}
+
+pub type TransferHeader = BlockHeader<u64, KeccakHasher>;
+pub type TransferBlock = Block<TransferHeader, Transfer>;
+
+/// State transition function matching the transfer circuit.
+pub struct TransferStf;
+
+impl StateTransitionFunction for TransferStf {
+    type State = PoseidonLedger;
+    type Error = TransferError;
+    type BlockHeader = TransferHeader;
+    type Block = TransferBlock;
+
+    fn validate_block(
+        _state: &PoseidonLedger,
+        _block: &TransferBlock,
+    ) -> Result<(), TransferError> {
+        Ok(())
+    }
+
+    fn apply_transaction(
+        state: &mut PoseidonLedger,
+        transaction: &Transfer,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, TransferError> {
+        meter.consume(TRANSFER_GAS)?;
+        state.transfer(transaction)?;
+        Ok(Receipt::success(TRANSFER_GAS))
+    }
+}
+
+/// Circuit proving a batch of transfers between two state roots.
+#[derive(Clone, Debug)]
+pub struct TransferCircuit {
+    depth: usize,
+    pre_root: Fr,
+    post_root: Fr,
+    steps: Vec<TransferStep>,
+}
+
+impl TransferCircuit {
+    pub fn new(depth: usize, pre_root: Fr, post_root: Fr, steps: Vec<TransferStep>) -> Self {
+        Self {
+            depth,
+            pre_root,
+            post_root,
+            steps,
+        }
+    }
+
+    /// Circuit of `batch_size` transfers in a tree of `depth`, with placeholder values, for
+    /// generating its keys.
+    pub fn blank(depth: usize, batch_size: usize) -> Self {
+        let step = TransferStep {
+            transfer: Transfer::default(),
+            sender_balance: 0,
+            sender_path: vec![Fr::from(0u64); depth],
+            receiver_balance: 0,
+            receiver_path: vec![Fr::from(0u64); depth],
+        };
+        Self::new(
+            depth,
+            Fr::from(0u64),
+            Fr::from(0u64),
+            vec![step; batch_size],
+        )
+    }
+
+    /// Public inputs of the circuit, in allocation order.
+    pub fn public_inputs(pre_root: Fr, post_root: Fr, transfers: &[Transfer]) -> Vec<Fr> {
+        let mut inputs = vec![pre_root, post_root];
+        for transfer in transfers {
+            inputs.extend([
+                Fr::from(transfer.from),
+                Fr::from(transfer.to),
+                Fr::from(transfer.amount),
+            ]);
+        }
+        inputs
+    }
+}
+
+/// Allocates the `len` little-endian bits of `value` and constrains them to `var`.
+fn bits_le(
+    cs: &ConstraintSystemRef<Fr>,
+    var: &FpVar<Fr>,
+    value: u64,
+    len: usize,
+) -> Result<Vec<Boolean<Fr>>, SynthesisError> {
+    let bits = (0..len)
+        .map(|i| Boolean::new_witness(cs.clone(), || Ok(value >> i & 1 == 1)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Boolean::le_bits_to_fp(&bits)?.enforce_equal(var)?;
+    Ok(bits)
+}
+
+/// Computes the root of the tree from a leaf, its position bits and its siblings.
+fn root_from_path(
+    leaf: &FpVar<Fr>,
+    position: &[Boolean<Fr>],
+    siblings: &[FpVar<Fr>],
+) -> Result<FpVar<Fr>, SynthesisError> {
+    let mut node = leaf.clone();
+    for (is_right, sibling) in position.iter().zip(siblings) {
+        let left = is_right.select(sibling, &node)?;
+        let right = is_right.select(&node, sibling)?;
+        node = poseidon_var(&[left, right])?;
+    }
+    Ok(node)
+}
+
+impl ConstraintSynthesizer<Fr> for TransferCircuit {
+    fn generate_constraints(self, cs: ConstraintSystemRef<Fr>) -> Result<(), SynthesisError> {
+        let pre_root = FpVar::new_input(cs.clone(), || Ok(self.pre_root))?;
+        let post_root = FpVar::new_input(cs.clone(), || Ok(self.post_root))?;
+        let transfers = self
+            .steps
+            .iter()
+            .map(|step| {
+                let Transfer { from, to, amount } = step.transfer;
+                Ok([
+                    FpVar::new_input(cs.clone(), || Ok(Fr::from(from)))?,
+                    FpVar::new_input(cs.clone(), || Ok(Fr::from(to)))?,
+                    FpVar::new_input(cs.clone(), || Ok(Fr::from(amount)))?,
+                ])
+            })
+            .collect::<Result<Vec<_>, SynthesisError>>()?;
+
+        let mut root = pre_root;
+        for (step, [from, to, amount]) in self.steps.iter().zip(transfers) {
+            let Transfer {
+                from: from_value,
+                to: to_value,
+                amount: amount_value,
+            } = step.transfer;
+            let from_bits = bits_le(&cs, &from, from_value as u64, self.depth)?;
+            let to_bits = bits_le(&cs, &to, to_value as u64, self.depth)?;
+            bits_le(&cs, &amount, amount_value, BALANCE_BITS)?;
+
+            let sender_path = Vec::new_witness(cs.clone(), || Ok(step.sender_path.clone()))?;
+            let sender_balance =
+                FpVar::new_witness(cs.clone(), || Ok(Fr::from(step.sender_balance)))?;
+            root_from_path(
+                &poseidon_var(std::slice::from_ref(&sender_balance))?,
+                &from_bits,
+                &sender_path,
+            )?
+            .enforce_equal(&root)?;
+            let debited = &sender_balance - &amount;
+            bits_le(
+                &cs,
+                &debited,
+                step.sender_balance.wrapping_sub(amount_value),
+                BALANCE_BITS,
+            )?;
+            root = root_from_path(&poseidon_var(&[debited])?, &from_bits, &sender_path)?;
+
+            let receiver_path = Vec::new_witness(cs.clone(), || Ok(step.receiver_path.clone()))?;
+            let receiver_balance =
+                FpVar::new_witness(cs.clone(), || Ok(Fr::from(step.receiver_balance)))?;
+            root_from_path(
+                &poseidon_var(std::slice::from_ref(&receiver_balance))?,
+                &to_bits,
+                &receiver_path,
+            )?
+            .enforce_equal(&root)?;
+            let credited = &receiver_balance + &amount;
+            bits_le(
+                &cs,
+                &credited,
+                step.receiver_balance.wrapping_add(amount_value),
+                BALANCE_BITS,
+            )?;
+            root = root_from_path(&poseidon_var(&[credited])?, &to_bits, &receiver_path)?;
+        }
+        root.enforce_equal(&post_root)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Groth16Error {
+    /// Block holds more transfers than the circuit
+    #[error("Block holds {} transfers, the circuit at most {}", found, max)]
+    TooManyTransfers { found: usize, max: usize },
+    /// Ledger depth differs from the circuit depth
+    #[error("Expected a ledger of depth {}, found {}", expected, found)]
+    DepthMismatch { expected: usize, found: usize },
+    /// Block cannot be applied to the ledger
+    #[error(transparent)]
+    Transfer(#[from] TransferError),
+    /// Applying the block does not yield the state root of its header
+    #[error("Block does not yield the state root of its header")]
+    StateRootMismatch,
+    /// State root is not the encoding of a field element
+    #[error("State root is not a field element")]
+    InvalidStateRoot,
+    /// Proof does not verify against the statement
+    #[error("Invalid proof")]
+    InvalidProof,
+    #[error("Constraint synthesis failed: {}", _0)]
+    Synthesis(#[from] SynthesisError),
+}
+
+/// Decodes a state root into the field element it encodes.
+fn root_element(root: &[u8; 32]) -> Result<Fr, Groth16Error> {
+    let element = Fr::from_be_bytes_mod_order(root);
+    if poseidon::to_bytes(&element) != *root {
+        return Err(Groth16Error::InvalidStateRoot);
+    }
+    Ok(element)
+}
+
+/// Transfers of `block` padded to `batch_size`.
+fn padded_transfers(
+    block: &TransferBlock,
+    batch_size: usize,
+) -> Result<Vec<Transfer>, Groth16Error> {
+    let transfers = block.transactions();
+    if transfers.len() > batch_size {
+        return Err(Groth16Error::TooManyTransfers {
+            found: transfers.len(),
+            max: batch_size,
+        });
+    }
+    let mut padded = transfers.to_vec();
+    padded.resize(batch_size, Transfer::default());
+    Ok(padded)
+}
+
+/// Generates the keys of the circuit of `batch_size` transfers in a tree of `depth`.
+///
+/// The randomness of the setup must be discarded, as it allows forging proofs.
+pub fn setup<R: RngCore + CryptoRng>(
+    depth: usize,
+    batch_size: usize,
+    rng: &mut R,
+) -> Result<(Groth16Prover, Groth16Verifier), Groth16Error> {
+    if !(1..=MAX_DEPTH).contains(&depth) {
+        return Err(TransferError::UnsupportedDepth(depth).into());
+    }
+    let (proving_key, verifying_key) =
+        Groth16::<Bn254>::circuit_specific_setup(TransferCircuit::blank(depth, batch_size), rng)?;
+    let verifier = Groth16Verifier {
+        depth,
+        batch_size,
+        key: Groth16::<Bn254>::process_vk(&verifying_key)?,
+    };
+    let prover = Groth16Prover {
+        depth,
+        batch_size,
+        key: proving_key,
+    };
+    Ok((prover, verifier))
+}
+
+/// Prover of blocks of transfers.
+#[derive(Clone, Debug)]
+pub struct Groth16Prover {
+    depth: usize,
+    batch_size: usize,
+    key: ProvingKey<Bn254>,
+}
+
+impl ProverT<TransferStf> for Groth16Prover {
+    type Proof = Proof<Bn254>;
+    type Error = Groth16Error;
+
+    fn prove(
+        &self,
+        state: &PoseidonLedger,
+        block: &TransferBlock,
+    ) -> Result<Proof<Bn254>, Groth16Error> {
+        if state.depth() != self.depth {
+            return Err(Groth16Error::DepthMismatch {
+                expected: self.depth,
+                found: state.depth(),
+            });
+        }
+        let mut ledger = state.clone();
+        let steps = padded_transfers(block, self.batch_size)?
+            .iter()
+            .map(|transfer| ledger.transfer(transfer))
+            .collect::<Result<Vec<_>, _>>()?;
+        if ledger.state_root() != block.header().state_root {
+            return Err(Groth16Error::StateRootMismatch);
+        }
+        let circuit = TransferCircuit::new(self.depth, state.root(), ledger.root(), steps);
+        Ok(Groth16::<Bn254>::prove(&self.key, circuit, &mut OsRng)?)
+    }
+}
+
+/// Verifier of proofs of blocks of transfers.
+#[derive(Clone, Debug)]
+pub struct Groth16Verifier {
+    depth: usize,
+    batch_size: usize,
+    key: PreparedVerifyingKey<Bn254>,
+}
+
+impl Groth16Verifier {
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+}
+
+impl VerifierT<TransferStf> for Groth16Verifier {
+    type Proof = Proof<Bn254>;
+    type Error = Groth16Error;
+
+    fn verify(
+        &self,
+        pre_state_root: &[u8; 32],
+        block: &TransferBlock,
+        proof: &Proof<Bn254>,
+    ) -> Result<(), Groth16Error> {
+        let inputs = TransferCircuit::public_inputs(
+            root_element(pre_state_root)?,
+            root_element(&block.header().state_root)?,
+            &padded_transfers(block, self.batch_size)?,
+        );
+        match Groth16::<Bn254>::verify_with_processed_vk(&self.key, &inputs, proof)? {
+            true => Ok(()),
+            false => Err(Groth16Error::InvalidProof),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::merkle;
+    use crate::traits::BlockHeaderT;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::rand::{SeedableRng, rngs::StdRng};
+
+    // Proving is slow in unoptimized builds, so the proven circuit is kept small
+    const DEPTH: usize = 2;
+    const BATCH_SIZE: usize = 2;
+
+    fn ledger(depth: usize) -> PoseidonLedger {
+        let mut ledger = PoseidonLedger::new(depth).unwrap();
+        ledger.set_balance(1, 100).unwrap();
+        ledger.set_balance(2, 50).unwrap();
+        ledger
+    }
+
+    /// Block of `transfers` applied on `ledger`, with the resulting state root.
+    fn block(ledger: &PoseidonLedger, transfers: Vec<Transfer>) -> TransferBlock {
+        let mut block = TransferBlock::new(
+            TransferHeader::new(1, [0; 32], [0; 32], merkle::empty_root::<KeccakHasher>()),
+            transfers,
+        );
+        block.header.transactions_root = block.compute_transactions_root();
+        let mut post_state = ledger.clone();
+        TransferStf::apply_block(&mut post_state, &block).unwrap();
+        block.header.state_root = post_state.state_root();
+        block
+    }
+
+    fn transfer(from: u32, to: u32, amount: u64) -> Transfer {
+        Transfer { from, to, amount }
+    }
+
+    #[test]
+    fn test_ledger_paths() {
+        let mut ledger = ledger(3);
+        let empty_root = PoseidonLedger::new(3).unwrap().root();
+        assert_ne!(ledger.root(), empty_root);
+        assert_eq!(ledger.balance(1), Some(100));
+        assert_eq!(ledger.balance(7), Some(0));
+        assert_eq!(ledger.balance(8), None);
+
+        // Emptying the accounts restores the empty tree
+        ledger.set_balance(1, 0).unwrap();
+        ledger.set_balance(2, 0).unwrap();
+        assert_eq!(ledger.root(), empty_root);
+
+        assert_eq!(
+            ledger.transfer(&transfer(1, 2, 1)),
+            Err(TransferError::InsufficientBalance(1))
+        );
+        assert_eq!(
+            ledger.transfer(&transfer(1, 9, 0)),
+            Err(TransferError::UnknownAccount(9))
+        );
+        ledger.set_balance(3, u64::MAX).unwrap();
+        ledger.set_balance(4, 1).unwrap();
+        assert_eq!(
+            ledger.transfer(&transfer(4, 3, 1)),
+            Err(TransferError::BalanceOverflow(3))
+        );
+        assert_eq!(ledger.balance(4), Some(1));
+
+        assert!(matches!(
+            PoseidonLedger::new(0),
+            Err(TransferError::UnsupportedDepth(0))
+        ));
+        assert!(matches!(
+            PoseidonLedger::new(MAX_DEPTH + 1),
+            Err(TransferError::UnsupportedDepth(33))
+        ));
+    }
+
+    #[test]
+    fn test_circuit_rejects_forged_witnesses() {
+        let satisfied = |circuit: TransferCircuit| {
+            let cs = ConstraintSystem::<Fr>::new_ref();
+            circuit.generate_constraints(cs.clone()).unwrap();
+            cs.is_satisfied().unwrap()
+        };
+        let pre_state = ledger(3);
+        let mut post_state = pre_state.clone();
+        let steps = [transfer(1, 2, 30), transfer(2, 2, 10), transfer(2, 5, 80)]
+            .iter()
+            .map(|transfer| post_state.transfer(transfer).unwrap())
+            .collect::<Vec<_>>();
+        let circuit = TransferCircuit::new(3, pre_state.root(), post_state.root(), steps);
+        assert!(satisfied(circuit.clone()));
+
+        let mut forged = circuit.clone();
+        forged.post_root = pre_state.root();
+        assert!(!satisfied(forged));
+
+        // Spending more than the balance wraps around the field, which the range checks reject
+        let mut forged = circuit.clone();
+        forged.steps[0].transfer.amount = 101;
+        assert!(!satisfied(forged));
+
+        let mut forged = circuit;
+        forged.steps[2].receiver_balance = 5;
+        assert!(!satisfied(forged));
+    }
+
+    #[test]
+    fn test_groth16_proof_round_trip() {
+        let (prover, verifier) = setup(DEPTH, BATCH_SIZE, &mut StdRng::seed_from_u64(7)).unwrap();
+        let state = ledger(DEPTH);
+        let block = block(&state, vec![transfer(1, 2, 30), transfer(2, 3, 60)]);
+        let proof = prover.prove(&state, &block).unwrap();
+        verifier
+            .verify(&state.state_root(), &block, &proof)
+            .unwrap();
+
+        // The proof is bound to the pre-state root and the transfers
+        assert!(matches!(
+            verifier.verify(&block.header().state_root, &block, &proof),
+            Err(Groth16Error::InvalidProof)
+        ));
+        let mut altered = block.clone();
+        altered.transactions[1].amount = 61;
+        assert!(matches!(
+            verifier.verify(&state.state_root(), &altered, &proof),
+            Err(Groth16Error::InvalidProof)
+        ));
+        assert!(matches!(
+            verifier.verify(&[0xff; 32], &block, &proof),
+            Err(Groth16Error::InvalidStateRoot)
+        ));
+
+        let mut forged = block.clone();
+        forged.header.state_root = state.state_root();
+        assert!(matches!(
+            prover.prove(&state, &forged),
+            Err(Groth16Error::StateRootMismatch)
+        ));
+        let mut oversized = block.clone();
+        oversized.transactions = vec![transfer(1, 2, 1); BATCH_SIZE + 1];
+        assert!(matches!(
+            prover.prove(&state, &oversized),
+            Err(Groth16Error::TooManyTransfers { found: 3, max: 2 })
+        ));
+        let overdraft = TransferBlock::new(block.header.clone(), vec![transfer(2, 1, 51)]);
+        assert!(matches!(
+            prover.prove(&state, &overdraft),
+            Err(Groth16Error::Transfer(TransferError::InsufficientBalance(
+                2
+            )))
+        ));
+    }
+}
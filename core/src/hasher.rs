@@ -1,13 +1,177 @@
+use crate::codec::{CodecError, Decode, Encode};
 use crate::traits::HasherT;
 use serde::{Deserialize, Serialize};
+use sha2::Digest;
+use std::fmt;
+use std::str::FromStr;
+
+/// Hashing algorithms that can be selected at runtime.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HasherKind {
+    #[default]
+    Keccak,
+    Sha256,
+    Blake3,
+    Poseidon,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("Unknown hasher {}", _0)]
+pub struct UnknownHasher(pub String);
+
+impl HasherKind {
+    pub const ALL: [HasherKind; 4] = [Self::Keccak, Self::Sha256, Self::Blake3, Self::Poseidon];
+
+    /// Identifier recorded in encoded block headers.
+    pub fn id(self) -> u8 {
+        match self {
+            Self::Keccak => 0,
+            Self::Sha256 => 1,
+            Self::Blake3 => 2,
+            Self::Poseidon => 3,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.id() == id)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Keccak => "keccak",
+            Self::Sha256 => "sha256",
+            Self::Blake3 => "blake3",
+            Self::Poseidon => "poseidon",
+        }
+    }
+
+    /// Hashes `data` with the selected algorithm.
+    pub fn hash(self, data: &[u8]) -> [u8; 32] {
+        match self {
+            Self::Keccak => KeccakHasher::hash(data),
+            Self::Sha256 => Sha256Hasher::hash(data),
+            Self::Blake3 => Blake3Hasher::hash(data),
+            Self::Poseidon => PoseidonHasher::hash(data),
+        }
+    }
+}
+
+impl fmt::Display for HasherKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for HasherKind {
+    type Err = UnknownHasher;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .into_iter()
+            .find(|kind| kind.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| UnknownHasher(s.to_string()))
+    }
+}
+
+impl Encode for HasherKind {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.id().encode_to(out);
+    }
+}
+
+impl Decode for HasherKind {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        let id = u8::decode_from(input)?;
+        Self::from_id(id).ok_or_else(|| CodecError::InvalidValue(format!("Unknown hasher id {id}")))
+    }
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct KeccakHasher;
 
 impl HasherT for KeccakHasher {
     type Output = [u8; 32];
+    const KIND: HasherKind = HasherKind::Keccak;
 
     fn hash(s: &[u8]) -> Self::Output {
         alloy::primitives::keccak256(s).0
     }
 }
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Sha256Hasher;
+
+impl HasherT for Sha256Hasher {
+    type Output = [u8; 32];
+    const KIND: HasherKind = HasherKind::Sha256;
+
+    fn hash(s: &[u8]) -> Self::Output {
+        sha2::Sha256::digest(s).into()
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Blake3Hasher;
+
+impl HasherT for Blake3Hasher {
+    type Output = [u8; 32];
+    const KIND: HasherKind = HasherKind::Blake3;
+
+    fn hash(s: &[u8]) -> Self::Output {
+        blake3::hash(s).into()
+    }
+}
+
+/// Poseidon over the BN254 scalar field, producing the big-endian encoding of the digest.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PoseidonHasher;
+
+impl HasherT for PoseidonHasher {
+    type Output = [u8; 32];
+    const KIND: HasherKind = HasherKind::Poseidon;
+
+    fn hash(s: &[u8]) -> Self::Output {
+        crate::poseidon::to_bytes(&crate::poseidon::poseidon_bytes(s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_digests() {
+        assert_eq!(
+            hex::encode(KeccakHasher::hash(b"abc")),
+            "4e03657aea45a94fc7d47ba826c8d667c0d1e6e33a64a036ec44f58fa12d6c45"
+        );
+        assert_eq!(
+            hex::encode(Sha256Hasher::hash(b"abc")),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(
+            hex::encode(Blake3Hasher::hash(b"abc")),
+            "6437b3ac38465133ffb63b75273a8db548c558465d79db03fd359c6cd5bd9d85"
+        );
+    }
+
+    #[test]
+    fn test_kind_dispatch_and_parsing() {
+        for kind in HasherKind::ALL {
+            assert_eq!(kind.name().parse::<HasherKind>(), Ok(kind));
+            assert_eq!(HasherKind::from_bytes(&kind.to_bytes()), Ok(kind));
+        }
+        assert_eq!(HasherKind::Sha256.hash(b"abc"), Sha256Hasher::hash(b"abc"));
+        assert_eq!(
+            HasherKind::Poseidon.hash(b"abc"),
+            PoseidonHasher::hash(b"abc")
+        );
+        assert_eq!("SHA256".parse::<HasherKind>(), Ok(HasherKind::Sha256));
+        assert!("md5".parse::<HasherKind>().is_err());
+        assert_eq!(
+            serde_json::from_str::<HasherKind>("\"blake3\"").unwrap(),
+            HasherKind::Blake3
+        );
+    }
+}
@@ -0,0 +1,258 @@
+//! Journaled overlay over a key-value store.
+//!
+//! While a checkpoint is open every write records the previous value of its key, so that
+//! the store can be rolled back to the checkpoint without being cloned. Checkpoints nest
+//! and are closed in reverse order: committing keeps the changes, which an enclosing
+//! checkpoint may still revert, and reverting undoes them.
+use crate::smt::{Key, SparseMerkleTree};
+use crate::traits::{HasherT, RevertibleState};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::ops::Deref;
+
+/// Key-value store whose writes can be journaled.
+pub trait KeyValueStore {
+    type Key: Clone;
+    type Value;
+
+    // Returns the value of `key`.
+    fn read(&self, key: &Self::Key) -> Option<Self::Value>;
+
+    // Sets the value of `key`, or removes it if `value` is `None`. Returns the previous value.
+    fn write(&mut self, key: Self::Key, value: Option<Self::Value>) -> Option<Self::Value>;
+}
+
+impl<K: Ord + Clone, V: Clone> KeyValueStore for BTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn read(&self, key: &K) -> Option<V> {
+        self.get(key).cloned()
+    }
+
+    fn write(&mut self, key: K, value: Option<V>) -> Option<V> {
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self.remove(&key),
+        }
+    }
+}
+
+impl<H: HasherT> KeyValueStore for SparseMerkleTree<H> {
+    type Key = Key;
+    type Value = Vec<u8>;
+
+    fn read(&self, key: &Key) -> Option<Vec<u8>> {
+        self.get(key).map(<[u8]>::to_vec)
+    }
+
+    fn write(&mut self, key: Key, value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        match value {
+            Some(value) => self.insert(key, value),
+            None => self.delete(&key),
+        }
+    }
+}
+
+/// Handle to roll a [`Journaled`] store back to, or to commit.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "a checkpoint must be committed or reverted"]
+pub struct Checkpoint(usize);
+
+/// Store with a journal of the writes made since its oldest open checkpoint. Reads go
+/// through `Deref`, writes through [`Journaled::write`].
+#[derive(Clone, Debug, Default)]
+pub struct Journaled<S: KeyValueStore> {
+    inner: S,
+    // Previous value of every key written since the oldest open checkpoint
+    journal: Vec<(S::Key, Option<S::Value>)>,
+    // Journal length when every open checkpoint was taken, oldest first
+    checkpoints: Vec<usize>,
+}
+
+impl<S: KeyValueStore> Journaled<S> {
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            journal: Vec::new(),
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Returns the store, dropping the journal.
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    /// Number of open checkpoints.
+    pub fn depth(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    /// Sets the value of `key`, or removes it if `value` is `None`. Returns the previous value.
+    pub fn write(&mut self, key: S::Key, value: Option<S::Value>) -> Option<S::Value>
+    where
+        S::Value: Clone,
+    {
+        let previous = self.inner.write(key.clone(), value);
+        if !self.checkpoints.is_empty() {
+            self.journal.push((key, previous.clone()));
+        }
+        previous
+    }
+
+    /// Opens a checkpoint at the current content of the store.
+    pub fn checkpoint(&mut self) -> Checkpoint {
+        self.checkpoints.push(self.journal.len());
+        Checkpoint(self.checkpoints.len() - 1)
+    }
+
+    /// Undoes every write made since `checkpoint`, closing it and the checkpoints opened
+    /// after it.
+    pub fn revert(&mut self, checkpoint: Checkpoint) {
+        let Some(&start) = self.checkpoints.get(checkpoint.0) else {
+            return;
+        };
+        for (key, previous) in self.journal.drain(start..).rev() {
+            self.inner.write(key, previous);
+        }
+        self.checkpoints.truncate(checkpoint.0);
+    }
+
+    /// Keeps the writes made since `checkpoint`, closing it and the checkpoints opened after
+    /// it. The writes are forgotten once no checkpoint is open.
+    pub fn commit(&mut self, checkpoint: Checkpoint) {
+        self.checkpoints.truncate(checkpoint.0);
+        if self.checkpoints.is_empty() {
+            self.journal.clear();
+        }
+    }
+}
+
+impl<S: KeyValueStore> Deref for Journaled<S> {
+    type Target = S;
+
+    fn deref(&self) -> &S {
+        &self.inner
+    }
+}
+
+impl<S: KeyValueStore> KeyValueStore for Journaled<S>
+where
+    S::Value: Clone,
+{
+    type Key = S::Key;
+    type Value = S::Value;
+
+    fn read(&self, key: &S::Key) -> Option<S::Value> {
+        self.inner.read(key)
+    }
+
+    fn write(&mut self, key: S::Key, value: Option<S::Value>) -> Option<S::Value> {
+        Journaled::write(self, key, value)
+    }
+}
+
+// Only the store is serialized, without the journal of the open checkpoints
+impl<S: KeyValueStore + Serialize> Serialize for Journaled<S> {
+    fn serialize<Ser: Serializer>(&self, serializer: Ser) -> Result<Ser::Ok, Ser::Error> {
+        self.inner.serialize(serializer)
+    }
+}
+
+impl<'de, S: KeyValueStore + Deserialize<'de>> Deserialize<'de> for Journaled<S> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        S::deserialize(deserializer).map(Self::new)
+    }
+}
+
+impl<S: KeyValueStore> RevertibleState for Journaled<S> {
+    type Checkpoint = Checkpoint;
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        Journaled::checkpoint(self)
+    }
+
+    fn revert(&mut self, checkpoint: Checkpoint) {
+        Journaled::revert(self, checkpoint)
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) {
+        Journaled::commit(self, checkpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+
+    #[test]
+    fn test_revert_and_commit() {
+        let mut store = Journaled::new(BTreeMap::from([(1, "a")]));
+        // Writes without an open checkpoint are not journaled
+        store.write(2, Some("b"));
+        assert_eq!(store.journal.len(), 0);
+
+        let outer = store.checkpoint();
+        store.write(1, Some("c"));
+        let inner = store.checkpoint();
+        store.write(2, None);
+        store.write(3, Some("d"));
+        store.revert(inner);
+        assert_eq!(*store, BTreeMap::from([(1, "c"), (2, "b")]));
+
+        let inner = store.checkpoint();
+        store.write(3, Some("e"));
+        store.commit(inner);
+        assert_eq!(store.depth(), 1);
+        // Committed writes are reverted with their enclosing checkpoint
+        store.revert(outer);
+        assert_eq!(*store, BTreeMap::from([(1, "a"), (2, "b")]));
+        assert_eq!(store.depth(), 0);
+        assert!(store.journal.is_empty());
+    }
+
+    #[test]
+    fn test_serializes_the_store() {
+        let mut store = Journaled::new(BTreeMap::from([(1, 10)]));
+        let checkpoint = store.checkpoint();
+        store.write(2, Some(20));
+        let json = serde_json::to_string(&store).unwrap();
+        assert_eq!(json, r#"{"1":10,"2":20}"#);
+        store.revert(checkpoint);
+
+        let restored: Journaled<BTreeMap<u32, u32>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.depth(), 0);
+        assert_eq!(restored.get(&2), Some(&20));
+    }
+
+    #[test]
+    fn test_revert_closes_later_checkpoints() {
+        let mut store = Journaled::new(BTreeMap::new());
+        let outer = store.checkpoint();
+        store.write(1, Some(1));
+        let _inner = store.checkpoint();
+        store.write(2, Some(2));
+        store.revert(outer);
+        assert!(store.is_empty());
+        assert_eq!(store.depth(), 0);
+    }
+
+    #[test]
+    fn test_reverts_to_previous_root() {
+        let mut tree = Journaled::new(SparseMerkleTree::<KeccakHasher>::new());
+        tree.write([1; 32], Some(b"one".to_vec()));
+        let root = tree.root();
+
+        let checkpoint = tree.checkpoint();
+        tree.write([1; 32], Some(b"uno".to_vec()));
+        tree.write([2; 32], Some(b"two".to_vec()));
+        tree.write([1; 32], None);
+        assert_ne!(tree.root(), root);
+        tree.revert(checkpoint);
+        assert_eq!(tree.root(), root);
+        assert_eq!(tree.get(&[1; 32]), Some(&b"one"[..]));
+        assert_eq!(tree.len(), 1);
+    }
+}
@@ -1,3 +1,28 @@
+pub mod bisection;
 pub mod block;
+pub mod block_store;
+pub mod builder;
+pub mod chain_tree;
+pub mod codec;
+pub mod data_availability;
+pub mod forced_inclusion;
+pub mod fraud_proof;
+pub mod genesis;
+pub mod groth16;
 pub mod hasher;
+pub mod journal;
+pub mod merkle;
+pub mod metering;
+pub mod mmr;
+pub mod parallel;
+pub mod poseidon;
+pub mod receipt;
+pub mod signed_header;
+pub mod smt;
+pub mod snapshot;
 pub mod traits;
+pub mod validation;
+pub mod validity;
+
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
@@ -0,0 +1,209 @@
+//! Binary Merkle tree over any [`HasherT`] with inclusion proofs.
+//!
+//! Leaves and inner nodes are domain separated, and an unpaired node is promoted to the
+//! next level unchanged, so no two distinct leaf lists share a root.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::traits::HasherT;
+use serde::{Deserialize, Serialize};
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hashes a leaf.
+pub fn leaf_hash<H: HasherT>(data: &[u8]) -> H::Output {
+    let mut buf = Vec::with_capacity(data.len() + 1);
+    buf.push(LEAF_PREFIX);
+    buf.extend_from_slice(data);
+    H::hash(&buf)
+}
+
+/// Hashes two child nodes.
+pub fn node_hash<H: HasherT>(left: &H::Output, right: &H::Output) -> H::Output {
+    let mut buf = Vec::with_capacity(left.as_ref().len() + right.as_ref().len() + 1);
+    buf.push(NODE_PREFIX);
+    buf.extend_from_slice(left.as_ref());
+    buf.extend_from_slice(right.as_ref());
+    H::hash(&buf)
+}
+
+/// Root of a tree without leaves.
+pub fn empty_root<H: HasherT>() -> H::Output {
+    H::hash(&[])
+}
+
+/// Computes the Merkle root of `leaves`.
+pub fn merkle_root<H: HasherT, L: AsRef<[u8]>>(leaves: &[L]) -> H::Output {
+    if leaves.is_empty() {
+        return empty_root::<H>();
+    }
+    let mut level: Vec<H::Output> = leaves.iter().map(|l| leaf_hash::<H>(l.as_ref())).collect();
+    while level.len() > 1 {
+        level = next_level::<H>(&level);
+    }
+    level[0]
+}
+
+/// Builds an inclusion proof for the leaf at `index`, or `None` if it is out of bounds.
+pub fn merkle_proof<H: HasherT, L: AsRef<[u8]>>(
+    leaves: &[L],
+    index: usize,
+) -> Option<MerkleProof<H::Output>> {
+    if index >= leaves.len() {
+        return None;
+    }
+    let mut siblings = Vec::new();
+    let mut level: Vec<H::Output> = leaves.iter().map(|l| leaf_hash::<H>(l.as_ref())).collect();
+    let mut position = index;
+    while level.len() > 1 {
+        if let Some(sibling) = level.get(position ^ 1) {
+            siblings.push(*sibling);
+        }
+        level = next_level::<H>(&level);
+        position /= 2;
+    }
+    Some(MerkleProof {
+        index: index as u64,
+        leaf_count: leaves.len() as u64,
+        siblings,
+    })
+}
+
+fn next_level<H: HasherT>(level: &[H::Output]) -> Vec<H::Output> {
+    level
+        .chunks(2)
+        .map(|pair| match pair {
+            [left, right] => node_hash::<H>(left, right),
+            [single] => *single,
+            _ => unreachable!("chunks of two"),
+        })
+        .collect()
+}
+
+/// Proof that a leaf is included at `index` in a tree of `leaf_count` leaves.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleProof<Hash> {
+    pub index: u64,
+    pub leaf_count: u64,
+    pub siblings: Vec<Hash>,
+}
+
+impl<Hash: AsRef<[u8]> + Copy> MerkleProof<Hash> {
+    /// Recomputes the root from `leaf`, or returns `None` if the proof is malformed.
+    pub fn compute_root<H: HasherT<Output = Hash>>(&self, leaf: &[u8]) -> Option<Hash> {
+        if self.index >= self.leaf_count {
+            return None;
+        }
+        let mut siblings = self.siblings.iter();
+        let mut node = leaf_hash::<H>(leaf);
+        let mut position = self.index;
+        let mut width = self.leaf_count;
+        while width > 1 {
+            if position % 2 == 1 {
+                node = node_hash::<H>(siblings.next()?, &node);
+            } else if position + 1 < width {
+                node = node_hash::<H>(&node, siblings.next()?);
+            }
+            position /= 2;
+            width = width.div_ceil(2);
+        }
+        // Every sibling must be consumed
+        siblings.next().is_none().then_some(node)
+    }
+
+    /// Verifies that `leaf` is included under `root`.
+    pub fn verify<H: HasherT<Output = Hash>>(&self, root: &Hash, leaf: &[u8]) -> bool
+    where
+        Hash: Eq,
+    {
+        self.compute_root::<H>(leaf).as_ref() == Some(root)
+    }
+}
+
+impl<Hash: Encode> Encode for MerkleProof<Hash> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.index.encode_to(out);
+        self.leaf_count.encode_to(out);
+        self.siblings.encode_to(out);
+    }
+}
+
+impl<Hash: Decode> Decode for MerkleProof<Hash> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            index: u64::decode_from(input)?,
+            leaf_count: u64::decode_from(input)?,
+            siblings: Vec::decode_from(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+
+    fn leaves(n: u8) -> Vec<Vec<u8>> {
+        (0..n).map(|i| vec![i; i as usize + 1]).collect()
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_leaf() {
+        for n in 1..=9 {
+            let leaves = leaves(n);
+            let root = merkle_root::<KeccakHasher, _>(&leaves);
+            for (index, leaf) in leaves.iter().enumerate() {
+                let proof = merkle_proof::<KeccakHasher, _>(&leaves, index).unwrap();
+                assert!(proof.verify::<KeccakHasher>(&root, leaf));
+                // The proof does not verify a different leaf
+                assert!(!proof.verify::<KeccakHasher>(&root, b"other"));
+            }
+            assert!(merkle_proof::<KeccakHasher, _>(&leaves, n as usize).is_none());
+        }
+    }
+
+    #[test]
+    fn test_root_layout() {
+        let leaves = leaves(3);
+        let l: Vec<_> = leaves
+            .iter()
+            .map(|l| leaf_hash::<KeccakHasher>(l))
+            .collect();
+        let expected = node_hash::<KeccakHasher>(&node_hash::<KeccakHasher>(&l[0], &l[1]), &l[2]);
+        assert_eq!(merkle_root::<KeccakHasher, _>(&leaves), expected);
+        assert_eq!(
+            merkle_root::<KeccakHasher, Vec<u8>>(&[]),
+            empty_root::<KeccakHasher>()
+        );
+        // A single leaf tree is not its bare leaf data hash
+        assert_ne!(
+            merkle_root::<KeccakHasher, _>(&leaves[..1]),
+            KeccakHasher::hash(&leaves[0])
+        );
+    }
+
+    #[test]
+    fn test_rejects_tampered_proof() {
+        let leaves = leaves(5);
+        let root = merkle_root::<KeccakHasher, _>(&leaves);
+        let proof = merkle_proof::<KeccakHasher, _>(&leaves, 2).unwrap();
+
+        let mut wrong_index = proof.clone();
+        wrong_index.index = 3;
+        assert!(!wrong_index.verify::<KeccakHasher>(&root, &leaves[2]));
+
+        let mut extra_sibling = proof.clone();
+        extra_sibling.siblings.push(root);
+        assert!(!extra_sibling.verify::<KeccakHasher>(&root, &leaves[2]));
+
+        let mut out_of_range = proof.clone();
+        out_of_range.index = out_of_range.leaf_count;
+        assert!(
+            out_of_range
+                .compute_root::<KeccakHasher>(&leaves[2])
+                .is_none()
+        );
+
+        let decoded = MerkleProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(proof, decoded);
+    }
+}
@@ -0,0 +1,133 @@
+//! Gas metering of transaction execution.
+//!
+//! Every transaction is applied with a meter limited to the gas left in its block. The state
+//! transition function charges the resources it uses to the meter, and fails with
+//! [`OutOfGas`] once the limit is reached.
+
+/// Unit of the resources consumed by a transaction, defined by the application.
+pub type Gas = u64;
+
+/// Gas limit exceeded.
+#[derive(Debug, thiserror::Error, Clone, Copy, PartialEq, Eq)]
+#[error("Out of gas: {} requested with {} of {} used", requested, used, limit)]
+pub struct OutOfGas {
+    pub limit: Gas,
+    pub used: Gas,
+    pub requested: Gas,
+}
+
+/// Tracks the gas consumed against a limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GasMeter {
+    limit: Gas,
+    used: Gas,
+    // Whether a charge was refused for exceeding the limit
+    out_of_gas: bool,
+}
+
+impl GasMeter {
+    pub fn new(limit: Gas) -> Self {
+        Self {
+            limit,
+            used: 0,
+            out_of_gas: false,
+        }
+    }
+
+    pub fn unlimited() -> Self {
+        Self::new(Gas::MAX)
+    }
+
+    pub fn limit(&self) -> Gas {
+        self.limit
+    }
+
+    pub fn used(&self) -> Gas {
+        self.used
+    }
+
+    pub fn remaining(&self) -> Gas {
+        self.limit - self.used
+    }
+
+    /// Whether a charge to the meter, or to a scope of it, exceeded the limit.
+    pub fn is_out_of_gas(&self) -> bool {
+        self.out_of_gas
+    }
+
+    /// Charges `gas` to the meter. Exceeding the limit consumes all the remaining gas.
+    pub fn consume(&mut self, gas: Gas) -> Result<(), OutOfGas> {
+        if gas > self.remaining() {
+            let error = OutOfGas {
+                limit: self.limit,
+                used: self.used,
+                requested: gas,
+            };
+            self.used = self.limit;
+            self.out_of_gas = true;
+            return Err(error);
+        }
+        self.used += gas;
+        Ok(())
+    }
+
+    /// Runs `f` with a meter limited to the remaining gas, then charges the gas it consumed,
+    /// whether it succeeded or not. Returns the result of `f` and the consumed gas.
+    pub fn scoped<T>(&mut self, f: impl FnOnce(&mut GasMeter) -> T) -> (T, Gas) {
+        let mut scope = GasMeter::new(self.remaining());
+        let result = f(&mut scope);
+        self.used += scope.used;
+        self.out_of_gas |= scope.out_of_gas;
+        (result, scope.used)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_consume() {
+        let mut meter = GasMeter::new(10);
+        assert_eq!(meter.consume(4), Ok(()));
+        assert_eq!(meter.remaining(), 6);
+        assert_eq!(
+            meter.consume(7),
+            Err(OutOfGas {
+                limit: 10,
+                used: 4,
+                requested: 7
+            })
+        );
+        // Running out of gas consumes everything left
+        assert_eq!(meter.used(), 10);
+        assert!(meter.is_out_of_gas());
+        assert!(meter.consume(1).is_err());
+        assert_eq!(meter.consume(0), Ok(()));
+    }
+
+    #[test]
+    fn test_scoped() {
+        let mut meter = GasMeter::new(10);
+        let (result, used) = meter.scoped(|scope| {
+            assert_eq!(scope.limit(), 10);
+            scope.consume(3)
+        });
+        assert_eq!((result, used), (Ok(()), 3));
+        assert!(!meter.is_out_of_gas());
+
+        // Failed scopes are charged too
+        let (result, used) = meter.scoped(|scope| scope.consume(8));
+        assert!(result.is_err());
+        assert_eq!(used, 7);
+        assert_eq!(meter.remaining(), 0);
+        assert!(meter.is_out_of_gas());
+
+        // Using exactly the remaining gas does not run out
+        let mut meter = GasMeter::new(10);
+        let (result, _) = meter.scoped(|scope| scope.consume(10));
+        assert_eq!(result, Ok(()));
+        assert_eq!(meter.remaining(), 0);
+        assert!(!meter.is_out_of_gas());
+    }
+}
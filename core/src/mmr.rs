@@ -0,0 +1,457 @@
+//! Merkle Mountain Range accumulator of block hashes.
+//!
+//! The range is a list of perfect binary trees (peaks) of strictly decreasing height. Every
+//! node only depends on the leaves below it, so the nodes of any earlier size are a prefix
+//! of the current ones and roots and proofs can be produced for any past size.
+//!
+//! Appending only reads the peaks, so a range can also be resumed from the peaks of an
+//! earlier size, e.g. from a snapshot. Leaves before that size are then unknown, and so are
+//! the roots and proofs that depend on them.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::merkle::{leaf_hash, node_hash};
+use crate::traits::HasherT;
+use serde::{Deserialize, Serialize};
+
+const ROOT_PREFIX: u8 = 0x02;
+
+/// Height and leaf offset of each peak of a range of `leaf_count` leaves, highest first.
+fn peak_positions(leaf_count: u64) -> impl Iterator<Item = (usize, u64)> {
+    let mut start = 0;
+    (0..u64::BITS as usize)
+        .rev()
+        .filter(move |height| leaf_count >> height & 1 == 1)
+        .map(move |height| {
+            let peak = (height, start);
+            start += 1 << height;
+            peak
+        })
+}
+
+/// Bags the peaks into a root that also commits to the number of leaves.
+fn bag_peaks<H: HasherT>(leaf_count: u64, peaks: &[H::Output]) -> H::Output {
+    let mut buf = vec![ROOT_PREFIX];
+    buf.extend_from_slice(&leaf_count.to_le_bytes());
+    for peak in peaks {
+        buf.extend_from_slice(peak.as_ref());
+    }
+    H::hash(&buf)
+}
+
+/// Nodes of one height, from the first known one on.
+#[derive(Clone, Debug)]
+struct Level<Hash> {
+    // Index of the first node, past the nodes dropped when resuming from peaks
+    offset: u64,
+    nodes: Vec<Hash>,
+}
+
+impl<Hash> Level<Hash> {
+    fn len(&self) -> u64 {
+        self.offset + self.nodes.len() as u64
+    }
+
+    fn get(&self, index: u64) -> Option<&Hash> {
+        self.nodes
+            .get(usize::try_from(index.checked_sub(self.offset)?).ok()?)
+    }
+}
+
+/// Append-only accumulator of hashes, typically the hashes of the canonical blocks.
+#[derive(Clone, Debug)]
+pub struct MerkleMountainRange<H: HasherT> {
+    // Nodes per height, `levels[0]` holding the leaf nodes
+    levels: Vec<Level<H::Output>>,
+    leaves: Vec<H::Output>,
+    // Number of leaves only known through the peaks the range was resumed from
+    pruned: u64,
+}
+
+impl<H: HasherT> Default for MerkleMountainRange<H> {
+    fn default() -> Self {
+        Self {
+            levels: vec![Level {
+                offset: 0,
+                nodes: Vec::new(),
+            }],
+            leaves: Vec::new(),
+            pruned: 0,
+        }
+    }
+}
+
+impl<H: HasherT> MerkleMountainRange<H> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resumes the range of `leaf_count` leaves with the given `peaks`, highest first, or
+    /// returns `None` if their number does not match. Its earlier leaves are unknown.
+    pub fn from_peaks(leaf_count: u64, peaks: &[H::Output]) -> Option<Self> {
+        let mut positions = peak_positions(leaf_count).peekable();
+        let height = positions.peek().map_or(0, |(height, _)| *height);
+        let mut levels: Vec<_> = (0..=height)
+            .map(|height| Level {
+                offset: leaf_count >> height,
+                nodes: Vec::new(),
+            })
+            .collect();
+        let mut count = 0;
+        for ((height, _), peak) in positions.zip(peaks) {
+            let level = &mut levels[height];
+            level.offset -= 1;
+            level.nodes.push(*peak);
+            count += 1;
+        }
+        (count == peaks.len() && count == leaf_count.count_ones() as usize).then_some(Self {
+            levels,
+            leaves: Vec::new(),
+            pruned: leaf_count,
+        })
+    }
+
+    /// Number of appended leaves.
+    pub fn len(&self) -> u64 {
+        self.pruned + self.leaves.len() as u64
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the leaf appended at `index`.
+    pub fn leaf(&self, index: u64) -> Option<&H::Output> {
+        self.leaves
+            .get(usize::try_from(index.checked_sub(self.pruned)?).ok()?)
+    }
+
+    /// Appends `leaf`, returning its index.
+    pub fn push(&mut self, leaf: H::Output) -> u64 {
+        let index = self.len();
+        self.leaves.push(leaf);
+        self.levels[0].nodes.push(leaf_hash::<H>(leaf.as_ref()));
+        let mut height = 0;
+        while self.levels[height].len().is_multiple_of(2) {
+            let nodes = &self.levels[height].nodes;
+            let parent = node_hash::<H>(&nodes[nodes.len() - 2], &nodes[nodes.len() - 1]);
+            if self.levels.len() == height + 1 {
+                self.levels.push(Level {
+                    offset: 0,
+                    nodes: Vec::new(),
+                });
+            }
+            self.levels[height + 1].nodes.push(parent);
+            height += 1;
+        }
+        index
+    }
+
+    /// Returns the peaks of the current range, highest first.
+    pub fn peaks(&self) -> Vec<H::Output> {
+        self.peaks_at(self.len())
+            .expect("peaks of the current range are known")
+    }
+
+    /// Returns the peaks the range had when it held `leaf_count` leaves, if they are known.
+    pub fn peaks_at(&self, leaf_count: u64) -> Option<Vec<H::Output>> {
+        if leaf_count < self.pruned || leaf_count > self.len() {
+            return None;
+        }
+        peak_positions(leaf_count)
+            .map(|(height, start)| self.node(height, start >> height))
+            .collect()
+    }
+
+    fn node(&self, height: usize, index: u64) -> Option<H::Output> {
+        self.levels.get(height)?.get(index).copied()
+    }
+
+    /// Returns the root of the current range.
+    pub fn root(&self) -> H::Output {
+        bag_peaks::<H>(self.len(), &self.peaks())
+    }
+
+    /// Returns the root the range had when it held `leaf_count` leaves.
+    pub fn root_at(&self, leaf_count: u64) -> Option<H::Output> {
+        Some(bag_peaks::<H>(leaf_count, &self.peaks_at(leaf_count)?))
+    }
+
+    /// Builds an inclusion proof of the leaf at `index` against the current root.
+    pub fn prove(&self, index: u64) -> Option<MmrProof<H::Output>> {
+        self.prove_at(index, self.len())
+    }
+
+    /// Builds an inclusion proof of the leaf at `index` against the root the range had when
+    /// it held `leaf_count` leaves.
+    pub fn prove_at(&self, index: u64, leaf_count: u64) -> Option<MmrProof<H::Output>> {
+        if index >= leaf_count || index < self.pruned || leaf_count > self.len() {
+            return None;
+        }
+        let mut peaks = Vec::new();
+        let mut siblings = Vec::new();
+        for (height, start) in peak_positions(leaf_count) {
+            if (start..start + (1 << height)).contains(&index) {
+                siblings = (0..height)
+                    .map(|level| self.node(level, (index >> level) ^ 1))
+                    .collect::<Option<_>>()?;
+            } else {
+                peaks.push(self.node(height, start >> height)?);
+            }
+        }
+        Some(MmrProof {
+            leaf_index: index,
+            leaf_count,
+            siblings,
+            peaks,
+        })
+    }
+
+    /// Extends `proof`, built for an earlier size, to the root the range had when it held
+    /// `leaf_count` leaves. Leaves only known through the peaks the range was resumed from
+    /// can then still be proven, given a proof of them at the size it was resumed at.
+    pub fn extend_proof(
+        &self,
+        proof: &MmrProof<H::Output>,
+        leaf_count: u64,
+    ) -> Option<MmrProof<H::Output>> {
+        let index = proof.leaf_index;
+        if index >= leaf_count || proof.leaf_count > leaf_count || leaf_count > self.len() {
+            return None;
+        }
+        let mut siblings = proof.siblings.clone();
+        let mut peaks = Vec::new();
+        for (height, start) in peak_positions(leaf_count) {
+            if (start..start + (1 << height)).contains(&index) {
+                for level in proof.siblings.len()..height {
+                    siblings.push(self.node(level, (index >> level) ^ 1)?);
+                }
+            } else {
+                peaks.push(self.node(height, start >> height)?);
+            }
+        }
+        Some(MmrProof {
+            leaf_index: index,
+            leaf_count,
+            siblings,
+            peaks,
+        })
+    }
+}
+
+/// Proof that a leaf is included at `leaf_index` in a range of `leaf_count` leaves.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MmrProof<Hash> {
+    pub leaf_index: u64,
+    pub leaf_count: u64,
+    /// Siblings from the leaf up to its peak
+    pub siblings: Vec<Hash>,
+    /// The other peaks, highest first
+    pub peaks: Vec<Hash>,
+}
+
+impl<Hash: AsRef<[u8]> + Copy + Eq> MmrProof<Hash> {
+    /// Recomputes the root from `leaf`, or returns `None` if the proof is malformed.
+    pub fn compute_root<H: HasherT<Output = Hash>>(&self, leaf: &Hash) -> Option<Hash> {
+        if self.leaf_index >= self.leaf_count {
+            return None;
+        }
+        let positions: Vec<_> = peak_positions(self.leaf_count).collect();
+        if self.peaks.len() + 1 != positions.len() {
+            return None;
+        }
+        let slot = positions
+            .iter()
+            .position(|(height, start)| self.leaf_index < start + (1 << height))?;
+        if self.siblings.len() != positions[slot].0 {
+            return None;
+        }
+
+        let mut node = leaf_hash::<H>(leaf.as_ref());
+        for (level, sibling) in self.siblings.iter().enumerate() {
+            node = if self.leaf_index >> level & 1 == 1 {
+                node_hash::<H>(sibling, &node)
+            } else {
+                node_hash::<H>(&node, sibling)
+            };
+        }
+        let mut peaks = self.peaks.clone();
+        peaks.insert(slot, node);
+        Some(bag_peaks::<H>(self.leaf_count, &peaks))
+    }
+
+    /// Verifies that `leaf` is included under `root`.
+    pub fn verify<H: HasherT<Output = Hash>>(&self, root: &Hash, leaf: &Hash) -> bool {
+        self.compute_root::<H>(leaf).as_ref() == Some(root)
+    }
+}
+
+impl<Hash: Encode> Encode for MmrProof<Hash> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.leaf_index.encode_to(out);
+        self.leaf_count.encode_to(out);
+        self.siblings.encode_to(out);
+        self.peaks.encode_to(out);
+    }
+}
+
+impl<Hash: Decode> Decode for MmrProof<Hash> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            leaf_index: u64::decode_from(input)?,
+            leaf_count: u64::decode_from(input)?,
+            siblings: Vec::decode_from(input)?,
+            peaks: Vec::decode_from(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+    use crate::test_utils::test_chain;
+    use crate::traits::BlockHeaderT;
+
+    type Mmr = MerkleMountainRange<KeccakHasher>;
+
+    fn leaf(i: u64) -> [u8; 32] {
+        KeccakHasher::hash(&i.to_le_bytes())
+    }
+
+    #[test]
+    fn test_peaks_follow_binary_decomposition() {
+        let mut mmr = Mmr::new();
+        for i in 0..11 {
+            assert_eq!(mmr.push(leaf(i)), i);
+        }
+        // 11 = 8 + 2 + 1
+        let peaks = mmr.peaks();
+        assert_eq!(peaks.len(), 3);
+        assert_eq!(peaks[2], leaf_hash::<KeccakHasher>(&leaf(10)));
+        assert_eq!(
+            peaks[1],
+            node_hash::<KeccakHasher>(
+                &leaf_hash::<KeccakHasher>(&leaf(8)),
+                &leaf_hash::<KeccakHasher>(&leaf(9))
+            )
+        );
+    }
+
+    #[test]
+    fn test_proofs_verify_for_every_size() {
+        let mut mmr = Mmr::new();
+        for n in 1..=17 {
+            mmr.push(leaf(n - 1));
+            let root = mmr.root();
+            for index in 0..n {
+                let proof = mmr.prove(index).unwrap();
+                assert!(proof.verify::<KeccakHasher>(&root, &leaf(index)));
+                assert!(!proof.verify::<KeccakHasher>(&root, &leaf(n)));
+            }
+            assert!(mmr.prove(n).is_none());
+        }
+    }
+
+    #[test]
+    fn test_historical_roots_and_proofs() {
+        let mut mmr = Mmr::new();
+        let mut roots = vec![mmr.root()];
+        for i in 0..20 {
+            mmr.push(leaf(i));
+            roots.push(mmr.root());
+        }
+        for (count, root) in roots.iter().enumerate() {
+            let count = count as u64;
+            assert_eq!(mmr.root_at(count).as_ref(), Some(root));
+            for index in 0..count {
+                let proof = mmr.prove_at(index, count).unwrap();
+                assert!(proof.verify::<KeccakHasher>(root, &leaf(index)));
+            }
+        }
+        assert!(mmr.root_at(21).is_none());
+        assert!(mmr.prove_at(3, 21).is_none());
+    }
+
+    #[test]
+    fn test_rejects_tampered_proof() {
+        let mut mmr = Mmr::new();
+        for i in 0..13 {
+            mmr.push(leaf(i));
+        }
+        let root = mmr.root();
+        let proof = mmr.prove(5).unwrap();
+
+        let mut wrong_index = proof.clone();
+        wrong_index.leaf_index = 4;
+        assert!(!wrong_index.verify::<KeccakHasher>(&root, &leaf(5)));
+
+        let mut wrong_count = proof.clone();
+        wrong_count.leaf_count = 14;
+        assert!(!wrong_count.verify::<KeccakHasher>(&root, &leaf(5)));
+
+        let mut extra_sibling = proof.clone();
+        extra_sibling.siblings.push(root);
+        assert!(
+            extra_sibling
+                .compute_root::<KeccakHasher>(&leaf(5))
+                .is_none()
+        );
+
+        let decoded = MmrProof::from_bytes(&proof.to_bytes()).unwrap();
+        assert_eq!(proof, decoded);
+    }
+
+    #[test]
+    fn test_resumes_from_peaks() {
+        let mut mmr = Mmr::new();
+        for i in 0..11 {
+            mmr.push(leaf(i));
+        }
+        let mut resumed = Mmr::from_peaks(11, &mmr.peaks()).unwrap();
+        assert_eq!(resumed.len(), 11);
+        assert_eq!(resumed.root(), mmr.root());
+        assert!(Mmr::from_peaks(12, &mmr.peaks()).is_none());
+        assert!(Mmr::from_peaks(11, &mmr.peaks()[1..]).is_none());
+        assert_eq!(Mmr::from_peaks(0, &[]).unwrap().root(), Mmr::new().root());
+
+        for i in 11..30 {
+            assert_eq!(resumed.push(leaf(i)), mmr.push(leaf(i)));
+            assert_eq!(resumed.root(), mmr.root());
+        }
+        // Only the leaves appended after resuming are known, and proven
+        assert_eq!(resumed.leaf(12), mmr.leaf(12));
+        assert!(resumed.leaf(10).is_none());
+        assert!(resumed.root_at(10).is_none());
+        assert_eq!(resumed.root_at(20), mmr.root_at(20));
+        for index in 11..30 {
+            assert_eq!(resumed.prove(index), mmr.prove(index));
+            assert_eq!(resumed.prove_at(index, 25), mmr.prove_at(index, 25));
+        }
+        assert!(resumed.prove(10).is_none());
+
+        // Earlier leaves are proven from their proof at the resumed size, the first one
+        // always as it lies under the highest peak
+        for count in 11..30 {
+            let extended = resumed.extend_proof(&mmr.prove_at(0, 11).unwrap(), count);
+            assert_eq!(extended, mmr.prove_at(0, count));
+            for index in 1..11 {
+                let proof = mmr.prove_at(index, 11).unwrap();
+                if let Some(extended) = resumed.extend_proof(&proof, count) {
+                    assert_eq!(Some(extended), mmr.prove_at(index, count));
+                }
+            }
+        }
+        assert!(resumed.extend_proof(&mmr.prove(0).unwrap(), 11).is_none());
+    }
+
+    #[test]
+    fn test_accumulates_block_hashes() {
+        let chain = test_chain(6);
+        let mut mmr = Mmr::new();
+        for block in &chain {
+            mmr.push(block.header.hash());
+        }
+        let root = mmr.root();
+        let proof = mmr.prove(2).unwrap();
+        assert!(proof.verify::<KeccakHasher>(&root, &chain[2].header.hash()));
+    }
+}
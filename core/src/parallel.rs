@@ -0,0 +1,741 @@
+//! Optimistic parallel execution of the transactions of a block, after Block-STM.
+//!
+//! Transactions are executed speculatively by a pool of threads against a multi-version
+//! memory, which holds the values written to every key by each transaction of the block. A
+//! transaction reads the value written by the closest preceding transaction, or the state
+//! before the block if there is none, and its reads are validated once it is executed: if a
+//! preceding transaction has since written another version of a key it read, it is executed
+//! again. The writes of a transaction about to be executed again are marked as estimates,
+//! and a transaction reading an estimate waits for the writer to be executed first.
+//!
+//! The scheduler always hands out the lowest transaction to execute or to validate, and
+//! execution ends once every transaction has been validated against the final writes of
+//! those preceding it. The resulting state and receipts are therefore the ones of executing
+//! the transactions sequentially, in block order.
+use crate::journal::KeyValueStore;
+use crate::metering::{Gas, GasMeter};
+use crate::receipt::Receipt;
+use crate::traits::{
+    BlockHeaderT, BlockT, ParallelTransition, StateTransitionFunction, TransactionOf,
+};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::num::NonZeroUsize;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering::SeqCst};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Key-value state a transaction is executed against.
+pub trait StateView {
+    type Key;
+    type Value;
+
+    // Returns the value of `key`.
+    fn read(&mut self, key: &Self::Key) -> Option<Self::Value>;
+
+    // Sets the value of `key`, or removes it if `value` is `None`.
+    fn write(&mut self, key: Self::Key, value: Option<Self::Value>);
+}
+
+// Sequential execution reads and writes the state directly
+impl<S: KeyValueStore> StateView for S {
+    type Key = S::Key;
+    type Value = S::Value;
+
+    fn read(&mut self, key: &S::Key) -> Option<S::Value> {
+        KeyValueStore::read(self, key)
+    }
+
+    fn write(&mut self, key: S::Key, value: Option<S::Value>) {
+        KeyValueStore::write(self, key, value);
+    }
+}
+
+type KeyOf<S> = <<S as StateTransitionFunction>::State as KeyValueStore>::Key;
+type ValueOf<S> = <<S as StateTransitionFunction>::State as KeyValueStore>::Value;
+
+type TxIndex = usize;
+type Incarnation = usize;
+type WriteSet<K, V> = Vec<(K, Option<V>)>;
+// Values written to every key, by writing transaction
+type VersionedValues<K, V> = HashMap<K, BTreeMap<TxIndex, Entry<V>>>;
+
+/// Number of independently locked partitions of the multi-version memory.
+const SHARDS: usize = 64;
+
+// A poisoned lock means that a worker panicked, which is raised again once the workers are
+// joined
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Value written to a key by an execution of a transaction.
+enum Entry<V> {
+    Written(Incarnation, Option<V>),
+    // The transaction is to be executed again and will likely write the key again
+    Estimate,
+}
+
+/// Version of a key read by a transaction.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ReadOrigin {
+    // State before the block
+    Storage,
+    // Write of a preceding transaction
+    Version(TxIndex, Incarnation),
+}
+
+enum ReadResult<V> {
+    Storage,
+    Version(TxIndex, Incarnation, Option<V>),
+    Estimate(TxIndex),
+}
+
+/// Values written to every key by each transaction of the block, with the reads and writes
+/// of the last execution of every transaction.
+struct MultiVersionMemory<K, V> {
+    shards: Vec<Mutex<VersionedValues<K, V>>>,
+    reads: Vec<Mutex<Vec<(K, ReadOrigin)>>>,
+    writes: Vec<Mutex<WriteSet<K, V>>>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> MultiVersionMemory<K, V> {
+    fn new(transactions: usize) -> Self {
+        Self {
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            reads: (0..transactions).map(|_| Mutex::default()).collect(),
+            writes: (0..transactions).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn shard(&self, key: &K) -> MutexGuard<'_, VersionedValues<K, V>> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        lock(&self.shards[hasher.finish() as usize % SHARDS])
+    }
+
+    // Returns the value of `key` written by the closest transaction preceding `index`.
+    fn read(&self, key: &K, index: TxIndex) -> ReadResult<V> {
+        let shard = self.shard(key);
+        let Some((&writer, entry)) = shard
+            .get(key)
+            .and_then(|entries| entries.range(..index).next_back())
+        else {
+            return ReadResult::Storage;
+        };
+        match entry {
+            Entry::Written(incarnation, value) => {
+                ReadResult::Version(writer, *incarnation, value.clone())
+            }
+            Entry::Estimate => ReadResult::Estimate(writer),
+        }
+    }
+
+    // Records an execution of transaction `index`. Returns whether it wrote a key the
+    // previous execution did not, which may invalidate reads already validated.
+    fn record(
+        &self,
+        index: TxIndex,
+        incarnation: Incarnation,
+        reads: Vec<(K, ReadOrigin)>,
+        writes: WriteSet<K, V>,
+    ) -> bool {
+        for (key, value) in &writes {
+            self.shard(key)
+                .entry(key.clone())
+                .or_default()
+                .insert(index, Entry::Written(incarnation, value.clone()));
+        }
+        let previous = std::mem::take(&mut *lock(&self.writes[index]));
+        let written: HashSet<&K> = writes.iter().map(|(key, _)| key).collect();
+        for (key, _) in &previous {
+            if !written.contains(key)
+                && let Some(entries) = self.shard(key).get_mut(key)
+            {
+                entries.remove(&index);
+            }
+        }
+        let previous: HashSet<&K> = previous.iter().map(|(key, _)| key).collect();
+        let wrote_new_key = written.iter().any(|key| !previous.contains(key));
+
+        *lock(&self.reads[index]) = reads;
+        *lock(&self.writes[index]) = writes;
+        wrote_new_key
+    }
+
+    // Marks the writes of transaction `index` as estimates until it is executed again.
+    fn convert_writes_to_estimates(&self, index: TxIndex) {
+        for (key, _) in lock(&self.writes[index]).iter() {
+            if let Some(entry) = self
+                .shard(key)
+                .get_mut(key)
+                .and_then(|entries| entries.get_mut(&index))
+            {
+                *entry = Entry::Estimate;
+            }
+        }
+    }
+
+    // Returns whether transaction `index` would still read the versions it last read.
+    fn validate_reads(&self, index: TxIndex) -> bool {
+        lock(&self.reads[index])
+            .iter()
+            .all(|(key, origin)| match (self.read(key, index), origin) {
+                (ReadResult::Storage, ReadOrigin::Storage) => true,
+                (ReadResult::Version(writer, incarnation, _), ReadOrigin::Version(w, i)) => {
+                    writer == *w && incarnation == *i
+                }
+                _ => false,
+            })
+    }
+
+    // Returns the writes of the last execution of every transaction, in block order.
+    fn into_writes(self) -> Vec<WriteSet<K, V>> {
+        self.writes
+            .into_iter()
+            .map(|writes| writes.into_inner().unwrap_or_else(PoisonError::into_inner))
+            .collect()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    ReadyToExecute,
+    Executing,
+    Executed,
+    Aborting,
+}
+
+enum Task {
+    Execute(TxIndex, Incarnation),
+    Validate(TxIndex, Incarnation),
+}
+
+/// Collaborative scheduler of the execution and validation tasks of a block.
+struct Scheduler {
+    transactions: usize,
+    // Lowest transaction that may need to be executed and to be validated
+    execution_index: AtomicUsize,
+    validation_index: AtomicUsize,
+    // Incremented whenever one of the indexes above is lowered
+    decrease_count: AtomicUsize,
+    // Tasks handed out and not finished yet
+    active_tasks: AtomicUsize,
+    done: AtomicBool,
+    status: Vec<Mutex<(Incarnation, Status)>>,
+    // Transactions waiting for every transaction to be executed
+    dependencies: Vec<Mutex<Vec<TxIndex>>>,
+}
+
+impl Scheduler {
+    fn new(transactions: usize) -> Self {
+        Self {
+            transactions,
+            execution_index: AtomicUsize::new(0),
+            validation_index: AtomicUsize::new(0),
+            decrease_count: AtomicUsize::new(0),
+            active_tasks: AtomicUsize::new(0),
+            done: AtomicBool::new(false),
+            status: (0..transactions)
+                .map(|_| Mutex::new((0, Status::ReadyToExecute)))
+                .collect(),
+            dependencies: (0..transactions).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.done.load(SeqCst)
+    }
+
+    // Execution is done once no task is left, unless an index was lowered in the meantime.
+    fn check_done(&self) {
+        let observed = self.decrease_count.load(SeqCst);
+        if self
+            .execution_index
+            .load(SeqCst)
+            .min(self.validation_index.load(SeqCst))
+            >= self.transactions
+            && self.active_tasks.load(SeqCst) == 0
+            && observed == self.decrease_count.load(SeqCst)
+        {
+            self.done.store(true, SeqCst);
+        }
+    }
+
+    fn decrease_execution_index(&self, index: TxIndex) {
+        self.execution_index.fetch_min(index, SeqCst);
+        self.decrease_count.fetch_add(1, SeqCst);
+    }
+
+    fn decrease_validation_index(&self, index: TxIndex) {
+        self.validation_index.fetch_min(index, SeqCst);
+        self.decrease_count.fetch_add(1, SeqCst);
+    }
+
+    fn next_task(&self) -> Option<Task> {
+        if self.validation_index.load(SeqCst) < self.execution_index.load(SeqCst) {
+            self.next_validation()
+        } else {
+            self.next_execution()
+        }
+    }
+
+    fn try_incarnate(&self, index: TxIndex) -> Option<Task> {
+        let mut status = lock(self.status.get(index)?);
+        if status.1 != Status::ReadyToExecute {
+            return None;
+        }
+        status.1 = Status::Executing;
+        Some(Task::Execute(index, status.0))
+    }
+
+    fn next_execution(&self) -> Option<Task> {
+        if self.execution_index.load(SeqCst) >= self.transactions {
+            self.check_done();
+            return None;
+        }
+        self.active_tasks.fetch_add(1, SeqCst);
+        let task = self.try_incarnate(self.execution_index.fetch_add(1, SeqCst));
+        if task.is_none() {
+            self.active_tasks.fetch_sub(1, SeqCst);
+        }
+        task
+    }
+
+    fn next_validation(&self) -> Option<Task> {
+        if self.validation_index.load(SeqCst) >= self.transactions {
+            self.check_done();
+            return None;
+        }
+        self.active_tasks.fetch_add(1, SeqCst);
+        let index = self.validation_index.fetch_add(1, SeqCst);
+        if let Some(status) = self.status.get(index) {
+            let (incarnation, status) = *lock(status);
+            if status == Status::Executed {
+                return Some(Task::Validate(index, incarnation));
+            }
+        }
+        self.active_tasks.fetch_sub(1, SeqCst);
+        None
+    }
+
+    // Suspends the execution of `index` until `blocking` is executed. Returns false if it
+    // already is, in which case `index` can be executed again right away.
+    fn add_dependency(&self, index: TxIndex, blocking: TxIndex) -> bool {
+        let mut dependencies = lock(&self.dependencies[blocking]);
+        if lock(&self.status[blocking]).1 == Status::Executed {
+            return false;
+        }
+        lock(&self.status[index]).1 = Status::Aborting;
+        dependencies.push(index);
+        self.active_tasks.fetch_sub(1, SeqCst);
+        true
+    }
+
+    fn set_ready(&self, index: TxIndex) {
+        let mut status = lock(&self.status[index]);
+        *status = (status.0 + 1, Status::ReadyToExecute);
+    }
+
+    fn finish_execution(
+        &self,
+        index: TxIndex,
+        incarnation: Incarnation,
+        wrote_new_key: bool,
+    ) -> Option<Task> {
+        lock(&self.status[index]).1 = Status::Executed;
+        let dependencies = std::mem::take(&mut *lock(&self.dependencies[index]));
+        for &dependency in &dependencies {
+            self.set_ready(dependency);
+        }
+        if let Some(&lowest) = dependencies.iter().min() {
+            self.decrease_execution_index(lowest);
+        }
+        if self.validation_index.load(SeqCst) > index {
+            // Transactions after this one were validated against its previous writes
+            if wrote_new_key {
+                self.decrease_validation_index(index);
+            } else {
+                return Some(Task::Validate(index, incarnation));
+            }
+        }
+        self.active_tasks.fetch_sub(1, SeqCst);
+        None
+    }
+
+    fn try_validation_abort(&self, index: TxIndex, incarnation: Incarnation) -> bool {
+        let mut status = lock(&self.status[index]);
+        if *status != (incarnation, Status::Executed) {
+            return false;
+        }
+        status.1 = Status::Aborting;
+        true
+    }
+
+    fn finish_validation(&self, index: TxIndex, aborted: bool) -> Option<Task> {
+        if aborted {
+            self.set_ready(index);
+            self.decrease_validation_index(index + 1);
+            if self.execution_index.load(SeqCst) > index
+                && let Some(task) = self.try_incarnate(index)
+            {
+                return Some(task);
+            }
+        }
+        self.active_tasks.fetch_sub(1, SeqCst);
+        None
+    }
+}
+
+/// View of the state of a transaction executed in parallel, recording the versions it reads
+/// and buffering its writes.
+struct TransactionView<'a, S: KeyValueStore> {
+    index: TxIndex,
+    state: &'a S,
+    memory: &'a MultiVersionMemory<S::Key, S::Value>,
+    reads: Vec<(S::Key, ReadOrigin)>,
+    writes: BTreeMap<S::Key, Option<S::Value>>,
+    // Transaction whose estimate was read, which must be executed first
+    blocked_by: Option<TxIndex>,
+}
+
+impl<S> StateView for TransactionView<'_, S>
+where
+    S: KeyValueStore,
+    S::Key: Ord + Hash,
+    S::Value: Clone,
+{
+    type Key = S::Key;
+    type Value = S::Value;
+
+    fn read(&mut self, key: &S::Key) -> Option<S::Value> {
+        if let Some(value) = self.writes.get(key) {
+            return value.clone();
+        }
+        match self.memory.read(key, self.index) {
+            ReadResult::Storage => {
+                self.reads.push((key.clone(), ReadOrigin::Storage));
+                self.state.read(key)
+            }
+            ReadResult::Version(writer, incarnation, value) => {
+                self.reads
+                    .push((key.clone(), ReadOrigin::Version(writer, incarnation)));
+                value
+            }
+            // The execution is discarded, so whatever value is read does not matter
+            ReadResult::Estimate(writer) => {
+                self.blocked_by.get_or_insert(writer);
+                self.state.read(key)
+            }
+        }
+    }
+
+    fn write(&mut self, key: S::Key, value: Option<S::Value>) {
+        self.writes.insert(key, value);
+    }
+}
+
+// Result of a transaction with the gas it used, whether it succeeded or not
+type TransactionResult<S> = (Result<Receipt, <S as StateTransitionFunction>::Error>, Gas);
+
+/// Parallel execution of the transactions of a block.
+struct Execution<'a, S: ParallelTransition> {
+    state: &'a S::State,
+    transactions: &'a [TransactionOf<S>],
+    gas_limit: Gas,
+    memory: MultiVersionMemory<KeyOf<S>, ValueOf<S>>,
+    scheduler: Scheduler,
+    // Result of the last execution of every transaction
+    results: Vec<Mutex<Option<TransactionResult<S>>>>,
+}
+
+impl<'a, S> Execution<'a, S>
+where
+    S: ParallelTransition,
+    S::State: Sync,
+    KeyOf<S>: Ord + Hash + Send + Sync,
+    ValueOf<S>: Clone + Send + Sync,
+{
+    fn new(state: &'a S::State, transactions: &'a [TransactionOf<S>], gas_limit: Gas) -> Self {
+        Self {
+            state,
+            transactions,
+            gas_limit,
+            memory: MultiVersionMemory::new(transactions.len()),
+            scheduler: Scheduler::new(transactions.len()),
+            results: (0..transactions.len()).map(|_| Mutex::default()).collect(),
+        }
+    }
+
+    fn run(&self, threads: usize) {
+        if self.transactions.is_empty() {
+            return;
+        }
+        std::thread::scope(|scope| {
+            for _ in 1..threads {
+                scope.spawn(|| self.work());
+            }
+            self.work();
+        });
+    }
+
+    fn work(&self) {
+        let mut task = None;
+        while !self.scheduler.done() {
+            task = match task.take() {
+                Some(Task::Execute(index, incarnation)) => self.execute(index, incarnation),
+                Some(Task::Validate(index, incarnation)) => self.validate(index, incarnation),
+                None => {
+                    let task = self.scheduler.next_task();
+                    if task.is_none() {
+                        std::thread::yield_now();
+                    }
+                    task
+                }
+            };
+        }
+    }
+
+    fn view(&self, index: TxIndex) -> TransactionView<'_, S::State> {
+        TransactionView {
+            index,
+            state: self.state,
+            memory: &self.memory,
+            reads: Vec::new(),
+            writes: BTreeMap::new(),
+            blocked_by: None,
+        }
+    }
+
+    fn execute(&self, index: TxIndex, incarnation: Incarnation) -> Option<Task> {
+        loop {
+            let mut view = self.view(index);
+            // Every transaction may use the whole block gas limit, the gas left by the
+            // preceding transactions is only known once they are all executed
+            let mut meter = GasMeter::new(self.gas_limit);
+            let result = S::execute_transaction(&mut view, &self.transactions[index], &mut meter);
+            if let Some(blocking) = view.blocked_by {
+                if self.scheduler.add_dependency(index, blocking) {
+                    return None;
+                }
+                continue;
+            }
+            *lock(&self.results[index]) = Some((result, meter.used()));
+            let wrote_new_key = self.memory.record(
+                index,
+                incarnation,
+                view.reads,
+                view.writes.into_iter().collect(),
+            );
+            return self
+                .scheduler
+                .finish_execution(index, incarnation, wrote_new_key);
+        }
+    }
+
+    fn validate(&self, index: TxIndex, incarnation: Incarnation) -> Option<Task> {
+        let aborted = !self.memory.validate_reads(index)
+            && self.scheduler.try_validation_abort(index, incarnation);
+        if aborted {
+            self.memory.convert_writes_to_estimates(index);
+        }
+        self.scheduler.finish_validation(index, aborted)
+    }
+
+    /// Takes the result of the last execution of the transaction at `index`.
+    fn take_result(&self, index: TxIndex) -> TransactionResult<S> {
+        lock(&self.results[index])
+            .take()
+            .expect("every transaction is executed")
+    }
+
+    /// Executes the transaction at `index` again with `gas_limit`, once every transaction
+    /// was executed, so that it reads the final writes of the preceding ones.
+    fn execute_with_limit(&self, index: TxIndex, gas_limit: Gas) -> TransactionResult<S> {
+        let mut view = self.view(index);
+        let mut meter = GasMeter::new(gas_limit);
+        let result = S::execute_transaction(&mut view, &self.transactions[index], &mut meter);
+        (result, meter.used())
+    }
+
+    /// Writes of every transaction, in block order.
+    fn into_writes(self) -> Vec<WriteSet<KeyOf<S>, ValueOf<S>>> {
+        self.memory.into_writes()
+    }
+}
+
+/// Executes the transactions of a block in parallel on a pool of threads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ParallelExecutor {
+    threads: usize,
+}
+
+impl Default for ParallelExecutor {
+    fn default() -> Self {
+        Self::new(std::thread::available_parallelism().map_or(1, NonZeroUsize::get))
+    }
+}
+
+impl ParallelExecutor {
+    pub fn new(threads: usize) -> Self {
+        Self {
+            threads: threads.max(1),
+        }
+    }
+
+    pub fn threads(&self) -> usize {
+        self.threads
+    }
+
+    /// Applies `block` to `state` like [`StateTransitionFunction::apply_block`], executing
+    /// its transactions in parallel. If a transaction fails or the block gas limit is
+    /// exceeded, the block is rejected with the error sequential execution returns and none
+    /// of the writes of its transactions is applied.
+    pub fn apply_block<S>(
+        &self,
+        state: &mut S::State,
+        block: &S::Block,
+    ) -> Result<Vec<Receipt>, S::Error>
+    where
+        S: ParallelTransition,
+        S::State: Sync,
+        KeyOf<S>: Ord + Hash + Send + Sync,
+        ValueOf<S>: Clone + Send + Sync,
+    {
+        S::validate_block(state, block)?;
+        let mut meter = GasMeter::new(S::block_gas_limit(state));
+        let header = block.header().unsealed();
+        S::begin_block(state, &header)?;
+
+        let transactions = block.transactions();
+        let execution = Execution::<S>::new(state, transactions, meter.limit());
+        execution.run(self.threads.min(transactions.len()));
+
+        let mut receipts = Vec::with_capacity(transactions.len());
+        for index in 0..transactions.len() {
+            let (mut result, mut used) = execution.take_result(index);
+            // Executed sequentially, a transaction only has the gas left by the preceding
+            // ones: one that used more is executed again with that gas, to fail as it would
+            if used > meter.remaining() {
+                (result, used) = execution.execute_with_limit(index, meter.remaining());
+            }
+            let receipt = result?;
+            meter.consume(used)?;
+            receipts.push(Receipt {
+                resources_used: used,
+                ..receipt
+            });
+        }
+        for (key, value) in execution.into_writes().into_iter().flatten() {
+            KeyValueStore::write(state, key, value);
+        }
+        S::end_block(state, &header)?;
+        Ok(receipts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{
+        TestAccounts, TestBlock, TestTransfers, TransferError, child_block, test_chain, transfer,
+    };
+    use crate::traits::AppState;
+
+    const GAS_LIMIT: Gas = 100;
+
+    fn ledger(accounts: u16) -> TestAccounts {
+        let balances: Vec<_> = (0..accounts).map(|account| (account, 10_000)).collect();
+        TestAccounts::with_balances(&balances)
+    }
+
+    /// Pseudo-random transfers between `accounts` accounts.
+    fn transfers(count: usize, accounts: u16, seed: u64) -> TestBlock {
+        let mut rng = seed;
+        let mut next = move |bound: u64| {
+            rng = rng
+                .wrapping_mul(6364136223846793005)
+                .wrapping_add(1442695040888963407);
+            (rng >> 33) % bound
+        };
+        let transactions = (0..count)
+            .map(|_| {
+                let (from, to) = (next(accounts as u64), next(accounts as u64));
+                transfer(from as u16, to as u16, 1 + next(50))
+            })
+            .collect();
+        child_block(&test_chain(1)[0].header, b"transfers", transactions)
+    }
+
+    #[test]
+    fn test_matches_sequential_execution() {
+        // From a single account, where every transaction depends on the previous one, to
+        // mostly independent transactions
+        for (accounts, seed) in [(1, 1), (2, 2), (8, 3), (64, 4), (256, 5)] {
+            let block = transfers(200, accounts, seed);
+            let mut expected = ledger(accounts);
+            let expected_receipts = TestTransfers::apply_block(&mut expected, &block).unwrap();
+
+            for threads in [1, 4, 8] {
+                let mut state = ledger(accounts);
+                let receipts = ParallelExecutor::new(threads)
+                    .apply_block::<TestTransfers>(&mut state, &block)
+                    .unwrap();
+                assert_eq!(receipts, expected_receipts);
+                assert_eq!(state.state_root(), expected.state_root());
+            }
+        }
+    }
+
+    #[test]
+    fn test_rejects_failing_transaction() {
+        let mut block = transfers(100, 4, 7);
+        // Account 9 only receives the transfer after the one overdrawing it
+        block.transactions[40] = transfer(9, 0, 1);
+        block.transactions[60] = transfer(0, 9, 1);
+
+        let mut state = ledger(4);
+        assert!(matches!(
+            TestTransfers::apply_block(&mut state, &block),
+            Err(TransferError::InsufficientBalance(9))
+        ));
+        let mut state = ledger(4);
+        assert!(matches!(
+            ParallelExecutor::new(4).apply_block::<TestTransfers>(&mut state, &block),
+            Err(TransferError::InsufficientBalance(9))
+        ));
+        assert_eq!(state.state_root(), ledger(4).state_root());
+    }
+
+    #[test]
+    fn test_enforces_block_gas_limit() {
+        let block = transfers(GAS_LIMIT as usize + 1, 16, 8);
+        let mut state = ledger(16).with_gas_limit(GAS_LIMIT);
+        assert!(matches!(
+            TestTransfers::apply_block(&mut state, &block),
+            Err(TransferError::OutOfGas(_))
+        ));
+        let mut state = ledger(16).with_gas_limit(GAS_LIMIT);
+        assert!(matches!(
+            ParallelExecutor::new(4).apply_block::<TestTransfers>(&mut state, &block),
+            Err(TransferError::OutOfGas(_))
+        ));
+        assert_eq!(state.state_root(), ledger(16).state_root());
+
+        // A transaction failing past the block gas limit runs out of gas, as it does
+        // sequentially
+        let mut block = transfers(GAS_LIMIT as usize, 16, 9);
+        block.transactions.push(transfer(99, 0, 1));
+        let mut state = ledger(16).with_gas_limit(GAS_LIMIT);
+        assert!(matches!(
+            TestTransfers::apply_block(&mut state, &block),
+            Err(TransferError::OutOfGas(_))
+        ));
+        for threads in [1, 4] {
+            let mut state = ledger(16).with_gas_limit(GAS_LIMIT);
+            assert!(matches!(
+                ParallelExecutor::new(threads).apply_block::<TestTransfers>(&mut state, &block),
+                Err(TransferError::OutOfGas(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,324 @@
+//! Poseidon permutation over the BN254 scalar field.
+//!
+//! Parameters follow the reference instantiation used by circomlib: the `x^5` S-box, 8 full
+//! rounds and the recommended number of partial rounds for the state width, with the round
+//! constants and the MDS matrix sampled from the Grain LFSR of the Poseidon paper.
+//!
+//! The permutation is also available as an R1CS gadget, for hashing inside circuits.
+use ark_bn254::Fr;
+use ark_ff::{BigInteger, Field, PrimeField, Zero};
+use ark_r1cs_std::fields::{FieldVar, fp::FpVar};
+use ark_relations::r1cs::SynthesisError;
+use std::sync::OnceLock;
+
+/// Number of full rounds.
+pub const FULL_ROUNDS: usize = 8;
+/// Number of partial rounds indexed by state width minus two.
+const PARTIAL_ROUNDS: [usize; 4] = [56, 57, 56, 60];
+/// Largest supported state width.
+pub const MAX_WIDTH: usize = PARTIAL_ROUNDS.len() + 1;
+
+/// Round constants and MDS matrix of a state width.
+#[derive(Clone, Debug)]
+pub struct PoseidonParams {
+    pub width: usize,
+    pub partial_rounds: usize,
+    /// `width` constants per round
+    pub round_constants: Vec<Fr>,
+    pub mds: Vec<Vec<Fr>>,
+}
+
+/// Grain LFSR in self-shrinking mode, as specified for Poseidon parameter generation.
+struct Grain {
+    state: [bool; 80],
+}
+
+impl Grain {
+    fn new(width: usize, partial_rounds: usize) -> Self {
+        let mut bits = Vec::with_capacity(80);
+        let mut push = |value: usize, len: usize| {
+            bits.extend((0..len).rev().map(|i| value >> i & 1 == 1));
+        };
+        // Prime field, x^alpha S-box, field size, width, full and partial rounds
+        push(1, 2);
+        push(0, 4);
+        push(Fr::MODULUS_BIT_SIZE as usize, 12);
+        push(width, 12);
+        push(FULL_ROUNDS, 10);
+        push(partial_rounds, 10);
+        bits.resize(80, true);
+
+        let mut grain = Self {
+            state: bits.try_into().expect("80 bits"),
+        };
+        for _ in 0..160 {
+            grain.clock();
+        }
+        grain
+    }
+
+    fn clock(&mut self) -> bool {
+        let s = &self.state;
+        let bit = s[62] ^ s[51] ^ s[38] ^ s[23] ^ s[13] ^ s[0];
+        self.state.copy_within(1.., 0);
+        self.state[79] = bit;
+        bit
+    }
+
+    fn next_bit(&mut self) -> bool {
+        loop {
+            let keep = self.clock();
+            let bit = self.clock();
+            if keep {
+                return bit;
+            }
+        }
+    }
+
+    /// Returns the next field-size bits as a big-endian byte string.
+    fn next_bytes(&mut self) -> [u8; 32] {
+        let bits = Fr::MODULUS_BIT_SIZE as usize;
+        let mut bytes = [0u8; 32];
+        for i in (0..bits).rev() {
+            if self.next_bit() {
+                bytes[31 - i / 8] |= 1 << (i % 8);
+            }
+        }
+        bytes
+    }
+
+    /// Samples a field element by rejection.
+    fn next_element(&mut self) -> Fr {
+        loop {
+            let bytes = self.next_bytes();
+            let mut bigint = <Fr as PrimeField>::BigInt::default();
+            for (limb, chunk) in bigint.0.iter_mut().zip(bytes.rchunks(8)) {
+                *limb = u64::from_be_bytes(chunk.try_into().expect("8 bytes"));
+            }
+            if let Some(element) = Fr::from_bigint(bigint) {
+                return element;
+            }
+        }
+    }
+}
+
+impl PoseidonParams {
+    /// Generates the parameters of a state of `width` elements, a capacity of one and a rate
+    /// of `width - 1`.
+    pub fn generate(width: usize) -> Self {
+        assert!((2..=MAX_WIDTH).contains(&width), "Unsupported width");
+        let partial_rounds = PARTIAL_ROUNDS[width - 2];
+        let mut grain = Grain::new(width, partial_rounds);
+        let round_constants = (0..(FULL_ROUNDS + partial_rounds) * width)
+            .map(|_| grain.next_element())
+            .collect();
+
+        // Cauchy matrix 1 / (x_i + y_j) over distinct sampled points
+        let mds = loop {
+            let points: Vec<Fr> = (0..2 * width)
+                .map(|_| Fr::from_be_bytes_mod_order(&grain.next_bytes()))
+                .collect();
+            let (xs, ys) = points.split_at(width);
+            let distinct = points
+                .iter()
+                .enumerate()
+                .all(|(i, p)| !points[..i].contains(p));
+            let entries: Option<Vec<Vec<Fr>>> = xs
+                .iter()
+                .map(|x| ys.iter().map(|y| (*x + y).inverse()).collect())
+                .collect();
+            if let (true, Some(mds)) = (distinct, entries) {
+                break mds;
+            }
+        };
+
+        Self {
+            width,
+            partial_rounds,
+            round_constants,
+            mds,
+        }
+    }
+
+    /// Returns the cached parameters of `width`.
+    pub fn get(width: usize) -> &'static Self {
+        static PARAMS: [OnceLock<PoseidonParams>; MAX_WIDTH - 1] =
+            [const { OnceLock::new() }; MAX_WIDTH - 1];
+        assert!((2..=MAX_WIDTH).contains(&width), "Unsupported width");
+        PARAMS[width - 2].get_or_init(|| Self::generate(width))
+    }
+
+    /// Applies the permutation to `state`.
+    pub fn permute(&self, state: &mut [Fr]) {
+        assert_eq!(state.len(), self.width, "State width mismatch");
+        let rounds = FULL_ROUNDS + self.partial_rounds;
+        for (round, constants) in self.round_constants.chunks(self.width).enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element += constant;
+            }
+            let full = round < FULL_ROUNDS / 2 || round >= rounds - FULL_ROUNDS / 2;
+            if full {
+                state.iter_mut().for_each(sbox);
+            } else {
+                sbox(&mut state[0]);
+            }
+            let mixed: Vec<Fr> = self
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(state.iter()).map(|(m, s)| *m * s).sum())
+                .collect();
+            state.copy_from_slice(&mixed);
+        }
+    }
+
+    /// Applies the permutation to `state` in a constraint system.
+    pub fn permute_var(&self, state: &mut [FpVar<Fr>]) -> Result<(), SynthesisError> {
+        assert_eq!(state.len(), self.width, "State width mismatch");
+        let rounds = FULL_ROUNDS + self.partial_rounds;
+        for (round, constants) in self.round_constants.chunks(self.width).enumerate() {
+            for (element, constant) in state.iter_mut().zip(constants) {
+                *element += *constant;
+            }
+            let full = round < FULL_ROUNDS / 2 || round >= rounds - FULL_ROUNDS / 2;
+            if full {
+                for element in state.iter_mut() {
+                    sbox_var(element)?;
+                }
+            } else {
+                sbox_var(&mut state[0])?;
+            }
+            let mixed: Vec<FpVar<Fr>> = self
+                .mds
+                .iter()
+                .map(|row| row.iter().zip(state.iter()).map(|(m, s)| s * *m).sum())
+                .collect();
+            state.clone_from_slice(&mixed);
+        }
+        Ok(())
+    }
+}
+
+fn sbox(element: &mut Fr) {
+    let square = element.square();
+    *element *= square.square();
+}
+
+fn sbox_var(element: &mut FpVar<Fr>) -> Result<(), SynthesisError> {
+    let square = element.square()?;
+    *element *= square.square()?;
+    Ok(())
+}
+
+/// Hashes up to `MAX_WIDTH - 1` field elements, matching circomlib's `Poseidon(n)`.
+pub fn poseidon(inputs: &[Fr]) -> Fr {
+    let params = PoseidonParams::get(inputs.len() + 1);
+    let mut state = vec![Fr::zero(); params.width];
+    state[1..].copy_from_slice(inputs);
+    params.permute(&mut state);
+    state[0]
+}
+
+/// Constrains the [`poseidon`] hash of `inputs`.
+pub fn poseidon_var(inputs: &[FpVar<Fr>]) -> Result<FpVar<Fr>, SynthesisError> {
+    let params = PoseidonParams::get(inputs.len() + 1);
+    let mut state = vec![FpVar::zero(); params.width];
+    state[1..].clone_from_slice(inputs);
+    params.permute_var(&mut state)?;
+    Ok(state.swap_remove(0))
+}
+
+/// Hashes arbitrary bytes with a sponge of width 3.
+///
+/// The input is split into 31-byte big-endian chunks, which always fit in the field, and
+/// absorbed two at a time. The capacity is initialized with the input length, so inputs
+/// differing only by leading zero bytes in their last chunk do not collide.
+pub fn poseidon_bytes(input: &[u8]) -> Fr {
+    let params = PoseidonParams::get(3);
+    let mut state = [Fr::from(input.len() as u64), Fr::zero(), Fr::zero()];
+    let mut chunks = input.chunks(31).map(Fr::from_be_bytes_mod_order);
+    loop {
+        state[1] += chunks.next().unwrap_or_default();
+        state[2] += chunks.next().unwrap_or_default();
+        params.permute(&mut state);
+        if chunks.len() == 0 {
+            break;
+        }
+    }
+    state[1]
+}
+
+/// Big-endian encoding of a field element.
+pub fn to_bytes(element: &Fr) -> [u8; 32] {
+    element
+        .into_bigint()
+        .to_bytes_be()
+        .try_into()
+        .expect("32 bytes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_r1cs_std::{R1CSVar, alloc::AllocVar};
+    use ark_relations::r1cs::ConstraintSystem;
+    use std::str::FromStr;
+
+    fn hex_element(hex: &str) -> Fr {
+        Fr::from_be_bytes_mod_order(&hex::decode(hex).unwrap())
+    }
+
+    #[test]
+    fn test_parameters_match_reference() {
+        let params = PoseidonParams::get(3);
+        assert_eq!(params.round_constants.len(), 65 * 3);
+        assert_eq!(
+            params.round_constants[0],
+            hex_element("0ee9a592ba9a9518d05986d656f40c2114c4993c11bb29938d21d47304cd8e6e")
+        );
+        assert_eq!(
+            params.mds[0][0],
+            hex_element("109b7f411ba0e4c9b2b70caf5c36a7b194be7c11ad24378bfedb68592ba8118b")
+        );
+    }
+
+    #[test]
+    fn test_circomlib_vectors() {
+        assert_eq!(
+            poseidon(&[Fr::from(1u64), Fr::from(2u64)]),
+            hex_element("115cc0f5e7d690413df64c6b9662e9cf2a3617f2743245519e19607a4417189a")
+        );
+        assert_eq!(
+            poseidon(&[Fr::from(1u64)]),
+            Fr::from_str(
+                "18586133768512220936620570745912940619677854269274689475585506675881198879027"
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_gadget_matches_native() {
+        let cs = ConstraintSystem::<Fr>::new_ref();
+        for len in 1..MAX_WIDTH {
+            let inputs: Vec<Fr> = (0..len as u64).map(|i| Fr::from(i * 7 + 3)).collect();
+            let vars = inputs
+                .iter()
+                .map(|input| FpVar::new_witness(cs.clone(), || Ok(*input)))
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            let hash = poseidon_var(&vars).unwrap();
+            assert_eq!(hash.value().unwrap(), poseidon(&inputs));
+        }
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_bytes_hash_is_length_sensitive() {
+        assert_ne!(poseidon_bytes(b""), poseidon_bytes(&[0]));
+        assert_ne!(poseidon_bytes(&[1]), poseidon_bytes(&[0, 1]));
+        // Inputs spanning several absorptions
+        let long: Vec<u8> = (0..200).collect();
+        assert_ne!(poseidon_bytes(&long), poseidon_bytes(&long[..199]));
+        assert_eq!(poseidon_bytes(&long), poseidon_bytes(&long));
+    }
+}
@@ -0,0 +1,161 @@
+//! Transaction receipts reported by the state transition function.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::merkle::{self, MerkleProof};
+use crate::traits::HasherT;
+use alloy::primitives::B256;
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a transaction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReceiptStatus {
+    Failure,
+    Success,
+}
+
+/// Application-defined event emitted by a transaction.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Log {
+    // Indexed values identifying the event
+    pub topics: Vec<B256>,
+    // Event payload
+    pub data: Vec<u8>,
+}
+
+/// Result of applying a transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Receipt {
+    pub status: ReceiptStatus,
+    // Resources consumed by the transaction, in units defined by the application
+    pub resources_used: u64,
+    pub logs: Vec<Log>,
+}
+
+impl Receipt {
+    pub fn success(resources_used: u64) -> Self {
+        Self {
+            status: ReceiptStatus::Success,
+            resources_used,
+            logs: Vec::new(),
+        }
+    }
+
+    pub fn failure(resources_used: u64) -> Self {
+        Self {
+            status: ReceiptStatus::Failure,
+            resources_used,
+            logs: Vec::new(),
+        }
+    }
+
+    /// Appends `log` to the receipt.
+    pub fn with_log(mut self, log: Log) -> Self {
+        self.logs.push(log);
+        self
+    }
+
+    pub fn is_success(&self) -> bool {
+        self.status == ReceiptStatus::Success
+    }
+}
+
+/// Computes the Merkle root committing to `receipts`.
+pub fn receipts_root<H: HasherT>(receipts: &[Receipt]) -> H::Output {
+    let leaves: Vec<Vec<u8>> = receipts.iter().map(Encode::to_bytes).collect();
+    merkle::merkle_root::<H, _>(&leaves)
+}
+
+/// Builds an inclusion proof of the receipt at `index`.
+pub fn receipt_proof<H: HasherT>(
+    receipts: &[Receipt],
+    index: usize,
+) -> Option<MerkleProof<H::Output>> {
+    let leaves: Vec<Vec<u8>> = receipts.iter().map(Encode::to_bytes).collect();
+    merkle::merkle_proof::<H, _>(&leaves, index)
+}
+
+impl Encode for ReceiptStatus {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        (*self == ReceiptStatus::Success).encode_to(out);
+    }
+}
+
+impl Decode for ReceiptStatus {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(match bool::decode_from(input)? {
+            true => ReceiptStatus::Success,
+            false => ReceiptStatus::Failure,
+        })
+    }
+}
+
+impl Encode for Log {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.topics.encode_to(out);
+        self.data.encode_to(out);
+    }
+}
+
+impl Decode for Log {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            topics: Vec::decode_from(input)?,
+            data: Vec::decode_from(input)?,
+        })
+    }
+}
+
+impl Encode for Receipt {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.status.encode_to(out);
+        self.resources_used.encode_to(out);
+        self.logs.encode_to(out);
+    }
+}
+
+impl Decode for Receipt {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            status: ReceiptStatus::decode_from(input)?,
+            resources_used: u64::decode_from(input)?,
+            logs: Vec::decode_from(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+
+    fn receipts() -> Vec<Receipt> {
+        vec![
+            Receipt::success(21).with_log(Log {
+                topics: vec![B256::repeat_byte(1)],
+                data: b"transfer".to_vec(),
+            }),
+            Receipt::failure(3),
+        ]
+    }
+
+    #[test]
+    fn test_receipt_codec_round_trip() {
+        for receipt in receipts() {
+            assert_eq!(Receipt::from_bytes(&receipt.to_bytes()), Ok(receipt));
+        }
+    }
+
+    #[test]
+    fn test_receipts_root_and_proofs() {
+        let receipts = receipts();
+        let root = receipts_root::<KeccakHasher>(&receipts);
+        for (index, receipt) in receipts.iter().enumerate() {
+            let proof = receipt_proof::<KeccakHasher>(&receipts, index).unwrap();
+            assert!(proof.verify::<KeccakHasher>(&root, &receipt.to_bytes()));
+        }
+
+        // The root commits to the status of every receipt
+        let mut flipped = receipts.clone();
+        flipped[1].status = ReceiptStatus::Success;
+        assert_ne!(receipts_root::<KeccakHasher>(&flipped), root);
+    }
+}
@@ -0,0 +1,174 @@
+//! Block headers attested by the proposer key that produced them.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::traits::BlockHeaderT;
+use alloy::primitives::{Address, Signature, SignatureError};
+use alloy::signers::SignerSync;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SignedHeaderError {
+    /// Signing the header hash failed
+    #[error(transparent)]
+    Signer(#[from] alloy::signers::Error),
+    /// Signature does not recover to an address
+    #[error(transparent)]
+    InvalidSignature(#[from] SignatureError),
+    /// Header was signed by a key outside the allowed proposer set
+    #[error("Header signed by unknown proposer {}", _0)]
+    UnknownProposer(Address),
+    /// Signer differs from the proposer recorded in the header
+    #[error("Header proposer {} but signed by {}", proposer, signer)]
+    ProposerMismatch { proposer: Address, signer: Address },
+}
+
+/// Addresses allowed to propose blocks.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProposerSet(BTreeSet<Address>);
+
+impl ProposerSet {
+    pub fn new(proposers: impl IntoIterator<Item = Address>) -> Self {
+        Self(proposers.into_iter().collect())
+    }
+
+    pub fn insert(&mut self, proposer: Address) -> bool {
+        self.0.insert(proposer)
+    }
+
+    pub fn remove(&mut self, proposer: &Address) -> bool {
+        self.0.remove(proposer)
+    }
+
+    pub fn contains(&self, proposer: &Address) -> bool {
+        self.0.contains(proposer)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Address> {
+        self.0.iter()
+    }
+}
+
+impl FromIterator<Address> for ProposerSet {
+    fn from_iter<I: IntoIterator<Item = Address>>(iter: I) -> Self {
+        Self::new(iter)
+    }
+}
+
+/// Header with the proposer signature over its hash.
+///
+/// The hash is signed as an EIP-191 personal message, as transactions are.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SignedHeader<H> {
+    pub header: H,
+    pub signature: Signature,
+}
+
+impl<H: BlockHeaderT> SignedHeader<H> {
+    /// Signs the hash of `header` with `signer`.
+    pub fn sign(header: H, signer: &impl SignerSync) -> Result<Self, SignedHeaderError> {
+        let signature = signer.sign_message_sync(header.hash().as_ref())?;
+        Ok(Self { header, signature })
+    }
+
+    /// Recovers the address that signed the header.
+    pub fn signer(&self) -> Result<Address, SignedHeaderError> {
+        Ok(self
+            .signature
+            .recover_address_from_msg(self.header.hash().as_ref())?)
+    }
+
+    /// Verifies that the header was signed by an allowed proposer, which must also be the
+    /// proposer recorded in the header if any. Returns the signer.
+    pub fn verify(&self, proposers: &ProposerSet) -> Result<Address, SignedHeaderError> {
+        let signer = self.signer()?;
+        if let Some(proposer) = self.header.proposer()
+            && proposer != signer
+        {
+            return Err(SignedHeaderError::ProposerMismatch { proposer, signer });
+        }
+        if !proposers.contains(&signer) {
+            return Err(SignedHeaderError::UnknownProposer(signer));
+        }
+        Ok(signer)
+    }
+}
+
+impl<H: Encode> Encode for SignedHeader<H> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl<H: Decode> Decode for SignedHeader<H> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            header: H::decode_from(input)?,
+            signature: Signature::decode_from(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::HeaderExtension;
+    use crate::test_utils::{TestHeader, test_chain};
+    use alloy::signers::local::PrivateKeySigner;
+
+    fn header() -> TestHeader {
+        test_chain(2).remove(1).header
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let sequencer = PrivateKeySigner::random();
+        let signed = SignedHeader::sign(header(), &sequencer).unwrap();
+        assert_eq!(signed.signer().unwrap(), sequencer.address());
+
+        let proposers = ProposerSet::new([sequencer.address()]);
+        assert_eq!(signed.verify(&proposers).unwrap(), sequencer.address());
+
+        let decoded = SignedHeader::<TestHeader>::from_bytes(&signed.to_bytes()).unwrap();
+        assert_eq!(decoded.verify(&proposers).unwrap(), sequencer.address());
+    }
+
+    #[test]
+    fn test_rejects_unknown_proposer() {
+        let sequencer = PrivateKeySigner::random();
+        let intruder = PrivateKeySigner::random();
+        let proposers = ProposerSet::new([sequencer.address()]);
+
+        let signed = SignedHeader::sign(header(), &intruder).unwrap();
+        assert!(matches!(
+            signed.verify(&proposers),
+            Err(SignedHeaderError::UnknownProposer(address)) if address == intruder.address()
+        ));
+
+        // A signature over another header does not recover the proposer
+        let mut tampered = SignedHeader::sign(header(), &sequencer).unwrap();
+        tampered.header.number += 1;
+        assert!(tampered.verify(&proposers).is_err());
+    }
+
+    #[test]
+    fn test_signer_must_match_header_proposer() {
+        let sequencer = PrivateKeySigner::random();
+        let other = PrivateKeySigner::random();
+        let proposers = ProposerSet::new([sequencer.address(), other.address()]);
+        let header = header()
+            .with_extension(HeaderExtension {
+                proposer: Some(sequencer.address()),
+                ..Default::default()
+            })
+            .unwrap();
+
+        let signed = SignedHeader::sign(header.clone(), &sequencer).unwrap();
+        assert!(signed.verify(&proposers).is_ok());
+        let signed = SignedHeader::sign(header, &other).unwrap();
+        assert!(matches!(
+            signed.verify(&proposers),
+            Err(SignedHeaderError::ProposerMismatch { .. })
+        ));
+    }
+}
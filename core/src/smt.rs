@@ -0,0 +1,720 @@
+//! Sparse Merkle tree over 256-bit keys and any [`HasherT`].
+//!
+//! The tree is kept in compact form: a subtree holding a single leaf is represented by that
+//! leaf, and an empty subtree hashes to the all-zero digest. Key bits are read from the most
+//! significant bit of the first byte, a set bit descending to the right.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::traits::HasherT;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+const LEAF_PREFIX: u8 = 0x00;
+const NODE_PREFIX: u8 = 0x01;
+/// Number of bits in a key.
+pub const KEY_BITS: usize = 256;
+
+pub type Key = [u8; 32];
+
+fn bit(key: &Key, index: usize) -> bool {
+    key[index / 8] >> (7 - index % 8) & 1 == 1
+}
+
+fn leaf_hash<H: HasherT>(key: &Key, value_hash: &H::Output) -> H::Output {
+    let mut buf = Vec::with_capacity(1 + 32 + value_hash.as_ref().len());
+    buf.push(LEAF_PREFIX);
+    buf.extend_from_slice(key);
+    buf.extend_from_slice(value_hash.as_ref());
+    H::hash(&buf)
+}
+
+fn node_hash<H: HasherT>(left: &H::Output, right: &H::Output) -> H::Output {
+    let mut buf = Vec::with_capacity(1 + left.as_ref().len() + right.as_ref().len());
+    buf.push(NODE_PREFIX);
+    buf.extend_from_slice(left.as_ref());
+    buf.extend_from_slice(right.as_ref());
+    H::hash(&buf)
+}
+
+#[derive(Clone, Debug)]
+enum Node<Hash> {
+    Empty,
+    Leaf {
+        key: Key,
+        value_hash: Hash,
+        hash: Hash,
+    },
+    Internal {
+        left: Box<Node<Hash>>,
+        right: Box<Node<Hash>>,
+        hash: Hash,
+    },
+}
+
+impl<Hash: Copy + Default> Node<Hash> {
+    fn hash(&self) -> Hash {
+        match self {
+            Node::Empty => Hash::default(),
+            Node::Leaf { hash, .. } | Node::Internal { hash, .. } => *hash,
+        }
+    }
+}
+
+/// Sparse Merkle tree storing values under 256-bit keys.
+#[derive(Clone, Debug)]
+pub struct SparseMerkleTree<H: HasherT> {
+    root: Node<H::Output>,
+    values: BTreeMap<Key, Vec<u8>>,
+}
+
+impl<H: HasherT> Default for SparseMerkleTree<H> {
+    fn default() -> Self {
+        Self {
+            root: Node::Empty,
+            values: BTreeMap::new(),
+        }
+    }
+}
+
+impl<H: HasherT> SparseMerkleTree<H> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the root of the tree. The empty tree has the all-zero root.
+    pub fn root(&self) -> H::Output {
+        self.root.hash()
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    pub fn get(&self, key: &Key) -> Option<&[u8]> {
+        self.values.get(key).map(Vec::as_slice)
+    }
+
+    /// Iterates over the entries in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (&Key, &Vec<u8>)> {
+        self.values.iter()
+    }
+
+    /// Sets the value of `key`, returning the previous value.
+    pub fn insert(&mut self, key: Key, value: Vec<u8>) -> Option<Vec<u8>> {
+        let value_hash = H::hash(&value);
+        let root = std::mem::replace(&mut self.root, Node::Empty);
+        self.root = Self::insert_at(root, 0, key, value_hash);
+        self.values.insert(key, value)
+    }
+
+    /// Removes `key`, returning its value.
+    pub fn delete(&mut self, key: &Key) -> Option<Vec<u8>> {
+        let value = self.values.remove(key)?;
+        let root = std::mem::replace(&mut self.root, Node::Empty);
+        self.root = Self::delete_at(root, 0, key);
+        Some(value)
+    }
+
+    /// Applies a batch of updates in order, `None` deleting the key. Returns the new root.
+    pub fn update_batch(
+        &mut self,
+        updates: impl IntoIterator<Item = (Key, Option<Vec<u8>>)>,
+    ) -> H::Output {
+        for (key, value) in updates {
+            match value {
+                Some(value) => {
+                    self.insert(key, value);
+                }
+                None => {
+                    self.delete(&key);
+                }
+            }
+        }
+        self.root()
+    }
+
+    /// Builds a proof of the value of `key`, or of its absence.
+    pub fn prove(&self, key: &Key) -> SparseMerkleProof<H::Output> {
+        let mut siblings = Vec::new();
+        let mut node = &self.root;
+        let mut depth = 0;
+        loop {
+            match node {
+                Node::Empty => {
+                    return SparseMerkleProof {
+                        siblings,
+                        leaf: None,
+                    };
+                }
+                Node::Leaf {
+                    key, value_hash, ..
+                } => {
+                    return SparseMerkleProof {
+                        siblings,
+                        leaf: Some((*key, *value_hash)),
+                    };
+                }
+                Node::Internal { left, right, .. } => {
+                    let (next, sibling) = if bit(key, depth) {
+                        (right, left)
+                    } else {
+                        (left, right)
+                    };
+                    siblings.push(sibling.hash());
+                    node = next;
+                    depth += 1;
+                }
+            }
+        }
+    }
+
+    /// Builds the witness of the paths of `keys`, from which their values can be checked and
+    /// updated without the rest of the tree.
+    pub fn witness(&self, keys: &[Key]) -> SparseMerkleWitness<H::Output> {
+        SparseMerkleWitness {
+            root: Self::witness_at(&self.root, 0, keys),
+        }
+    }
+
+    fn witness_at(node: &Node<H::Output>, depth: usize, keys: &[Key]) -> WitnessNode<H::Output> {
+        match node {
+            Node::Empty => WitnessNode::Empty,
+            Node::Leaf {
+                key, value_hash, ..
+            } => WitnessNode::Leaf {
+                key: *key,
+                value_hash: *value_hash,
+            },
+            Node::Internal { left, right, .. } if keys.is_empty() => WitnessNode::Pruned {
+                left: left.hash(),
+                right: right.hash(),
+            },
+            Node::Internal { left, right, .. } => {
+                let (right_keys, left_keys): (Vec<Key>, Vec<Key>) =
+                    keys.iter().partition(|key| bit(key, depth));
+                WitnessNode::Internal(
+                    Box::new(Self::witness_at(left, depth + 1, &left_keys)),
+                    Box::new(Self::witness_at(right, depth + 1, &right_keys)),
+                )
+            }
+        }
+    }
+
+    fn leaf(key: Key, value_hash: H::Output) -> Node<H::Output> {
+        Node::Leaf {
+            key,
+            value_hash,
+            hash: leaf_hash::<H>(&key, &value_hash),
+        }
+    }
+
+    fn internal(left: Node<H::Output>, right: Node<H::Output>) -> Node<H::Output> {
+        // A subtree with a single leaf collapses into the leaf
+        match (&left, &right) {
+            (Node::Empty, Node::Empty) => return Node::Empty,
+            (Node::Empty, Node::Leaf { .. }) => return right,
+            (Node::Leaf { .. }, Node::Empty) => return left,
+            _ => {}
+        }
+        let hash = node_hash::<H>(&left.hash(), &right.hash());
+        Node::Internal {
+            left: Box::new(left),
+            right: Box::new(right),
+            hash,
+        }
+    }
+
+    fn insert_at(
+        node: Node<H::Output>,
+        depth: usize,
+        key: Key,
+        value_hash: H::Output,
+    ) -> Node<H::Output> {
+        match node {
+            Node::Empty => Self::leaf(key, value_hash),
+            Node::Leaf { key: existing, .. } if existing == key => Self::leaf(key, value_hash),
+            Node::Leaf { .. } => Self::split(node, Self::leaf(key, value_hash), depth),
+            Node::Internal { left, right, .. } => {
+                if bit(&key, depth) {
+                    Self::internal(*left, Self::insert_at(*right, depth + 1, key, value_hash))
+                } else {
+                    Self::internal(Self::insert_at(*left, depth + 1, key, value_hash), *right)
+                }
+            }
+        }
+    }
+
+    /// Builds the subtree at `depth` holding the two leaves `a` and `b`.
+    fn split(a: Node<H::Output>, b: Node<H::Output>, depth: usize) -> Node<H::Output> {
+        let (Node::Leaf { key: key_a, .. }, Node::Leaf { key: key_b, .. }) = (&a, &b) else {
+            unreachable!("split is only called with leaves");
+        };
+        match (bit(key_a, depth), bit(key_b, depth)) {
+            (false, true) => Self::internal(a, b),
+            (true, false) => Self::internal(b, a),
+            (false, false) => Self::internal(Self::split(a, b, depth + 1), Node::Empty),
+            (true, true) => Self::internal(Node::Empty, Self::split(a, b, depth + 1)),
+        }
+    }
+
+    fn delete_at(node: Node<H::Output>, depth: usize, key: &Key) -> Node<H::Output> {
+        match node {
+            Node::Leaf { key: existing, .. } if existing == *key => Node::Empty,
+            Node::Internal { left, right, .. } => {
+                if bit(key, depth) {
+                    Self::internal(*left, Self::delete_at(*right, depth + 1, key))
+                } else {
+                    Self::internal(Self::delete_at(*left, depth + 1, key), *right)
+                }
+            }
+            node => node,
+        }
+    }
+}
+
+/// Proof of the value stored under a key, or of its absence.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleProof<Hash> {
+    /// Sibling hashes along the key path, from the root downwards
+    pub siblings: Vec<Hash>,
+    /// Leaf found at the end of the path as `(key, value hash)`, `None` if the path ends in an
+    /// empty subtree
+    pub leaf: Option<(Key, Hash)>,
+}
+
+impl<Hash: AsRef<[u8]> + Copy + Default + Eq> SparseMerkleProof<Hash> {
+    fn compute_root<H: HasherT<Output = Hash>>(&self, key: &Key) -> Option<Hash> {
+        if self.siblings.len() > KEY_BITS {
+            return None;
+        }
+        let mut node = match &self.leaf {
+            Some((leaf_key, value_hash)) => leaf_hash::<H>(leaf_key, value_hash),
+            None => Hash::default(),
+        };
+        for (depth, sibling) in self.siblings.iter().enumerate().rev() {
+            node = if bit(key, depth) {
+                node_hash::<H>(sibling, &node)
+            } else {
+                node_hash::<H>(&node, sibling)
+            };
+        }
+        Some(node)
+    }
+
+    /// Verifies that `key` holds `value` under `root`.
+    pub fn verify_membership<H: HasherT<Output = Hash>>(
+        &self,
+        root: &Hash,
+        key: &Key,
+        value: &[u8],
+    ) -> bool {
+        self.leaf == Some((*key, H::hash(value)))
+            && self.compute_root::<H>(key).as_ref() == Some(root)
+    }
+
+    /// Verifies that `key` is absent under `root`.
+    pub fn verify_non_membership<H: HasherT<Output = Hash>>(&self, root: &Hash, key: &Key) -> bool {
+        if let Some((leaf_key, _)) = &self.leaf {
+            // The other leaf must sit on the path of `key`
+            if leaf_key == key || (0..self.siblings.len()).any(|i| bit(leaf_key, i) != bit(key, i))
+            {
+                return false;
+            }
+        }
+        self.compute_root::<H>(key).as_ref() == Some(root)
+    }
+}
+
+/// Derives the tree key of `data`, such as an account address.
+pub fn hashed_key<H: HasherT>(data: &[u8]) -> Key
+where
+    H::Output: Into<Key>,
+{
+    H::hash(data).into()
+}
+
+/// Value of a key in the state committed to by a block, with its proof.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StateProof<Hash> {
+    pub block_number: u64,
+    pub key: Key,
+    /// Value of the key, `None` if it is absent
+    pub value: Option<Vec<u8>>,
+    pub proof: SparseMerkleProof<Hash>,
+}
+
+impl<Hash: AsRef<[u8]> + Copy + Default + Eq> StateProof<Hash> {
+    /// Verifies the value of the key against the state root of block `block_number`.
+    pub fn verify<H: HasherT<Output = Hash>>(&self, state_root: &Hash) -> bool {
+        match &self.value {
+            Some(value) => self
+                .proof
+                .verify_membership::<H>(state_root, &self.key, value),
+            None => self.proof.verify_non_membership::<H>(state_root, &self.key),
+        }
+    }
+}
+
+/// Key outside the paths covered by a [`SparseMerkleWitness`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Key 0x{} is not covered by the witness", hex::encode(.0))]
+pub struct UnwitnessedKey(pub Key);
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+enum WitnessNode<Hash> {
+    Empty,
+    Leaf { key: Key, value_hash: Hash },
+    Internal(Box<WitnessNode<Hash>>, Box<WitnessNode<Hash>>),
+    // Internal node off the witnessed paths. It is given by the hashes of its children, so
+    // that it cannot stand for a single leaf, which would collapse when its sibling is deleted
+    Pruned { left: Hash, right: Hash },
+}
+
+/// Part of a sparse Merkle tree covering the paths of a set of keys, the subtrees off those
+/// paths being replaced by their hashes. The values of the covered keys can be read and
+/// updated, and the root recomputed, as in the full tree. A witness whose root matches a
+/// trusted root is that tree, since every node is bound by its hash.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SparseMerkleWitness<Hash> {
+    root: WitnessNode<Hash>,
+}
+
+impl<Hash: AsRef<[u8]> + Copy + Default + Eq> SparseMerkleWitness<Hash> {
+    /// Computes the root of the tree.
+    pub fn root<H: HasherT<Output = Hash>>(&self) -> Hash {
+        Self::hash_at::<H>(&self.root)
+    }
+
+    fn hash_at<H: HasherT<Output = Hash>>(node: &WitnessNode<Hash>) -> Hash {
+        match node {
+            WitnessNode::Empty => Hash::default(),
+            WitnessNode::Leaf { key, value_hash } => leaf_hash::<H>(key, value_hash),
+            WitnessNode::Internal(left, right) => {
+                node_hash::<H>(&Self::hash_at::<H>(left), &Self::hash_at::<H>(right))
+            }
+            WitnessNode::Pruned { left, right } => node_hash::<H>(left, right),
+        }
+    }
+
+    /// Returns the hash of the value of `key`, `None` if it is absent.
+    pub fn value_hash(&self, key: &Key) -> Result<Option<Hash>, UnwitnessedKey> {
+        let mut node = &self.root;
+        let mut depth = 0;
+        loop {
+            match node {
+                WitnessNode::Empty => return Ok(None),
+                WitnessNode::Leaf {
+                    key: leaf_key,
+                    value_hash,
+                } => return Ok((leaf_key == key).then_some(*value_hash)),
+                WitnessNode::Internal(left, right) => {
+                    node = if bit(key, depth) { right } else { left };
+                    depth += 1;
+                }
+                WitnessNode::Pruned { .. } => return Err(UnwitnessedKey(*key)),
+            }
+        }
+    }
+
+    /// Sets the hash of the value of `key`, or removes it if `value_hash` is `None`.
+    pub fn update(&mut self, key: Key, value_hash: Option<Hash>) -> Result<(), UnwitnessedKey> {
+        // The update follows the path of the lookup, which must not cross a pruned node
+        self.value_hash(&key)?;
+        let root = std::mem::replace(&mut self.root, WitnessNode::Empty);
+        self.root = Self::update_at(root, 0, key, value_hash);
+        Ok(())
+    }
+
+    fn update_at(
+        node: WitnessNode<Hash>,
+        depth: usize,
+        key: Key,
+        value_hash: Option<Hash>,
+    ) -> WitnessNode<Hash> {
+        match (node, value_hash) {
+            (WitnessNode::Internal(left, right), _) => {
+                if bit(&key, depth) {
+                    Self::internal(*left, Self::update_at(*right, depth + 1, key, value_hash))
+                } else {
+                    Self::internal(Self::update_at(*left, depth + 1, key, value_hash), *right)
+                }
+            }
+            (WitnessNode::Leaf { key: existing, .. }, None) if existing == key => {
+                WitnessNode::Empty
+            }
+            (WitnessNode::Empty, Some(value_hash)) => WitnessNode::Leaf { key, value_hash },
+            (WitnessNode::Leaf { key: existing, .. }, Some(value_hash)) if existing == key => {
+                WitnessNode::Leaf { key, value_hash }
+            }
+            (leaf @ WitnessNode::Leaf { .. }, Some(value_hash)) => {
+                Self::split(leaf, WitnessNode::Leaf { key, value_hash }, depth)
+            }
+            // Deleting an absent key, pruned nodes are ruled out by the caller
+            (node, _) => node,
+        }
+    }
+
+    // Same collapsing rule as the full tree, pruned nodes holding at least two leaves
+    fn internal(left: WitnessNode<Hash>, right: WitnessNode<Hash>) -> WitnessNode<Hash> {
+        match (&left, &right) {
+            (WitnessNode::Empty, WitnessNode::Empty) => WitnessNode::Empty,
+            (WitnessNode::Empty, WitnessNode::Leaf { .. }) => right,
+            (WitnessNode::Leaf { .. }, WitnessNode::Empty) => left,
+            _ => WitnessNode::Internal(Box::new(left), Box::new(right)),
+        }
+    }
+
+    fn split(a: WitnessNode<Hash>, b: WitnessNode<Hash>, depth: usize) -> WitnessNode<Hash> {
+        let (WitnessNode::Leaf { key: key_a, .. }, WitnessNode::Leaf { key: key_b, .. }) = (&a, &b)
+        else {
+            unreachable!("split is only called with leaves");
+        };
+        match (bit(key_a, depth), bit(key_b, depth)) {
+            (false, true) => Self::internal(a, b),
+            (true, false) => Self::internal(b, a),
+            (false, false) => Self::internal(Self::split(a, b, depth + 1), WitnessNode::Empty),
+            (true, true) => Self::internal(WitnessNode::Empty, Self::split(a, b, depth + 1)),
+        }
+    }
+}
+
+impl<Hash: Encode> Encode for SparseMerkleProof<Hash> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.siblings.encode_to(out);
+        self.leaf.encode_to(out);
+    }
+}
+
+impl<Hash: Decode> Decode for SparseMerkleProof<Hash> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            siblings: Vec::decode_from(input)?,
+            leaf: Option::decode_from(input)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::KeccakHasher;
+
+    type Tree = SparseMerkleTree<KeccakHasher>;
+
+    fn key(i: u32) -> Key {
+        KeccakHasher::hash(&i.to_le_bytes())
+    }
+
+    /// Root computed from scratch following the compact tree definition.
+    fn reference_root(entries: &[(Key, Vec<u8>)], depth: usize) -> [u8; 32] {
+        match entries {
+            [] => [0; 32],
+            [(key, value)] => leaf_hash::<KeccakHasher>(key, &KeccakHasher::hash(value)),
+            _ => {
+                let (right, left): (Vec<_>, Vec<_>) =
+                    entries.iter().cloned().partition(|(k, _)| bit(k, depth));
+                node_hash::<KeccakHasher>(
+                    &reference_root(&left, depth + 1),
+                    &reference_root(&right, depth + 1),
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn test_insert_get_delete() {
+        let mut tree = Tree::new();
+        assert_eq!(tree.root(), [0; 32]);
+
+        assert_eq!(tree.insert(key(1), b"one".to_vec()), None);
+        assert_eq!(tree.insert(key(2), b"two".to_vec()), None);
+        assert_eq!(tree.get(&key(1)), Some(&b"one"[..]));
+        assert_eq!(tree.insert(key(1), b"uno".to_vec()), Some(b"one".to_vec()));
+        assert_eq!(tree.len(), 2);
+
+        assert_eq!(tree.delete(&key(1)), Some(b"uno".to_vec()));
+        assert_eq!(tree.delete(&key(1)), None);
+        assert_eq!(tree.get(&key(1)), None);
+        assert_eq!(
+            tree.root(),
+            leaf_hash::<KeccakHasher>(&key(2), &KeccakHasher::hash(b"two"))
+        );
+        tree.delete(&key(2));
+        assert_eq!(tree.root(), [0; 32]);
+    }
+
+    #[test]
+    fn test_root_matches_reference_and_is_order_independent() {
+        let entries: Vec<_> = (0..50).map(|i| (key(i), vec![i as u8; 3])).collect();
+        let mut tree = Tree::new();
+        for (k, v) in entries.iter().rev() {
+            tree.insert(*k, v.clone());
+        }
+        assert_eq!(tree.root(), reference_root(&entries, 0));
+
+        // Deleting half the entries yields the root of the remaining half
+        let mut other = Tree::new();
+        let root = other.update_batch(
+            entries
+                .iter()
+                .map(|(k, v)| (*k, Some(v.clone())))
+                .chain(entries.iter().step_by(2).map(|(k, _)| (*k, None))),
+        );
+        let remaining: Vec<_> = entries.iter().skip(1).step_by(2).cloned().collect();
+        assert_eq!(root, reference_root(&remaining, 0));
+    }
+
+    #[test]
+    fn test_keys_sharing_long_prefix() {
+        let mut a = [0u8; 32];
+        let mut b = [0u8; 32];
+        a[31] = 0b10;
+        b[31] = 0b11;
+        let mut tree = Tree::new();
+        tree.insert(a, b"a".to_vec());
+        tree.insert(b, b"b".to_vec());
+        let entries = vec![(a, b"a".to_vec()), (b, b"b".to_vec())];
+        assert_eq!(tree.root(), reference_root(&entries, 0));
+
+        let proof = tree.prove(&b);
+        assert_eq!(proof.siblings.len(), 256);
+        assert!(proof.verify_membership::<KeccakHasher>(&tree.root(), &b, b"b"));
+    }
+
+    #[test]
+    fn test_membership_proofs() {
+        let mut tree = Tree::new();
+        for i in 0..20 {
+            tree.insert(key(i), vec![i as u8]);
+        }
+        let root = tree.root();
+        for i in 0..20 {
+            let proof = tree.prove(&key(i));
+            assert!(proof.verify_membership::<KeccakHasher>(&root, &key(i), &[i as u8]));
+            assert!(!proof.verify_membership::<KeccakHasher>(&root, &key(i), &[0xff]));
+            assert!(!proof.verify_non_membership::<KeccakHasher>(&root, &key(i)));
+        }
+        let decoded = SparseMerkleProof::from_bytes(&tree.prove(&key(3)).to_bytes()).unwrap();
+        assert!(decoded.verify_membership::<KeccakHasher>(&root, &key(3), &[3]));
+    }
+
+    #[test]
+    fn test_state_proof() {
+        let mut tree = Tree::new();
+        let key = hashed_key::<KeccakHasher>(b"alice");
+        tree.insert(key, b"balance".to_vec());
+        let root = tree.root();
+
+        let present = StateProof {
+            block_number: 1,
+            key,
+            value: tree.get(&key).map(<[u8]>::to_vec),
+            proof: tree.prove(&key),
+        };
+        assert!(present.verify::<KeccakHasher>(&root));
+        let mut forged = present.clone();
+        forged.value = None;
+        assert!(!forged.verify::<KeccakHasher>(&root));
+
+        let absent_key = hashed_key::<KeccakHasher>(b"bob");
+        let absent = StateProof {
+            block_number: 1,
+            key: absent_key,
+            value: None,
+            proof: tree.prove(&absent_key),
+        };
+        assert!(absent.verify::<KeccakHasher>(&root));
+    }
+
+    #[test]
+    fn test_non_membership_proofs() {
+        let mut tree = Tree::new();
+        let empty_proof = tree.prove(&key(0));
+        assert!(empty_proof.verify_non_membership::<KeccakHasher>(&tree.root(), &key(0)));
+
+        for i in 0..20 {
+            tree.insert(key(i), vec![i as u8]);
+        }
+        let root = tree.root();
+        for i in 20..60 {
+            let proof = tree.prove(&key(i));
+            assert!(proof.verify_non_membership::<KeccakHasher>(&root, &key(i)));
+            assert!(!proof.verify_membership::<KeccakHasher>(&root, &key(i), &[i as u8]));
+        }
+
+        // A membership proof of another key cannot be reused as a non-membership proof
+        // unless that key sits on the path of the absent key
+        let proof = tree.prove(&key(0));
+        let absent = (20..)
+            .map(key)
+            .find(|k| bit(k, 0) != bit(&key(0), 0))
+            .unwrap();
+        assert!(!proof.verify_non_membership::<KeccakHasher>(&root, &absent));
+    }
+
+    #[test]
+    fn test_witness_updates_match_tree() {
+        let mut tree = Tree::new();
+        for i in 0..40 {
+            tree.insert(key(i), vec![i as u8]);
+        }
+        // Keys 50 and 51 are absent
+        let keys = [1, 2, 3, 50, 51].map(key);
+        let mut witness = tree.witness(&keys);
+        assert_eq!(witness.root::<KeccakHasher>(), tree.root());
+        assert_eq!(
+            witness.value_hash(&key(1)),
+            Ok(Some(KeccakHasher::hash(&[1])))
+        );
+        assert_eq!(witness.value_hash(&key(50)), Ok(None));
+
+        let updates = [
+            (key(1), Some(b"uno".to_vec())),
+            (key(2), None),
+            (key(50), Some(b"fifty".to_vec())),
+            (key(3), None),
+            (key(51), None),
+        ];
+        for (k, value) in updates {
+            witness
+                .update(k, value.as_deref().map(KeccakHasher::hash))
+                .unwrap();
+            match value {
+                Some(value) => tree.insert(k, value),
+                None => tree.delete(&k),
+            };
+            assert_eq!(witness.root::<KeccakHasher>(), tree.root());
+        }
+    }
+
+    #[test]
+    fn test_witness_collapses_and_rejects_other_keys() {
+        let mut tree = Tree::new();
+        for i in 0..3 {
+            tree.insert(key(i), vec![i as u8]);
+        }
+        let mut witness = tree.witness(&[key(0), key(1)]);
+        for i in 0..2 {
+            witness.update(key(i), None).unwrap();
+            tree.delete(&key(i));
+        }
+        // The remaining leaf moves up to the root
+        assert_eq!(witness.root::<KeccakHasher>(), tree.root());
+
+        let mut tree = Tree::new();
+        for i in 0..40 {
+            tree.insert(key(i), vec![i as u8]);
+        }
+        let mut witness = tree.witness(&[key(0)]);
+        let other = (1..40)
+            .map(key)
+            .find(|k| bit(k, 0) != bit(&key(0), 0))
+            .unwrap();
+        assert_eq!(witness.value_hash(&other), Err(UnwitnessedKey(other)));
+        assert_eq!(witness.update(other, None), Err(UnwitnessedKey(other)));
+        assert_eq!(witness.root::<KeccakHasher>(), tree.root());
+    }
+}
@@ -0,0 +1,482 @@
+//! Snapshots of the application state at a given block.
+//!
+//! A snapshot file holds the header of the block, a manifest of checksummed chunks together
+//! with the peaks of the accumulator of the hashes of the blocks before it, and the chunks
+//! with the key-value entries of the state:
+//!
+//! `MAGIC || len (u32) || manifest || checksum (32 bytes) || (len (u32) || chunk)*`
+//!
+//! Every chunk is checked against its hash in the manifest while reading, the restored
+//! state is verified against the state root of the header, and the peaks against its history
+//! root. The manifest also proves the hash of the genesis block against the history root, so
+//! that the caller can check with [`SnapshotManifest::verify_genesis`] that the header
+//! belongs to its chain.
+use crate::codec::{CodecError, Decode, Encode};
+use crate::hasher::KeccakHasher;
+use crate::mmr::{MerkleMountainRange, MmrProof};
+use crate::traits::{AppState, BlockHeaderT, HasherT, SnapshotState};
+use std::fs::File;
+use std::io::{BufReader, BufWriter, ErrorKind, Read, Write};
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"ANSNAP\x00\x03";
+/// Default maximum size of the encoded entries of a chunk.
+pub const DEFAULT_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Key-value entry of a state.
+pub type StateEntry = (Vec<u8>, Vec<u8>);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnapshotError {
+    /// An Io error occurred.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// Snapshot content could not be decoded
+    #[error(transparent)]
+    Codec(#[from] CodecError),
+    /// File does not start with the snapshot magic bytes
+    #[error("Not a snapshot file")]
+    InvalidFormat,
+    /// Manifest does not match its checksum
+    #[error("Corrupted snapshot manifest")]
+    CorruptedManifest,
+    /// Chunk does not match its hash in the manifest
+    #[error("Corrupted snapshot chunk {}", _0)]
+    CorruptedChunk(usize),
+    /// Number of restored entries differs from the manifest
+    #[error("Expected {} entries, found {}", expected, found)]
+    EntryCountMismatch { expected: u64, found: u64 },
+    /// State does not hash to the state root of the header
+    #[error("State does not match the state root of block #{}", _0)]
+    StateRootMismatch(u64),
+    /// Accumulator peaks do not match the history root of the header
+    #[error("History does not match the history root of block #{}", _0)]
+    HistoryMismatch(u64),
+    /// Genesis block cannot be proven against the history root of the header
+    #[error("Block #{} does not descend from the genesis block", _0)]
+    GenesisMismatch(u64),
+    /// Entries do not form a valid state
+    #[error("Invalid state entry: {}", _0)]
+    InvalidEntry(String),
+}
+
+/// Description of the content of a snapshot.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotManifest<H: BlockHeaderT> {
+    /// Header of the block the state was taken at
+    pub header: H,
+    /// Peaks of the accumulator of the hashes of the blocks before the header, so that the
+    /// chain can be extended without them. Empty if the header has no history root.
+    pub history_peaks: Vec<H::Hash>,
+    /// Proof of the hash of the genesis block, the first leaf of the accumulator, against the
+    /// history root of the header. `None` if the header has no history root or is the genesis
+    /// block itself.
+    pub genesis_proof: Option<MmrProof<H::Hash>>,
+    pub entry_count: u64,
+    /// Hash of every encoded chunk, in order
+    pub chunk_hashes: Vec<[u8; 32]>,
+}
+
+impl<H: BlockHeaderT> Encode for SnapshotManifest<H> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.header.encode_to(out);
+        self.history_peaks.encode_to(out);
+        self.genesis_proof.encode_to(out);
+        self.entry_count.encode_to(out);
+        self.chunk_hashes.encode_to(out);
+    }
+}
+
+impl<H: BlockHeaderT> Decode for SnapshotManifest<H> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            header: H::decode_from(input)?,
+            history_peaks: Vec::decode_from(input)?,
+            genesis_proof: Option::decode_from(input)?,
+            entry_count: u64::decode_from(input)?,
+            chunk_hashes: Vec::decode_from(input)?,
+        })
+    }
+}
+
+impl<H: BlockHeaderT> SnapshotManifest<H> {
+    /// Checks that the header is the genesis block hashing to `genesis`, or descends from it
+    /// according to its history root.
+    pub fn verify_genesis(&self, genesis: &H::Hash) -> Result<(), SnapshotError> {
+        let number = (*self.header.number()).into();
+        let descends = match (&self.genesis_proof, self.header.history_root()) {
+            _ if number == 0 => self.header.hash() == *genesis,
+            (Some(proof), Some(root)) => {
+                proof.leaf_index == 0
+                    && proof.leaf_count == number
+                    && proof
+                        .compute_root::<H::Hashing>(genesis)
+                        .is_some_and(|computed| computed.as_ref() == root.as_slice())
+            }
+            _ => false,
+        };
+        if !descends {
+            return Err(SnapshotError::GenesisMismatch(number));
+        }
+        Ok(())
+    }
+}
+
+fn verify_state_root<H: BlockHeaderT>(
+    header: &H,
+    state: &impl AppState,
+) -> Result<(), SnapshotError> {
+    if state.state_root().as_slice() != header.state_root().as_ref() {
+        return Err(SnapshotError::StateRootMismatch((*header.number()).into()));
+    }
+    Ok(())
+}
+
+/// Checks that the range of `history_peaks` has the history root of `header`.
+fn verify_history<H: BlockHeaderT>(
+    header: &H,
+    history_peaks: &[H::Hash],
+) -> Result<(), SnapshotError> {
+    let number = (*header.number()).into();
+    let Some(expected) = header.history_root() else {
+        return Ok(());
+    };
+    let root = MerkleMountainRange::<H::Hashing>::from_peaks(number, history_peaks)
+        .map(|history| history.root());
+    if root.is_none_or(|root| root.as_ref() != expected.as_slice()) {
+        return Err(SnapshotError::HistoryMismatch(number));
+    }
+    Ok(())
+}
+
+/// Splits `entries` into encoded chunks of about `chunk_size` bytes each.
+fn chunks(entries: Vec<StateEntry>, chunk_size: usize) -> Vec<Vec<u8>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::new();
+    let mut size = 0;
+    for entry in entries {
+        size += entry.0.len() + entry.1.len();
+        chunk.push(entry);
+        if size >= chunk_size {
+            chunks.push(std::mem::take(&mut chunk).to_bytes());
+            size = 0;
+        }
+    }
+    if !chunk.is_empty() {
+        chunks.push(chunk.to_bytes());
+    }
+    chunks
+}
+
+/// Writes the snapshot of `state`, the state committed to by `header`, to `path`. `history`
+/// accumulates the hashes of the blocks before `header` at least. If it was resumed from
+/// peaks, `genesis_proof` proves the genesis block against it at an earlier size. The file
+/// is written next to `path` and moved in place once complete.
+pub fn write_snapshot<H: BlockHeaderT, S: SnapshotState>(
+    path: impl AsRef<Path>,
+    header: &H,
+    state: &S,
+    history: &MerkleMountainRange<H::Hashing>,
+    genesis_proof: Option<&MmrProof<H::Hash>>,
+    chunk_size: usize,
+) -> Result<SnapshotManifest<H>, SnapshotError> {
+    verify_state_root(header, state)?;
+    let number = (*header.number()).into();
+    let history_peaks = match header.history_root() {
+        Some(_) => history
+            .peaks_at(number)
+            .ok_or(SnapshotError::HistoryMismatch(number))?,
+        None => Vec::new(),
+    };
+    verify_history(header, &history_peaks)?;
+    let genesis_proof = match genesis_proof {
+        _ if number == 0 || header.history_root().is_none() => None,
+        Some(proof) => history.extend_proof(proof, number),
+        None => history.prove_at(0, number),
+    };
+    if genesis_proof.is_none() && !history_peaks.is_empty() {
+        return Err(SnapshotError::GenesisMismatch(number));
+    }
+    let entries = state.entries();
+    let entry_count = entries.len() as u64;
+    let chunks = chunks(entries, chunk_size);
+    let manifest = SnapshotManifest {
+        header: header.clone(),
+        history_peaks,
+        genesis_proof,
+        entry_count,
+        chunk_hashes: chunks
+            .iter()
+            .map(|chunk| KeccakHasher::hash(chunk))
+            .collect(),
+    };
+
+    let path = path.as_ref();
+    let partial = path.with_extension("partial");
+    let mut file = BufWriter::new(File::create(&partial)?);
+    file.write_all(MAGIC)?;
+    let payload = manifest.to_bytes();
+    file.write_all(&(payload.len() as u32).to_le_bytes())?;
+    file.write_all(&payload)?;
+    file.write_all(&KeccakHasher::hash(&payload))?;
+    for chunk in &chunks {
+        file.write_all(&(chunk.len() as u32).to_le_bytes())?;
+        file.write_all(chunk)?;
+    }
+    file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+    std::fs::rename(&partial, path)?;
+    Ok(manifest)
+}
+
+/// Reads a length-prefixed record, `remaining` being the number of bytes left in the file.
+/// The length is checked against it before anything is allocated.
+fn read_record(reader: &mut impl Read, remaining: &mut u64) -> Result<Vec<u8>, SnapshotError> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_le_bytes(len) as u64;
+    *remaining = remaining.saturating_sub(4);
+    if len > *remaining {
+        return Err(std::io::Error::from(ErrorKind::UnexpectedEof).into());
+    }
+    *remaining -= len;
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload)?;
+    Ok(payload)
+}
+
+/// Restores the state stored in the snapshot at `path`, verifying every chunk and the
+/// restored state against the state root of the snapshot header.
+pub fn read_snapshot<H: BlockHeaderT, S: SnapshotState>(
+    path: impl AsRef<Path>,
+) -> Result<(SnapshotManifest<H>, S), SnapshotError> {
+    let file = File::open(path)?;
+    let mut remaining = file.metadata()?.len();
+    let mut reader = BufReader::new(file);
+    let mut magic = [0u8; 8];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(SnapshotError::InvalidFormat);
+    }
+    remaining -= MAGIC.len() as u64;
+    let payload = read_record(&mut reader, &mut remaining)?;
+    let mut checksum = [0u8; 32];
+    reader.read_exact(&mut checksum)?;
+    remaining -= checksum.len() as u64;
+    if checksum != KeccakHasher::hash(&payload) {
+        return Err(SnapshotError::CorruptedManifest);
+    }
+    let manifest = SnapshotManifest::<H>::from_bytes(&payload)?;
+    verify_history(&manifest.header, &manifest.history_peaks)?;
+
+    let mut entries = Vec::new();
+    for (index, hash) in manifest.chunk_hashes.iter().enumerate() {
+        let chunk = read_record(&mut reader, &mut remaining)?;
+        if KeccakHasher::hash(&chunk) != *hash {
+            return Err(SnapshotError::CorruptedChunk(index));
+        }
+        entries.extend(Vec::<StateEntry>::from_bytes(&chunk)?);
+    }
+    if entries.len() as u64 != manifest.entry_count {
+        return Err(SnapshotError::EntryCountMismatch {
+            expected: manifest.entry_count,
+            found: entries.len() as u64,
+        });
+    }
+
+    let state = S::from_entries(entries)?;
+    verify_state_root(&manifest.header, &state)?;
+    Ok((manifest, state))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::HeaderExtension;
+    use crate::test_utils::{TestHeader, TestState, test_chain};
+
+    type KvState = TestState<Vec<u8>, Vec<u8>>;
+
+    impl SnapshotState for KvState {
+        fn entries(&self) -> Vec<StateEntry> {
+            self.0.clone().into_iter().collect()
+        }
+
+        fn from_entries(entries: Vec<StateEntry>) -> Result<Self, SnapshotError> {
+            Ok(Self(entries.into_iter().collect()))
+        }
+    }
+
+    fn state() -> KvState {
+        TestState(
+            (0u32..100)
+                .map(|i| (i.to_be_bytes().to_vec(), vec![i as u8; 10]))
+                .collect(),
+        )
+    }
+
+    /// Accumulator of the blocks before the snapshot header.
+    fn history() -> MerkleMountainRange<KeccakHasher> {
+        let mut history = MerkleMountainRange::new();
+        for block in test_chain(2) {
+            history.push(block.header.hash());
+        }
+        history
+    }
+
+    fn header(state: &KvState) -> TestHeader {
+        let mut header = test_chain(3).remove(2).header;
+        header.state_root = state.state_root();
+        let extension = HeaderExtension {
+            history_root: Some(history().root().into()),
+            ..Default::default()
+        };
+        header.with_extension(extension).unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.snapshot");
+        let state = state();
+        let header = header(&state);
+
+        // Each entry is 14 bytes, so chunks hold 8 entries
+        let manifest = write_snapshot(&path, &header, &state, &history(), None, 100).unwrap();
+        assert_eq!(manifest.entry_count, 100);
+        assert_eq!(manifest.chunk_hashes.len(), 13);
+        assert!(!dir.path().join("state.partial").exists());
+
+        let (restored_manifest, restored) = read_snapshot::<TestHeader, KvState>(&path).unwrap();
+        assert_eq!(restored, state);
+        assert_eq!(restored_manifest.chunk_hashes, manifest.chunk_hashes);
+        assert_eq!(restored_manifest.header.hash(), header.hash());
+        assert_eq!(restored_manifest.history_peaks, history().peaks());
+
+        // The header is tied to the genesis block it descends from
+        let genesis = test_chain(1).remove(0).header;
+        restored_manifest.verify_genesis(&genesis.hash()).unwrap();
+        assert!(matches!(
+            restored_manifest.verify_genesis(&[1; 32]),
+            Err(SnapshotError::GenesisMismatch(2))
+        ));
+        let mut unproven = restored_manifest.clone();
+        unproven.genesis_proof = None;
+        assert!(matches!(
+            unproven.verify_genesis(&genesis.hash()),
+            Err(SnapshotError::GenesisMismatch(2))
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_of_resumed_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.snapshot");
+        let state = state();
+        let header = header(&state);
+        let full = history();
+        let mut resumed = MerkleMountainRange::from_peaks(1, &full.peaks_at(1).unwrap()).unwrap();
+        resumed.push(*full.leaf(1).unwrap());
+
+        // The genesis block is only proven from its proof at the size the history resumed at
+        assert!(matches!(
+            write_snapshot(&path, &header, &state, &resumed, None, 100),
+            Err(SnapshotError::GenesisMismatch(2))
+        ));
+        let proof = full.prove_at(0, 1).unwrap();
+        let manifest = write_snapshot(&path, &header, &state, &resumed, Some(&proof), 100).unwrap();
+        assert_eq!(manifest.genesis_proof, full.prove(0));
+        manifest
+            .verify_genesis(&test_chain(1).remove(0).header.hash())
+            .unwrap();
+    }
+
+    #[test]
+    fn test_rejects_other_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.snapshot");
+        let state = state();
+        let header = header(&state);
+        assert!(matches!(
+            write_snapshot(
+                &path,
+                &header,
+                &state,
+                &MerkleMountainRange::new(),
+                None,
+                100
+            ),
+            Err(SnapshotError::HistoryMismatch(2))
+        ));
+
+        let mut other = MerkleMountainRange::new();
+        other.push([1; 32]);
+        other.push([2; 32]);
+        assert!(matches!(
+            write_snapshot(&path, &header, &state, &other, None, 100),
+            Err(SnapshotError::HistoryMismatch(2))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_state_of_other_block() {
+        let dir = tempfile::tempdir().unwrap();
+        let state = state();
+        let mut header = header(&state);
+        header.state_root = [1; 32];
+        assert!(matches!(
+            write_snapshot(
+                dir.path().join("state.snapshot"),
+                &header,
+                &state,
+                &history(),
+                None,
+                100
+            ),
+            Err(SnapshotError::StateRootMismatch(2))
+        ));
+    }
+
+    #[test]
+    fn test_detects_corruption() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("state.snapshot");
+        let state = state();
+        let header = header(&state);
+        write_snapshot(&path, &header, &state, &history(), None, 100).unwrap();
+        let data = std::fs::read(&path).unwrap();
+
+        // Flip a byte of the last chunk
+        let mut corrupted = data.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            read_snapshot::<TestHeader, KvState>(&path),
+            Err(SnapshotError::CorruptedChunk(12))
+        ));
+
+        // Flip a byte of the manifest
+        let mut corrupted = data.clone();
+        corrupted[MAGIC.len() + 8] ^= 1;
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            read_snapshot::<TestHeader, KvState>(&path),
+            Err(SnapshotError::CorruptedManifest)
+        ));
+
+        // Drop the last chunk
+        std::fs::write(&path, &data[..data.len() - 10]).unwrap();
+        assert!(matches!(
+            read_snapshot::<TestHeader, KvState>(&path),
+            Err(SnapshotError::Io(_))
+        ));
+
+        // A length past the end of the file is refused before allocating it
+        let mut corrupted = data.clone();
+        corrupted[MAGIC.len()..MAGIC.len() + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        std::fs::write(&path, &corrupted).unwrap();
+        assert!(matches!(
+            read_snapshot::<TestHeader, KvState>(&path),
+            Err(SnapshotError::Io(e)) if e.kind() == ErrorKind::UnexpectedEof
+        ));
+    }
+}
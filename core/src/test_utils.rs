@@ -0,0 +1,297 @@
+//! Helpers shared by the unit tests of this crate.
+use crate::block::{Block, BlockHeader};
+use crate::codec::{CodecError, Decode, Encode};
+use crate::hasher::KeccakHasher;
+use crate::journal::{Checkpoint, Journaled, KeyValueStore};
+use crate::metering::{Gas, GasMeter, OutOfGas};
+use crate::parallel::StateView;
+use crate::receipt::Receipt;
+use crate::smt::{Key, SparseMerkleTree, hashed_key};
+use crate::traits::{
+    AppState, BlockHeaderT, BlockT, HasherT, MerkleizedState, ParallelTransition, RevertibleState,
+    SignedTransactionT, StateTransitionFunction,
+};
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TestTransaction(pub Vec<u8>);
+
+impl Encode for TestTransaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.0.encode_to(out);
+    }
+}
+
+impl Decode for TestTransaction {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self(Vec::decode_from(input)?))
+    }
+}
+
+impl SignedTransactionT for TestTransaction {}
+
+pub type TestHeader = BlockHeader<u64, KeccakHasher>;
+pub type TestBlock = Block<TestHeader, TestTransaction>;
+
+/// Builds the child of `parent` carrying `transactions`, with a state root derived from `seed`.
+pub fn child_block(
+    parent: &TestHeader,
+    seed: &[u8],
+    transactions: Vec<TestTransaction>,
+) -> TestBlock {
+    let mut block = Block::new(
+        TestHeader::new(
+            parent.number + 1,
+            KeccakHasher::hash(seed),
+            parent.hash(),
+            [0; 32],
+        ),
+        transactions,
+    );
+    block.header.transactions_root = block.compute_transactions_root();
+    block
+}
+
+/// Builds a chain of `len` blocks starting with a genesis block.
+pub fn test_chain(len: usize) -> Vec<TestBlock> {
+    let genesis = Block::new(
+        TestHeader::new(
+            0,
+            KeccakHasher::hash(b"genesis"),
+            [0; 32],
+            crate::merkle::empty_root::<KeccakHasher>(),
+        ),
+        vec![],
+    );
+    let mut chain = vec![genesis];
+    while chain.len() < len {
+        let number = chain.len() as u8;
+        let block = child_block(
+            &chain[chain.len() - 1].header,
+            &[number],
+            vec![TestTransaction(vec![number; 4])],
+        );
+        chain.push(block);
+    }
+    chain.truncate(len);
+    chain
+}
+
+/// Key-value state whose root is the hash of its encoded entries.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TestState<K, V>(pub BTreeMap<K, V>);
+
+impl<K: Encode + Clone, V: Encode + Clone> AppState for TestState<K, V> {
+    fn state_root(&self) -> [u8; 32] {
+        let entries: Vec<(K, V)> = self.0.clone().into_iter().collect();
+        KeccakHasher::hash(&entries.to_bytes())
+    }
+
+    fn previous_state_root(&self) -> Option<[u8; 32]> {
+        None
+    }
+}
+
+impl<K: Ord + Clone, V: Clone> KeyValueStore for TestState<K, V> {
+    type Key = K;
+    type Value = V;
+
+    fn read(&self, key: &K) -> Option<V> {
+        KeyValueStore::read(&self.0, key)
+    }
+
+    fn write(&mut self, key: K, value: Option<V>) -> Option<V> {
+        KeyValueStore::write(&mut self.0, key, value)
+    }
+}
+
+// The checkpoint of a test state is a copy of it
+impl<K: Clone, V: Clone> RevertibleState for TestState<K, V> {
+    type Checkpoint = Self;
+
+    fn checkpoint(&mut self) -> Self {
+        self.clone()
+    }
+
+    fn revert(&mut self, checkpoint: Self) {
+        *self = checkpoint;
+    }
+
+    fn commit(&mut self, _checkpoint: Self) {}
+}
+
+/// State key of the balance of `account`.
+pub fn account_key(account: u16) -> Key {
+    hashed_key::<KeccakHasher>(&account.to_le_bytes())
+}
+
+/// Account balances, stored little-endian in a sparse Merkle tree, with the gas limit of the
+/// blocks applied to them.
+#[derive(Clone, Debug)]
+pub struct TestAccounts {
+    tree: Journaled<SparseMerkleTree<KeccakHasher>>,
+    gas_limit: Gas,
+}
+
+impl Default for TestAccounts {
+    fn default() -> Self {
+        Self {
+            tree: Journaled::default(),
+            gas_limit: Gas::MAX,
+        }
+    }
+}
+
+impl TestAccounts {
+    pub fn with_balances(balances: &[(u16, u64)]) -> Self {
+        let mut accounts = Self::default();
+        for (account, balance) in balances {
+            accounts
+                .tree
+                .write(account_key(*account), Some(balance.to_le_bytes().to_vec()));
+        }
+        accounts
+    }
+
+    pub fn with_gas_limit(self, gas_limit: Gas) -> Self {
+        Self { gas_limit, ..self }
+    }
+}
+
+impl AppState for TestAccounts {
+    fn state_root(&self) -> [u8; 32] {
+        self.tree.root()
+    }
+
+    fn previous_state_root(&self) -> Option<[u8; 32]> {
+        None
+    }
+}
+
+impl KeyValueStore for TestAccounts {
+    type Key = Key;
+    type Value = Vec<u8>;
+
+    fn read(&self, key: &Key) -> Option<Vec<u8>> {
+        self.tree.read(key)
+    }
+
+    fn write(&mut self, key: Key, value: Option<Vec<u8>>) -> Option<Vec<u8>> {
+        self.tree.write(key, value)
+    }
+}
+
+impl MerkleizedState for TestAccounts {
+    type Hasher = KeccakHasher;
+
+    fn tree(&self) -> &SparseMerkleTree<KeccakHasher> {
+        &self.tree
+    }
+}
+
+impl RevertibleState for TestAccounts {
+    type Checkpoint = Checkpoint;
+
+    fn checkpoint(&mut self) -> Checkpoint {
+        self.tree.checkpoint()
+    }
+
+    fn revert(&mut self, checkpoint: Checkpoint) {
+        self.tree.revert(checkpoint)
+    }
+
+    fn commit(&mut self, checkpoint: Checkpoint) {
+        self.tree.commit(checkpoint)
+    }
+}
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum TransferError {
+    #[error("Insufficient balance in account {}", _0)]
+    InsufficientBalance(u16),
+    #[error(transparent)]
+    OutOfGas(#[from] OutOfGas),
+}
+
+/// Gas charged for every transfer
+pub const TRANSFER_GAS: Gas = 1;
+
+/// Transfer of `amount` from account `from` to account `to`.
+pub fn transfer(from: u16, to: u16, amount: u64) -> TestTransaction {
+    let mut data = Vec::with_capacity(12);
+    data.extend(from.to_le_bytes());
+    data.extend(to.to_le_bytes());
+    data.extend(amount.to_le_bytes());
+    TestTransaction(data)
+}
+
+/// Transfers built with [`transfer`]. Emptied accounts are removed from the state.
+pub struct TestTransfers;
+
+impl TestTransfers {
+    fn decode(transaction: &TestTransaction) -> (u16, u16, u64) {
+        let data = &transaction.0;
+        (
+            u16::from_le_bytes([data[0], data[1]]),
+            u16::from_le_bytes([data[2], data[3]]),
+            u64::from_le_bytes(data[4..12].try_into().expect("transfer amount")),
+        )
+    }
+
+    fn balance(view: &mut impl StateView<Key = Key, Value = Vec<u8>>, account: u16) -> u64 {
+        view.read(&account_key(account))
+            .and_then(|value| Some(u64::from_le_bytes(value.try_into().ok()?)))
+            .unwrap_or_default()
+    }
+
+    fn set_balance(
+        view: &mut impl StateView<Key = Key, Value = Vec<u8>>,
+        account: u16,
+        balance: u64,
+    ) {
+        let value = (balance > 0).then(|| balance.to_le_bytes().to_vec());
+        view.write(account_key(account), value);
+    }
+}
+
+impl StateTransitionFunction for TestTransfers {
+    type State = TestAccounts;
+    type Error = TransferError;
+    type BlockHeader = TestHeader;
+    type Block = TestBlock;
+
+    fn validate_block(_state: &TestAccounts, _block: &TestBlock) -> Result<(), TransferError> {
+        Ok(())
+    }
+
+    fn block_gas_limit(state: &TestAccounts) -> Gas {
+        state.gas_limit
+    }
+
+    fn apply_transaction(
+        state: &mut TestAccounts,
+        transaction: &TestTransaction,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, TransferError> {
+        Self::execute_transaction(state, transaction, meter)
+    }
+}
+
+impl ParallelTransition for TestTransfers {
+    fn execute_transaction(
+        view: &mut impl StateView<Key = Key, Value = Vec<u8>>,
+        transaction: &TestTransaction,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, TransferError> {
+        meter.consume(TRANSFER_GAS)?;
+        let (from, to, amount) = Self::decode(transaction);
+        let balance = Self::balance(view, from);
+        if balance < amount {
+            return Err(TransferError::InsufficientBalance(from));
+        }
+        Self::set_balance(view, from, balance - amount);
+        let balance = Self::balance(view, to);
+        Self::set_balance(view, to, balance + amount);
+        Ok(Receipt::success(TRANSFER_GAS))
+    }
+}
@@ -1,26 +1,53 @@
 #![allow(dead_code)]
 use super::*;
+use crate::block::{HeaderError, HeaderExtension, L1Origin};
+use crate::codec::{Decode, Encode};
+use crate::merkle::{self, MerkleProof};
+use alloy::primitives::{Address, B256};
 use std::fmt::Debug;
 
-pub trait BlockT: Clone + Send + Sync + Debug + 'static {
+pub trait BlockT: Clone + Send + Sync + Debug + Encode + Decode + 'static {
     type Transaction: SignedTransactionT;
     type BlockHeader: BlockHeaderT;
 
     fn header(&self) -> &Self::BlockHeader;
     fn transactions(&self) -> &[Self::Transaction];
     fn new(header: Self::BlockHeader, transactions: Vec<Self::Transaction>) -> Self;
+
+    // Computes the Merkle root over the encoded transactions.
+    fn compute_transactions_root(&self) -> <Self::BlockHeader as BlockHeaderT>::Hash {
+        let leaves: Vec<Vec<u8>> = self.transactions().iter().map(|tx| tx.to_bytes()).collect();
+        merkle::merkle_root::<<Self::BlockHeader as BlockHeaderT>::Hashing, _>(&leaves)
+    }
+
+    // Returns an inclusion proof for the transaction at `index`.
+    fn transaction_proof(
+        &self,
+        index: usize,
+    ) -> Option<MerkleProof<<Self::BlockHeader as BlockHeaderT>::Hash>> {
+        let leaves: Vec<Vec<u8>> = self.transactions().iter().map(|tx| tx.to_bytes()).collect();
+        merkle::merkle_proof::<<Self::BlockHeader as BlockHeaderT>::Hashing, _>(&leaves, index)
+    }
 }
 
-pub trait BlockHeaderT: Clone + Send + Sync + Debug + 'static {
+pub trait BlockHeaderT: Clone + Send + Sync + Debug + Encode + Decode + 'static {
     // Header number
-    type Number: Into<u64>;
+    type Number: Into<u64> + TryFrom<u64> + Copy;
     // Header hash type
-    type Hash: AsRef<[u8]>;
+    type Hash: AsRef<[u8]> + Copy + Eq + std::hash::Hash + Debug + Send + Sync + Encode + Decode;
     // Hashing algorithm;
     type Hashing: HasherT<Output = Self::Hash>;
 
     // Creates new header
-    fn new(number: Self::Number, state_root: Self::Hash, parent_hash: Self::Hash) -> Self;
+    fn new(
+        number: Self::Number,
+        state_root: Self::Hash,
+        parent_hash: Self::Hash,
+        transactions_root: Self::Hash,
+    ) -> Self;
+
+    // Returns the header layout version.
+    fn version(&self) -> u8;
 
     // Return reference to the header number.
     fn number(&self) -> &Self::Number;
@@ -31,8 +58,69 @@ pub trait BlockHeaderT: Clone + Send + Sync + Debug + 'static {
     // Returns a reference to the parent hash.
     fn parent_hash(&self) -> &Self::Hash;
 
-    // Returns a vector of encoded values
-    fn encode(&self) -> Vec<u8>;
+    // Returns a reference to the Merkle root of the block transactions.
+    fn transactions_root(&self) -> &Self::Hash;
+
+    // Returns the optional header fields.
+    fn extension(&self) -> &HeaderExtension;
+
+    // Sets the optional header fields, upgrading the header version.
+    fn with_extension(self, extension: HeaderExtension) -> Result<Self, HeaderError>;
+
+    // Returns the header given to the block hooks: the fields known before the transactions
+    // are chosen and applied. The state and transactions roots are the empty root and the
+    // roots committing to the execution are unset, so that the block builder and the
+    // validators derive the same header.
+    fn unsealed(&self) -> Self;
+
+    // Returns the block timestamp if present.
+    fn timestamp(&self) -> Option<u64> {
+        self.extension().timestamp
+    }
+
+    // Returns the block proposer if present.
+    fn proposer(&self) -> Option<Address> {
+        self.extension().proposer
+    }
+
+    // Returns the L1 origin if present.
+    fn l1_origin(&self) -> Option<&L1Origin> {
+        self.extension().l1_origin.as_ref()
+    }
+
+    // Returns the data availability commitment if present.
+    fn da_commitment(&self) -> Option<B256> {
+        self.extension().da_commitment
+    }
+
+    // Returns the root of the accumulator of all preceding block hashes if present.
+    fn history_root(&self) -> Option<B256> {
+        self.extension().history_root
+    }
+
+    // Returns the Merkle root of the transaction receipts if present.
+    fn receipts_root(&self) -> Option<B256> {
+        self.extension().receipts_root
+    }
+
+    // Returns the Merkle root of the state roots after each transaction if present.
+    fn intermediate_roots_root(&self) -> Option<B256> {
+        self.extension().intermediate_roots_root
+    }
+
+    // Verifies that `transaction` is committed to by the transactions root.
+    fn verify_transaction<Tx: Encode>(
+        &self,
+        transaction: &Tx,
+        proof: &MerkleProof<Self::Hash>,
+    ) -> bool {
+        proof.verify::<Self::Hashing>(self.transactions_root(), &transaction.to_bytes())
+    }
+
+    // Returns the canonical versioned encoding of the header.
+    fn encode(&self) -> Vec<u8> {
+        self.to_bytes()
+    }
 
     // Returns the hash of the header.
     fn hash(&self) -> Self::Hash {
@@ -1,6 +1,20 @@
+use crate::codec::{Decode, Encode};
+use crate::hasher::HasherKind;
 use std::fmt::Debug;
 
 pub trait HasherT: Sync + Send {
-    type Output: AsRef<[u8]> + Send + Sync + Clone + Debug + Copy;
+    type Output: AsRef<[u8]>
+        + Send
+        + Sync
+        + Clone
+        + Debug
+        + Copy
+        + Default
+        + Eq
+        + std::hash::Hash
+        + Encode
+        + Decode;
+    // Algorithm recorded in the headers hashed with this hasher.
+    const KIND: HasherKind;
     fn hash(s: &[u8]) -> Self::Output;
 }
@@ -1,10 +1,12 @@
 mod block;
 mod hasher;
+mod prover;
 mod signed_tx;
 mod state_machine;
 
 // Re-export traits for easier access
 pub use block::*;
 pub use hasher::*;
+pub use prover::*;
 pub use signed_tx::*;
 pub use state_machine::*;
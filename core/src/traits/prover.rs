@@ -0,0 +1,26 @@
+use super::StateTransitionFunction;
+use std::error::Error;
+
+/// Proves that applying a block to a state yields the state root committed to by its header.
+pub trait ProverT<S: StateTransitionFunction> {
+    type Proof;
+    type Error: Error + Send + Sync;
+
+    // Prove the transition of `state` by `block`, leaving `state` unchanged.
+    fn prove(&self, state: &S::State, block: &S::Block) -> Result<Self::Proof, Self::Error>;
+}
+
+/// Checks validity proofs without access to the state.
+pub trait VerifierT<S: StateTransitionFunction> {
+    type Proof;
+    type Error: Error + Send + Sync;
+
+    // Check that `proof` shows `block` moves the state from `pre_state_root` to the state
+    // root of its header.
+    fn verify(
+        &self,
+        pre_state_root: &[u8; 32],
+        block: &S::Block,
+        proof: &Self::Proof,
+    ) -> Result<(), Self::Error>;
+}
@@ -1,2 +1,3 @@
+use crate::codec::{Decode, Encode};
 use std::fmt::Debug;
-pub trait SignedTransactionT: Clone + Send + Sync + Debug + 'static {}
+pub trait SignedTransactionT: Clone + Send + Sync + Debug + Encode + Decode + 'static {}
@@ -1,4 +1,12 @@
-use super::{BlockHeaderT, BlockT};
+use super::{BlockHeaderT, BlockT, HasherT};
+use crate::genesis::{Genesis, GenesisError};
+use crate::journal::KeyValueStore;
+use crate::metering::{Gas, GasMeter, OutOfGas};
+use crate::parallel::StateView;
+use crate::receipt::Receipt;
+use crate::smt::{Key, SparseMerkleTree};
+use crate::snapshot::{SnapshotError, StateEntry};
+use alloy::primitives::B256;
 use std::{error::Error, fmt::Debug};
 
 pub trait AppState {
@@ -9,15 +17,138 @@ pub trait AppState {
     fn previous_state_root(&self) -> Option<[u8; 32]>;
 }
 
+pub type TransactionOf<S> = <<S as StateTransitionFunction>::Block as BlockT>::Transaction;
+
 pub trait StateTransitionFunction {
     type State: AppState;
-    type Error: Error + Debug + Send + Sync;
+    type Error: Error + Debug + Send + Sync + From<OutOfGas>;
     type BlockHeader: BlockHeaderT;
-    type Block: BlockT;
+    type Block: BlockT<BlockHeader = Self::BlockHeader>;
 
     // Validate block
     fn validate_block(state: &Self::State, block: &Self::Block) -> Result<(), Self::Error>;
 
-    // Apply block
-    fn apply_block(state: &mut Self::State, block: &Self::Block) -> Result<(), Self::Error>;
+    // Returns the gas available to the transactions of the next block.
+    fn block_gas_limit(_state: &Self::State) -> Gas {
+        Gas::MAX
+    }
+
+    // Prepare the state before the transactions of the block are applied. `header` is the
+    // unsealed header of the block, see `BlockHeaderT::unsealed`. The state root must be left
+    // unchanged, as fraud proofs execute the first transaction from the parent state root.
+    fn begin_block(
+        _state: &mut Self::State,
+        _header: &Self::BlockHeader,
+    ) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    // Apply a single transaction, charging the resources it uses to `meter`. A failed
+    // transaction may leave the state partially modified.
+    fn apply_transaction(
+        state: &mut Self::State,
+        transaction: &TransactionOf<Self>,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, Self::Error>;
+
+    // Finalize the state after the transactions of the block were applied. `header` is the
+    // unsealed header of the block. The state root must be left unchanged, as the state root
+    // of the block is the one committed after its last transaction.
+    fn end_block(_state: &mut Self::State, _header: &Self::BlockHeader) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    // Returns the Merkle root of the withdrawals to L1 made by `transactions`, which the
+    // block builder commits to in the header. Applications without withdrawals return `None`.
+    fn withdrawals_root(_transactions: &[TransactionOf<Self>]) -> Option<B256> {
+        None
+    }
+
+    // Returns the hash chain of the L1 deposits applied to `state`, which the block builder
+    // commits to in the header once the block is applied, so that the deposits of a block can
+    // be checked against L1. Applications without deposits return `None`.
+    fn deposits_hash(_state: &Self::State) -> Option<B256> {
+        None
+    }
+
+    // Apply block, returning one receipt per transaction. Every receipt reports the gas
+    // charged to its transaction, and the whole block may not exceed the block gas limit.
+    fn apply_block(
+        state: &mut Self::State,
+        block: &Self::Block,
+    ) -> Result<Vec<Receipt>, Self::Error> {
+        Self::validate_block(state, block)?;
+        let mut meter = GasMeter::new(Self::block_gas_limit(state));
+        let header = block.header().unsealed();
+        Self::begin_block(state, &header)?;
+        let mut receipts = Vec::with_capacity(block.transactions().len());
+        for transaction in block.transactions() {
+            let (receipt, used) =
+                meter.scoped(|meter| Self::apply_transaction(state, transaction, meter));
+            receipts.push(Receipt {
+                resources_used: used,
+                ..receipt?
+            });
+        }
+        Self::end_block(state, &header)?;
+        Ok(receipts)
+    }
+}
+
+/// State that can be initialized from a genesis specification.
+pub trait GenesisState: AppState + Sized {
+    // Application part of the genesis specification
+    type Genesis;
+
+    // Builds the initial state described by `genesis`.
+    fn from_genesis(genesis: &Genesis<Self::Genesis>) -> Result<Self, GenesisError>;
+}
+
+/// State that can be exported to and restored from a snapshot.
+pub trait SnapshotState: AppState + Sized {
+    // Returns the key-value entries making up the state.
+    fn entries(&self) -> Vec<StateEntry>;
+
+    // Rebuilds the state from the entries of a snapshot.
+    fn from_entries(entries: Vec<StateEntry>) -> Result<Self, SnapshotError>;
+}
+
+/// State whose changes can be rolled back to a checkpoint without cloning it. Checkpoints
+/// nest and are closed in reverse order.
+pub trait RevertibleState {
+    type Checkpoint;
+
+    // Opens a checkpoint at the current state.
+    fn checkpoint(&mut self) -> Self::Checkpoint;
+
+    // Undoes every change made since `checkpoint`.
+    fn revert(&mut self, checkpoint: Self::Checkpoint);
+
+    // Keeps the changes made since `checkpoint`, an enclosing checkpoint may still revert them.
+    fn commit(&mut self, checkpoint: Self::Checkpoint);
+}
+
+/// State whose entries are stored in a sparse Merkle tree, the root of which is the state root.
+pub trait MerkleizedState: AppState + KeyValueStore<Key = Key, Value = Vec<u8>> {
+    type Hasher: HasherT<Output = [u8; 32]>;
+
+    // Returns the tree of the state entries.
+    fn tree(&self) -> &SparseMerkleTree<Self::Hasher>;
+}
+
+/// State transition whose transactions only access the state through a [`StateView`], so
+/// that they can be executed in parallel by a
+/// [`ParallelExecutor`](crate::parallel::ParallelExecutor).
+pub trait ParallelTransition: StateTransitionFunction<State: KeyValueStore> {
+    // Execute a single transaction against `view`, charging the resources it uses to `meter`.
+    // Must have the same effect as `apply_transaction` on the state `view` reads from, which
+    // is what sequential execution passes.
+    fn execute_transaction(
+        view: &mut impl StateView<
+            Key = <Self::State as KeyValueStore>::Key,
+            Value = <Self::State as KeyValueStore>::Value,
+        >,
+        transaction: &TransactionOf<Self>,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, Self::Error>;
 }
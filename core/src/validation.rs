@@ -0,0 +1,267 @@
+//! Structural validation of a block against its parent, run before the state transition
+//! function is invoked.
+use crate::fraud_proof::{IntermediateRoot, intermediate_roots_root};
+use crate::receipt::{Receipt, receipts_root};
+use crate::traits::{BlockHeaderT, BlockT};
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum ValidationError {
+    /// Parent hash is not the hash of the parent header
+    #[error("Parent hash does not match the parent header")]
+    ParentHashMismatch,
+    /// Number is not the parent number plus one
+    #[error("Expected block number {}, found {}", expected, found)]
+    InvalidNumber { expected: u64, found: u64 },
+    /// Timestamp is not after the parent timestamp
+    #[error("Timestamp {} is not after the parent timestamp {}", timestamp, parent)]
+    TimestampNotIncreasing { parent: u64, timestamp: u64 },
+    /// Parent carries a timestamp but the block does not
+    #[error("Block is missing a timestamp")]
+    MissingTimestamp,
+    /// Transactions root does not commit to the block transactions
+    #[error("Transactions root does not match the block transactions")]
+    TransactionsRootMismatch,
+    /// Receipts root does not commit to the receipts produced by the block
+    #[error("Receipts root does not match the block receipts")]
+    ReceiptsRootMismatch,
+    /// Header does not commit to the state roots after each transaction
+    #[error("Block does not commit to intermediate state roots")]
+    MissingIntermediateRoots,
+    /// Intermediate roots root does not commit to the state roots after each transaction
+    #[error("Intermediate roots root does not match the state roots of the block")]
+    IntermediateRootsMismatch,
+    /// State root differs from the one after the last transaction, changed by a block hook
+    #[error("State root does not match the state root after the last transaction")]
+    FinalRootMismatch,
+}
+
+/// Checks that `header` extends `parent`.
+pub fn validate_header<H: BlockHeaderT>(parent: &H, header: &H) -> Result<(), ValidationError> {
+    if *header.parent_hash() != parent.hash() {
+        return Err(ValidationError::ParentHashMismatch);
+    }
+    let expected = (*parent.number()).into().saturating_add(1);
+    let found = (*header.number()).into();
+    if found != expected {
+        return Err(ValidationError::InvalidNumber { expected, found });
+    }
+    match (parent.timestamp(), header.timestamp()) {
+        (Some(parent), Some(timestamp)) if timestamp <= parent => {
+            Err(ValidationError::TimestampNotIncreasing { parent, timestamp })
+        }
+        (Some(_), None) => Err(ValidationError::MissingTimestamp),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that `block` extends `parent` and that its header commits to its transactions.
+pub fn validate_block<B: BlockT>(
+    parent: &B::BlockHeader,
+    block: &B,
+) -> Result<(), ValidationError> {
+    validate_header(parent, block.header())?;
+    if *block.header().transactions_root() != block.compute_transactions_root() {
+        return Err(ValidationError::TransactionsRootMismatch);
+    }
+    Ok(())
+}
+
+/// Checks that the receipts produced by applying a block match its receipts root, when the
+/// header carries one.
+pub fn validate_receipts<H: BlockHeaderT>(
+    header: &H,
+    receipts: &[Receipt],
+) -> Result<(), ValidationError> {
+    match header.receipts_root() {
+        Some(root) if receipts_root::<H::Hashing>(receipts).as_ref() != root.as_slice() => {
+            Err(ValidationError::ReceiptsRootMismatch)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the state after each transaction of a block matches the intermediate roots
+/// root of its header, and that the last of them, or the state root of `parent` for a block
+/// without transactions, is the state root of the block. A header without the root could
+/// not be disputed, and is invalid.
+pub fn validate_intermediate_roots<H: BlockHeaderT>(
+    parent: &H,
+    header: &H,
+    roots: &[IntermediateRoot],
+) -> Result<(), ValidationError> {
+    let final_root = match roots.last() {
+        Some(last) => last.state_root.as_slice(),
+        None => parent.state_root().as_ref(),
+    };
+    if header.state_root().as_ref() != final_root {
+        return Err(ValidationError::FinalRootMismatch);
+    }
+    match header.intermediate_roots_root() {
+        None => Err(ValidationError::MissingIntermediateRoots),
+        Some(root) if intermediate_roots_root::<H::Hashing>(roots).as_ref() != root.as_slice() => {
+            Err(ValidationError::IntermediateRootsMismatch)
+        }
+        Some(_) => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::block::HeaderExtension;
+    use crate::hasher::KeccakHasher;
+    use crate::test_utils::{TestHeader, TestTransaction, child_block, test_chain};
+
+    fn with_timestamp(header: TestHeader, timestamp: u64) -> TestHeader {
+        header
+            .with_extension(HeaderExtension {
+                timestamp: Some(timestamp),
+                ..Default::default()
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn test_valid_chain() {
+        let chain = test_chain(5);
+        for pair in chain.windows(2) {
+            assert_eq!(validate_block(&pair[0].header, &pair[1]), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_rejects_broken_linkage() {
+        let chain = test_chain(3);
+        assert_eq!(
+            validate_block(&chain[0].header, &chain[2]),
+            Err(ValidationError::ParentHashMismatch)
+        );
+
+        let mut block = chain[2].clone();
+        block.header.number = 5;
+        assert_eq!(
+            validate_block(&chain[1].header, &block),
+            Err(ValidationError::InvalidNumber {
+                expected: 2,
+                found: 5
+            })
+        );
+
+        let mut block = chain[2].clone();
+        block
+            .transactions
+            .push(TestTransaction(b"injected".to_vec()));
+        assert_eq!(
+            validate_block(&chain[1].header, &block),
+            Err(ValidationError::TransactionsRootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_timestamps_increase() {
+        let genesis = test_chain(1).remove(0);
+        let parent = with_timestamp(genesis.header.clone(), 100);
+
+        let child = child_block(&parent, b"child", vec![]).header;
+        assert_eq!(
+            validate_header(&parent, &child),
+            Err(ValidationError::MissingTimestamp)
+        );
+        assert_eq!(
+            validate_header(&parent, &with_timestamp(child.clone(), 100)),
+            Err(ValidationError::TimestampNotIncreasing {
+                parent: 100,
+                timestamp: 100
+            })
+        );
+        assert_eq!(
+            validate_header(&parent, &with_timestamp(child.clone(), 101)),
+            Ok(())
+        );
+
+        // Timestamps may start at any block
+        let child = child_block(&genesis.header, b"child", vec![]).header;
+        assert_eq!(
+            validate_header(&genesis.header, &with_timestamp(child, 1)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn test_receipts_root() {
+        let genesis = test_chain(1).remove(0);
+        let receipts = vec![Receipt::success(1), Receipt::failure(2)];
+        // Headers without a receipts root accept any receipts
+        assert_eq!(validate_receipts(&genesis.header, &receipts), Ok(()));
+
+        let header = genesis
+            .header
+            .with_extension(HeaderExtension {
+                receipts_root: Some(receipts_root::<KeccakHasher>(&receipts).into()),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(validate_receipts(&header, &receipts), Ok(()));
+        assert_eq!(
+            validate_receipts(&header, &receipts[..1]),
+            Err(ValidationError::ReceiptsRootMismatch)
+        );
+    }
+
+    #[test]
+    fn test_intermediate_roots() {
+        let genesis = test_chain(1).remove(0);
+        let root = |state_root, gas_used| IntermediateRoot {
+            state_root,
+            gas_used,
+        };
+        let parent = genesis.header.clone();
+        let last = [9; 32];
+        let roots = [root([1; 32], 3), root(last, 5)];
+        let mut header = genesis
+            .header
+            .with_extension(HeaderExtension {
+                intermediate_roots_root: Some(
+                    intermediate_roots_root::<KeccakHasher>(&roots).into(),
+                ),
+                ..Default::default()
+            })
+            .unwrap();
+        header.state_root = last;
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &roots),
+            Ok(())
+        );
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &[root([2; 32], 3), root(last, 5)]),
+            Err(ValidationError::IntermediateRootsMismatch)
+        );
+        // The gas used is committed to as well
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &[root([1; 32], 3), root(last, 6)]),
+            Err(ValidationError::IntermediateRootsMismatch)
+        );
+        // The block hooks may not change the state after the last transaction
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &[root(last, 3), root([1; 32], 5)]),
+            Err(ValidationError::FinalRootMismatch)
+        );
+
+        // A block without transactions keeps the parent state root
+        header.extension.intermediate_roots_root =
+            Some(intermediate_roots_root::<KeccakHasher>(&[]).into());
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &[]),
+            Err(ValidationError::FinalRootMismatch)
+        );
+        header.state_root = parent.state_root;
+        assert_eq!(validate_intermediate_roots(&parent, &header, &[]), Ok(()));
+
+        // A header without the commitment is invalid
+        header.extension.intermediate_roots_root = None;
+        assert_eq!(
+            validate_intermediate_roots(&parent, &header, &[]),
+            Err(ValidationError::MissingIntermediateRoots)
+        );
+    }
+}
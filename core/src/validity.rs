@@ -0,0 +1,133 @@
+//! Mock validity proofs, for running a rollup against the prover and verifier interfaces
+//! without proving.
+//!
+//! The mock prover executes the block and only attests to the outcome, so its proofs carry
+//! no cryptographic guarantee: the verifier trusts whoever produced them.
+use crate::traits::{AppState, BlockHeaderT, BlockT, ProverT, StateTransitionFunction, VerifierT};
+use serde::{Deserialize, Serialize};
+use std::marker::PhantomData;
+
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+pub enum MockProofError<E> {
+    /// Block cannot be applied to the state
+    #[error(transparent)]
+    StateTransition(E),
+    /// Applying the block does not yield the state root of its header
+    #[error("Block does not yield the state root of its header")]
+    StateRootMismatch,
+    /// Proof is for another pre-state root or block
+    #[error("Proof does not match the pre-state root and block")]
+    StatementMismatch,
+}
+
+/// Statement attested to by the mock prover.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MockProof<Hash> {
+    pub pre_state_root: [u8; 32],
+    /// Hash of the header of the block, committing to its post-state root
+    pub block_hash: Hash,
+}
+
+type HeaderHashOf<S> = <<S as StateTransitionFunction>::BlockHeader as BlockHeaderT>::Hash;
+
+/// Prover executing the block on a copy of the state.
+#[derive(Debug)]
+pub struct MockProver<S>(PhantomData<S>);
+
+impl<S> Default for MockProver<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S> ProverT<S> for MockProver<S>
+where
+    S: StateTransitionFunction,
+    S::State: Clone,
+{
+    type Proof = MockProof<HeaderHashOf<S>>;
+    type Error = MockProofError<S::Error>;
+
+    fn prove(&self, state: &S::State, block: &S::Block) -> Result<Self::Proof, Self::Error> {
+        let mut post_state = state.clone();
+        S::apply_block(&mut post_state, block).map_err(MockProofError::StateTransition)?;
+        if post_state.state_root().as_slice() != block.header().state_root().as_ref() {
+            return Err(MockProofError::StateRootMismatch);
+        }
+        Ok(MockProof {
+            pre_state_root: state.state_root(),
+            block_hash: block.header().hash(),
+        })
+    }
+}
+
+/// Verifier accepting any mock proof of the statement.
+#[derive(Debug)]
+pub struct MockVerifier<S>(PhantomData<S>);
+
+impl<S> Default for MockVerifier<S> {
+    fn default() -> Self {
+        Self(PhantomData)
+    }
+}
+
+impl<S: StateTransitionFunction> VerifierT<S> for MockVerifier<S> {
+    type Proof = MockProof<HeaderHashOf<S>>;
+    type Error = MockProofError<S::Error>;
+
+    fn verify(
+        &self,
+        pre_state_root: &[u8; 32],
+        block: &S::Block,
+        proof: &Self::Proof,
+    ) -> Result<(), Self::Error> {
+        if proof.pre_state_root != *pre_state_root || proof.block_hash != block.header().hash() {
+            return Err(MockProofError::StatementMismatch);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{BlockBuilder, BlockBuilderConfig};
+    use crate::test_utils::{TestAccounts, TestTransfers, test_chain, transfer};
+
+    #[test]
+    fn test_mock_proof_round_trip() {
+        let parent = test_chain(1).remove(0).header;
+        let state = TestAccounts::with_balances(&[(1, 100), (2, 50)]);
+        let mut post_state = state.clone();
+        let block = BlockBuilder::<TestTransfers>::new(BlockBuilderConfig::default())
+            .build(
+                &parent,
+                &mut post_state,
+                &[transfer(1, 2, 30), transfer(2, 1, 5)],
+                None,
+            )
+            .unwrap()
+            .block;
+
+        let prover = MockProver::<TestTransfers>::default();
+        let verifier = MockVerifier::<TestTransfers>::default();
+        let proof = prover.prove(&state, &block).unwrap();
+        assert_eq!(verifier.verify(&state.state_root(), &block, &proof), Ok(()));
+        assert_eq!(
+            verifier.verify(&post_state.state_root(), &block, &proof),
+            Err(MockProofError::StatementMismatch)
+        );
+
+        // The prover refuses to attest to a wrong post-state root
+        let mut forged = block.clone();
+        forged.header.state_root = [1; 32];
+        assert_eq!(
+            prover.prove(&state, &forged).unwrap_err(),
+            MockProofError::StateRootMismatch
+        );
+        assert_eq!(
+            verifier.verify(&state.state_root(), &forged, &proof),
+            Err(MockProofError::StatementMismatch)
+        );
+    }
+}
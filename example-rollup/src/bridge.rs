@@ -0,0 +1,495 @@
+//! Bridge moving tokens between L1 and the rollup.
+//!
+//! Tokens locked in the L1 bridge contract are credited on the rollup by deposit system
+//! transactions, derived from the `Deposited` events of the contract. The contract and the
+//! rollup state both keep a hash chain over the deposits, which every header commits to, so
+//! that a block applying deposits not made on L1 cannot be settled. Withdrawals burn the
+//! tokens on the rollup: every block commits to its withdrawals through their Merkle root in
+//! the header, and once the block is settled on L1 a withdrawal is claimed there with its
+//! inclusion proof against that root.
+use crate::errors::BridgeError;
+use crate::state::TokenDappBlock;
+use crate::types::{BlockNumber, Deposit, Hash, Transaction, Withdrawal};
+use alloy::primitives::{Address, B256, U256};
+use alloy::providers::Provider;
+use alloy::rpc::types::{Filter, Log};
+use alloy::sol;
+use alloy::sol_types::{SolEvent, SolValue};
+use anunaya_rollup_core::hasher::KeccakHasher;
+use anunaya_rollup_core::merkle::{self, MerkleProof};
+use anunaya_rollup_core::traits::HasherT;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+sol! {
+    /// Events of the L1 bridge contract.
+    interface L1Bridge {
+        /// Emitted when `amount` is locked for `recipient` on the rollup
+        event Deposited(uint64 indexed index, address indexed recipient, uint256 amount);
+    }
+}
+
+/// Reads the deposit made by a `Deposited` event of the bridge.
+pub fn deposit_from_log(log: &Log) -> Result<Deposit, BridgeError> {
+    let event = log.log_decode::<L1Bridge::Deposited>()?.inner.data;
+    Ok(Deposit {
+        index: event.index,
+        l1_block: log.block_number.ok_or(BridgeError::PendingLog)?,
+        recipient: event.recipient,
+        amount: event
+            .amount
+            .try_into()
+            .map_err(|_| BridgeError::AmountOverflow(event.index))?,
+    })
+}
+
+/// Extends the deposits hash chain `previous` with `deposit`, as the L1 bridge does when the
+/// deposit is made.
+pub fn chain_deposit(previous: &Hash, deposit: &Deposit) -> Hash {
+    let encoded = (
+        B256::from(*previous),
+        deposit.index,
+        deposit.recipient,
+        U256::from(deposit.amount),
+    )
+        .abi_encode();
+    KeccakHasher::hash(&encoded)
+}
+
+/// Deposits made on L1 and not applied on the rollup yet, by index.
+#[derive(Debug, Clone)]
+pub struct DepositInbox {
+    bridge: Address,
+    deposits: BTreeMap<u64, Deposit>,
+}
+
+impl DepositInbox {
+    /// Inbox of the deposits made to the bridge contract at `bridge`.
+    pub fn new(bridge: Address) -> Self {
+        Self {
+            bridge,
+            deposits: BTreeMap::new(),
+        }
+    }
+
+    /// Records the deposits of `logs`, ignoring the logs of other contracts. Returns the
+    /// number of deposits not seen before.
+    pub fn ingest(&mut self, logs: &[Log]) -> Result<usize, BridgeError> {
+        let mut added = 0;
+        for log in logs.iter().filter(|log| log.address() == self.bridge) {
+            let deposit = deposit_from_log(log)?;
+            match self.deposits.get(&deposit.index) {
+                Some(known) if *known != deposit => {
+                    return Err(BridgeError::ConflictingDeposit(deposit.index));
+                }
+                Some(_) => {}
+                None => {
+                    self.deposits.insert(deposit.index, deposit);
+                    added += 1;
+                }
+            }
+        }
+        Ok(added)
+    }
+
+    /// Fetches and records the deposits made in L1 blocks `from..=to`.
+    pub async fn fetch<P: Provider>(
+        &mut self,
+        provider: &P,
+        from: u64,
+        to: u64,
+    ) -> Result<usize, BridgeError> {
+        let filter = Filter::new()
+            .address(self.bridge)
+            .event_signature(L1Bridge::Deposited::SIGNATURE_HASH)
+            .from_block(from)
+            .to_block(to);
+        let logs = provider.get_logs(&filter).await?;
+        self.ingest(&logs)
+    }
+
+    /// Deposit system transactions to apply next, starting at deposit `next` and stopping at
+    /// the first deposit not seen yet.
+    pub fn transactions(&self, next: u64) -> Vec<Transaction> {
+        self.deposits
+            .range(next..)
+            .zip(next..)
+            .take_while(|((index, _), expected)| *index == expected)
+            .map(|((_, deposit), _)| Transaction::Deposit(deposit.clone()))
+            .collect()
+    }
+
+    /// Drops the deposits before `next`, once they are applied.
+    pub fn prune(&mut self, next: u64) {
+        self.deposits = self.deposits.split_off(&next);
+    }
+
+    /// Checks that every deposit of `block` was made on L1 as it is recorded.
+    pub fn validate(&self, block: &TokenDappBlock) -> Result<(), BridgeError> {
+        for transaction in &block.transactions {
+            if let Transaction::Deposit(deposit) = transaction
+                && self.deposits.get(&deposit.index) != Some(deposit)
+            {
+                return Err(BridgeError::UnknownDeposit(deposit.index));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Withdrawals made by `transactions`, in order, with their senders. Withdrawals whose
+/// signature recovers no sender are left out, as no valid block includes them.
+pub fn withdrawals(transactions: &[Transaction]) -> impl Iterator<Item = (Address, &Withdrawal)> {
+    transactions
+        .iter()
+        .filter_map(|transaction| match transaction {
+            Transaction::Withdrawal(signed) => Some((signed.recover().ok()?, &signed.payload)),
+            _ => None,
+        })
+}
+
+/// Leaf of a withdrawal in the withdrawals tree: its ABI encoding with its sender, which the
+/// L1 bridge rebuilds from the claimed withdrawal.
+pub fn withdrawal_leaf(sender: Address, withdrawal: &Withdrawal) -> Vec<u8> {
+    (
+        sender,
+        withdrawal.l1_recipient,
+        withdrawal.amount,
+        withdrawal.nonce,
+    )
+        .abi_encode()
+}
+
+/// Computes the Merkle root of the withdrawals made by `transactions`.
+pub fn withdrawals_root(transactions: &[Transaction]) -> Hash {
+    let leaves: Vec<Vec<u8>> = withdrawals(transactions)
+        .map(|(sender, withdrawal)| withdrawal_leaf(sender, withdrawal))
+        .collect();
+    merkle::merkle_root::<KeccakHasher, _>(&leaves)
+}
+
+/// Withdrawal with its sender and inclusion proof in the withdrawals tree of a block.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WithdrawalClaim {
+    pub block_number: BlockNumber,
+    pub sender: Address,
+    pub withdrawal: Withdrawal,
+    pub proof: MerkleProof<Hash>,
+}
+
+/// Builds the claim of the `index`th withdrawal of `block`.
+pub fn withdrawal_claim(block: &TokenDappBlock, index: usize) -> Option<WithdrawalClaim> {
+    let withdrawals: Vec<_> = withdrawals(&block.transactions).collect();
+    let leaves: Vec<Vec<u8>> = withdrawals
+        .iter()
+        .map(|(sender, withdrawal)| withdrawal_leaf(*sender, withdrawal))
+        .collect();
+    let (sender, withdrawal) = withdrawals.get(index)?;
+    Some(WithdrawalClaim {
+        block_number: block.header.number,
+        sender: *sender,
+        withdrawal: (*withdrawal).clone(),
+        proof: merkle::merkle_proof::<KeccakHasher, _>(&leaves, index)?,
+    })
+}
+
+/// Checks `claim` against the withdrawals root of its block, as settled on L1.
+pub fn verify_claim(withdrawals_root: &Hash, claim: &WithdrawalClaim) -> bool {
+    claim.proof.verify::<KeccakHasher>(
+        withdrawals_root,
+        &withdrawal_leaf(claim.sender, &claim.withdrawal),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::TokenDappRollupError;
+    use crate::state::{TokenDappHeader, TokenDappRollup, TokenDappState};
+    use crate::types::{Amount, Signed, Transfer};
+    use alloy::signers::local::PrivateKeySigner;
+    use anunaya_rollup_core::builder::{BlockBuilder, BlockBuilderConfig};
+    use anunaya_rollup_core::codec::Encode;
+    use anunaya_rollup_core::data_availability::{BlobReceipt, blob_commitment};
+    use anunaya_rollup_core::traits::{AppState, BlockHeaderT, StateTransitionFunction};
+    use sequencer::settlement::BatchCommitment;
+    use std::collections::{HashMap, HashSet};
+
+    fn signer(byte: u8) -> PrivateKeySigner {
+        PrivateKeySigner::from_bytes(&B256::repeat_byte(byte)).unwrap()
+    }
+
+    fn alice() -> PrivateKeySigner {
+        signer(0xaa)
+    }
+
+    fn bob() -> PrivateKeySigner {
+        signer(0xbb)
+    }
+
+    /// L1 side of the bridge: the deposit events with the deposits hash chain after each of
+    /// them, and the withdrawals roots of the batches settled by the rollup contract with the
+    /// withdrawals claimed against them.
+    struct MockL1 {
+        bridge: Address,
+        logs: Vec<Log>,
+        deposits_hashes: Vec<Hash>,
+        settled: HashMap<u64, Hash>,
+        claimed: HashSet<Vec<u8>>,
+    }
+
+    impl MockL1 {
+        fn new() -> Self {
+            Self {
+                bridge: Address::repeat_byte(0xb1),
+                logs: Vec::new(),
+                deposits_hashes: vec![[0; 32]],
+                settled: HashMap::new(),
+                claimed: HashSet::new(),
+            }
+        }
+
+        /// Locks `amount` for `recipient`, in an L1 block of its own.
+        fn deposit(&mut self, recipient: Address, amount: Amount) {
+            let index = self.logs.len() as u64;
+            let deposit = Deposit {
+                index,
+                l1_block: 100 + index,
+                recipient,
+                amount,
+            };
+            let previous = self.deposits_hashes[self.logs.len()];
+            self.deposits_hashes
+                .push(chain_deposit(&previous, &deposit));
+            let event = L1Bridge::Deposited {
+                index,
+                recipient,
+                amount: U256::from(amount),
+            };
+            self.logs.push(Log {
+                inner: alloy::primitives::Log {
+                    address: self.bridge,
+                    data: event.encode_log_data(),
+                },
+                block_number: Some(deposit.l1_block),
+                ..Default::default()
+            });
+        }
+
+        /// Settles `batch` unless it applies deposits not made on L1.
+        fn settle(&mut self, batch: &BatchCommitment) -> bool {
+            if !self.deposits_hashes.contains(&batch.deposits_hash.0) {
+                return false;
+            }
+            self.settled
+                .insert(batch.block_number, batch.withdrawals_root.0);
+            true
+        }
+
+        /// Releases a withdrawal proven against a settled block, at most once.
+        fn claim(&mut self, claim: &WithdrawalClaim) -> bool {
+            self.settled
+                .get(&claim.block_number.into())
+                .is_some_and(|root| verify_claim(root, claim))
+                && self
+                    .claimed
+                    .insert(withdrawal_leaf(claim.sender, &claim.withdrawal))
+        }
+    }
+
+    fn build(
+        parent: &TokenDappHeader,
+        state: &mut TokenDappState,
+        candidates: &[Transaction],
+    ) -> TokenDappBlock {
+        let built = BlockBuilder::<TokenDappRollup>::new(BlockBuilderConfig::default())
+            .build(parent, state, candidates, None)
+            .unwrap();
+        assert!(built.rejected.is_empty());
+        built.block
+    }
+
+    /// Batch the sequencer commits to the rollup contract once `block` is published.
+    fn commit(block: &TokenDappBlock) -> BatchCommitment {
+        let receipt = BlobReceipt {
+            commitment: blob_commitment(&block.to_bytes()),
+            height: 1,
+        };
+        BatchCommitment::from_publication(block, &receipt)
+    }
+
+    fn withdraw(sender: &PrivateKeySigner, amount: Amount, nonce: u64) -> Transaction {
+        let withdrawal = Withdrawal {
+            amount,
+            l1_recipient: Address::repeat_byte(0x11),
+            nonce,
+        };
+        Transaction::Withdrawal(Signed::sign(withdrawal, sender).unwrap())
+    }
+
+    #[test]
+    fn test_deposit_and_withdraw() {
+        let (alice, bob) = (alice(), bob());
+        let mut l1 = MockL1::new();
+        l1.deposit(alice.address(), 100);
+        l1.deposit(bob.address(), 50);
+        let mut inbox = DepositInbox::new(l1.bridge);
+        assert_eq!(inbox.ingest(&l1.logs).unwrap(), 2);
+        assert_eq!(inbox.ingest(&l1.logs).unwrap(), 0);
+
+        let mut state = TokenDappState::default();
+        let genesis = TokenDappHeader::new(
+            0,
+            state.state_root(),
+            [0; 32],
+            merkle::empty_root::<KeccakHasher>(),
+        );
+        let mut candidates = inbox.transactions(state.next_deposit());
+        candidates.extend([
+            withdraw(&alice, 30, 0),
+            Transaction::Transfer(
+                Signed::sign(
+                    Transfer {
+                        amount: 10,
+                        destination: bob.address(),
+                        nonce: 1,
+                    },
+                    &alice,
+                )
+                .unwrap(),
+            ),
+            withdraw(&alice, 60, 2),
+        ]);
+        let block = build(&genesis, &mut state, &candidates);
+        assert_eq!(state.balance(&alice.address()), 0);
+        assert_eq!(state.balance(&bob.address()), 60);
+        assert_eq!(state.next_deposit(), 2);
+        inbox.prune(state.next_deposit());
+        assert!(inbox.transactions(state.next_deposit()).is_empty());
+
+        // Followers check the deposits against L1 and replay the block
+        let mut follower_inbox = DepositInbox::new(l1.bridge);
+        follower_inbox.ingest(&l1.logs).unwrap();
+        follower_inbox.validate(&block).unwrap();
+        let mut replica = TokenDappState::default();
+        TokenDappRollup::apply_block(&mut replica, &block).unwrap();
+        assert_eq!(replica.state_root(), state.state_root());
+
+        // Withdrawals are claimed once the block is settled, and only once
+        let first = withdrawal_claim(&block, 0).unwrap();
+        let second = withdrawal_claim(&block, 1).unwrap();
+        assert_eq!(second.sender, alice.address());
+        assert_eq!(second.withdrawal.amount, 60);
+        assert!(withdrawal_claim(&block, 2).is_none());
+        assert!(!l1.claim(&first));
+        assert!(l1.settle(&commit(&block)));
+        assert!(l1.claim(&first));
+        assert!(!l1.claim(&first));
+        let mut forged = second.clone();
+        forged.withdrawal.amount = 70;
+        assert!(!l1.claim(&forged));
+        forged = second.clone();
+        forged.sender = bob.address();
+        assert!(!l1.claim(&forged));
+        assert!(l1.claim(&second));
+    }
+
+    #[test]
+    fn test_rejects_invalid_bridge_transactions() {
+        let (alice, bob) = (alice(), bob());
+        let mut l1 = MockL1::new();
+        l1.deposit(alice.address(), 100);
+        l1.deposit(bob.address(), 50);
+        let mut inbox = DepositInbox::new(l1.bridge);
+        // Deposits are only applied once the preceding ones are
+        inbox.ingest(&l1.logs[1..]).unwrap();
+        assert!(inbox.transactions(0).is_empty());
+        inbox.ingest(&l1.logs[..1]).unwrap();
+        let deposits = inbox.transactions(0);
+        assert_eq!(deposits.len(), 2);
+
+        let mut state = TokenDappState::default();
+        let genesis = TokenDappHeader::new(
+            0,
+            state.state_root(),
+            [0; 32],
+            merkle::empty_root::<KeccakHasher>(),
+        );
+        let built = BlockBuilder::<TokenDappRollup>::new(BlockBuilderConfig::default())
+            .build(
+                &genesis,
+                &mut state,
+                &[
+                    deposits[1].clone(),
+                    deposits[0].clone(),
+                    withdraw(&alice, 101, 0),
+                    // Bob cannot withdraw the funds of Alice
+                    withdraw(&bob, 60, 0),
+                ],
+                None,
+            )
+            .unwrap();
+        assert!(matches!(
+            built.rejected[..],
+            [
+                (
+                    0,
+                    TokenDappRollupError::DepositOutOfOrder {
+                        expected: 0,
+                        got: 1
+                    }
+                ),
+                (2, TokenDappRollupError::InsufficientBalance(first)),
+                (3, TokenDappRollupError::InsufficientBalance(second)),
+            ] if first == alice.address() && second == bob.address()
+        ));
+
+        // The header must commit to the withdrawals of the block
+        let mut unsealed = built.block.clone();
+        unsealed.header.extension.withdrawals_root = None;
+        let mut replica = TokenDappState::default();
+        assert!(matches!(
+            TokenDappRollup::apply_block(&mut replica, &unsealed),
+            Err(TokenDappRollupError::WithdrawalsRootMismatch)
+        ));
+
+        // A withdrawal whose signature does not match its payload spends from another
+        // account
+        let Transaction::Withdrawal(mut tampered) = withdraw(&alice, 10, 0) else {
+            unreachable!()
+        };
+        tampered.payload.amount = 100;
+        assert_ne!(tampered.recover().ok(), Some(alice.address()));
+
+        // A deposit not made on L1 is detected by followers
+        let mut forged = built.block;
+        let Transaction::Deposit(deposit) = &mut forged.transactions[0] else {
+            unreachable!()
+        };
+        deposit.amount = 1_000;
+        let deposit = deposit.clone();
+        assert!(matches!(
+            inbox.validate(&forged),
+            Err(BridgeError::UnknownDeposit(0))
+        ));
+        // The header commits to the deposits it applies, which do not chain up to a deposits
+        // hash of L1
+        assert!(matches!(
+            TokenDappRollup::apply_block(&mut TokenDappState::default(), &forged),
+            Err(TokenDappRollupError::DepositsHashMismatch)
+        ));
+        forged.header.extension.deposits_hash = Some(B256::from(chain_deposit(&[0; 32], &deposit)));
+        assert!(!l1.settle(&commit(&forged)));
+        let mut log = l1.logs[0].clone();
+        log.inner.data = L1Bridge::Deposited {
+            index: 0,
+            recipient: alice.address(),
+            amount: U256::from(1),
+        }
+        .encode_log_data();
+        assert!(matches!(
+            inbox.ingest(&[log]),
+            Err(BridgeError::ConflictingDeposit(0))
+        ));
+    }
+}
@@ -1,6 +1,60 @@
+use crate::types::Nonce;
+use alloy::primitives::{Address, SignatureError};
+use anunaya_rollup_core::metering::OutOfGas;
+
 #[derive(Debug, thiserror::Error)]
 pub enum TokenDappRollupError {
     // Insufficient Shares
     #[error("{}", _0)]
     Generic(String),
+    // Transaction exceeds the gas left in the block
+    #[error(transparent)]
+    OutOfGas(#[from] OutOfGas),
+    // Transaction signature does not recover a sender
+    #[error(transparent)]
+    InvalidSignature(#[from] SignatureError),
+    // Sender balance does not cover the amount
+    #[error("Insufficient balance of {}", _0)]
+    InsufficientBalance(Address),
+    // Recipient balance would overflow
+    #[error("Balance overflow of {}", _0)]
+    BalanceOverflow(Address),
+    // Transaction does not use the next nonce of its sender
+    #[error("Invalid nonce {} of {}, expected {}", got, sender, expected)]
+    InvalidNonce {
+        sender: Address,
+        expected: Nonce,
+        got: Nonce,
+    },
+    // Deposits must be applied once each, in the order they were made on L1
+    #[error("Deposit {} applied out of order, expected {}", got, expected)]
+    DepositOutOfOrder { expected: u64, got: u64 },
+    // Header does not commit to the withdrawals of the block
+    #[error("Withdrawals root mismatch")]
+    WithdrawalsRootMismatch,
+    // Header does not commit to the deposits applied up to the block
+    #[error("Deposits hash mismatch")]
+    DepositsHashMismatch,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum BridgeError {
+    // Log is not a deposit of the L1 bridge
+    #[error(transparent)]
+    InvalidLog(#[from] alloy::sol_types::Error),
+    // L1 node request failed
+    #[error(transparent)]
+    Transport(#[from] alloy::transports::TransportError),
+    // Log was not included in a block
+    #[error("Deposit log is pending")]
+    PendingLog,
+    // Deposit amount does not fit the rollup balances
+    #[error("Deposit {} amount overflows", _0)]
+    AmountOverflow(u64),
+    // Another deposit was seen at the same index
+    #[error("Conflicting deposits at index {}", _0)]
+    ConflictingDeposit(u64),
+    // Block applies a deposit that was not made on L1
+    #[error("Unknown deposit {}", _0)]
+    UnknownDeposit(u64),
 }
@@ -0,0 +1,6 @@
+//! Example token rollup built on the rollup core: signed transfers between accounts, with
+//! deposits from and withdrawals to L1 through a bridge.
+pub mod bridge;
+pub mod errors;
+pub mod state;
+pub mod types;
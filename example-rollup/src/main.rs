@@ -1,5 +1 @@
-mod errors;
-mod state;
-mod types;
-
 fn main() {}
@@ -1,20 +1,108 @@
+use crate::bridge::{chain_deposit, withdrawals_root};
 use crate::errors::TokenDappRollupError;
 use crate::types::*;
-use alloy::primitives::Address;
+use alloy::primitives::{Address, B256};
 use anunaya_rollup_core::block::*;
+use anunaya_rollup_core::codec::Encode;
 use anunaya_rollup_core::hasher::KeccakHasher;
+use anunaya_rollup_core::journal::{Checkpoint, Journaled};
+use anunaya_rollup_core::metering::{Gas, GasMeter};
+use anunaya_rollup_core::receipt::Receipt;
 use anunaya_rollup_core::traits::*;
 use serde::{Deserialize, Serialize};
 
 use std::collections::BTreeMap;
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+
+/// Gas charged to every transaction.
+pub const TRANSACTION_GAS: Gas = 1;
+
+pub type TokenDappHeader = BlockHeader<BlockNumber, KeccakHasher>;
+pub type TokenDappBlock = Block<TokenDappHeader, Transaction>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenDappState {
-    accounts: BTreeMap<Address, Account>,
-    state_root: Hash,
+    accounts: Journaled<BTreeMap<Address, Account>>,
+    deposits: u64,                 // Index of the next deposit to apply
+    deposits_hash: Hash,           // Hash chain of the applied deposits
+    state_root: Hash,              // Hash of the accounts and deposit index
     prev_state_root: Option<Hash>, // Previous state root
     block_hash: Option<Hash>,      // Hash of most recent block
 }
 
+impl Default for TokenDappState {
+    fn default() -> Self {
+        let mut state = Self {
+            accounts: Journaled::default(),
+            deposits: 0,
+            deposits_hash: [0; 32],
+            state_root: [0; 32],
+            prev_state_root: None,
+            block_hash: None,
+        };
+        state.update_state_root();
+        state
+    }
+}
+
+impl TokenDappState {
+    pub fn account(&self, address: &Address) -> Option<&Account> {
+        self.accounts.get(address)
+    }
+
+    pub fn balance(&self, address: &Address) -> Amount {
+        self.account(address).map_or(0, Account::balance)
+    }
+
+    /// Index of the next L1 deposit to apply.
+    pub fn next_deposit(&self) -> u64 {
+        self.deposits
+    }
+
+    fn update_state_root(&mut self) {
+        let mut encoded = Vec::new();
+        for (address, account) in self.accounts.iter() {
+            address.encode_to(&mut encoded);
+            account.encode_to(&mut encoded);
+        }
+        self.deposits.encode_to(&mut encoded);
+        self.deposits_hash.encode_to(&mut encoded);
+        self.state_root = KeccakHasher::hash(&encoded);
+    }
+
+    /// Increments the nonce of `sender` if `nonce` is the next one and its balance covers
+    /// `amount`, then debits it.
+    fn spend(
+        &mut self,
+        sender: Address,
+        nonce: Nonce,
+        amount: Amount,
+    ) -> Result<(), TokenDappRollupError> {
+        let mut account = self.account(&sender).cloned().unwrap_or_default();
+        if nonce != account.nonce() {
+            return Err(TokenDappRollupError::InvalidNonce {
+                sender,
+                expected: account.nonce(),
+                got: nonce,
+            });
+        }
+        account
+            .debit(amount)
+            .ok_or(TokenDappRollupError::InsufficientBalance(sender))?;
+        account.increment_nonce();
+        self.accounts.write(sender, Some(account));
+        Ok(())
+    }
+
+    fn credit(&mut self, recipient: Address, amount: Amount) -> Result<(), TokenDappRollupError> {
+        let mut account = self.account(&recipient).cloned().unwrap_or_default();
+        account
+            .credit(amount)
+            .ok_or(TokenDappRollupError::BalanceOverflow(recipient))?;
+        self.accounts.write(recipient, Some(account));
+        Ok(())
+    }
+}
+
 impl AppState for TokenDappState {
     fn state_root(&self) -> [u8; 32] {
         self.state_root
@@ -25,20 +113,116 @@ impl AppState for TokenDappState {
     }
 }
 
+/// Checkpoint of the journaled accounts, with the other fields of the state as they were.
+#[derive(Debug)]
+pub struct TokenDappCheckpoint {
+    accounts: Checkpoint,
+    deposits: u64,
+    deposits_hash: Hash,
+    state_root: Hash,
+    prev_state_root: Option<Hash>,
+}
+
+impl RevertibleState for TokenDappState {
+    type Checkpoint = TokenDappCheckpoint;
+
+    fn checkpoint(&mut self) -> Self::Checkpoint {
+        TokenDappCheckpoint {
+            accounts: self.accounts.checkpoint(),
+            deposits: self.deposits,
+            deposits_hash: self.deposits_hash,
+            state_root: self.state_root,
+            prev_state_root: self.prev_state_root,
+        }
+    }
+
+    fn revert(&mut self, checkpoint: Self::Checkpoint) {
+        self.accounts.revert(checkpoint.accounts);
+        self.deposits = checkpoint.deposits;
+        self.deposits_hash = checkpoint.deposits_hash;
+        self.state_root = checkpoint.state_root;
+        self.prev_state_root = checkpoint.prev_state_root;
+    }
+
+    fn commit(&mut self, checkpoint: Self::Checkpoint) {
+        self.accounts.commit(checkpoint.accounts);
+    }
+}
+
 pub struct TokenDappRollup;
 
 impl StateTransitionFunction for TokenDappRollup {
     type State = TokenDappState;
 
     type Error = TokenDappRollupError;
-    type BlockHeader = BlockHeader<BlockNumber, KeccakHasher>;
-    type Block = Block<Self::BlockHeader, Transaction>;
+    type BlockHeader = TokenDappHeader;
+    type Block = TokenDappBlock;
 
     fn validate_block(state: &Self::State, block: &Self::Block) -> Result<(), Self::Error> {
-        todo!()
+        if block.header.extension.withdrawals_root != Self::withdrawals_root(block.transactions()) {
+            return Err(TokenDappRollupError::WithdrawalsRootMismatch);
+        }
+        // The deposits are those of the L1 chain the header commits to
+        let deposits_hash =
+            block
+                .transactions()
+                .iter()
+                .fold(state.deposits_hash, |hash, transaction| match transaction {
+                    Transaction::Deposit(deposit) => chain_deposit(&hash, deposit),
+                    _ => hash,
+                });
+        if block.header.extension.deposits_hash != Some(B256::from(deposits_hash)) {
+            return Err(TokenDappRollupError::DepositsHashMismatch);
+        }
+        Ok(())
+    }
+
+    fn withdrawals_root(transactions: &[Transaction]) -> Option<B256> {
+        Some(B256::from(withdrawals_root(transactions)))
+    }
+
+    fn deposits_hash(state: &Self::State) -> Option<B256> {
+        Some(B256::from(state.deposits_hash))
+    }
+
+    fn begin_block(
+        state: &mut Self::State,
+        _header: &Self::BlockHeader,
+    ) -> Result<(), Self::Error> {
+        state.prev_state_root = Some(state.state_root);
+        Ok(())
     }
 
-    fn apply_block(state: &mut Self::State, block: &Self::Block) -> Result<(), Self::Error> {
-        todo!()
+    fn apply_transaction(
+        state: &mut Self::State,
+        transaction: &Transaction,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, Self::Error> {
+        meter.consume(TRANSACTION_GAS)?;
+        match transaction {
+            Transaction::Transfer(signed) => {
+                let transfer = &signed.payload;
+                state.spend(signed.recover()?, transfer.nonce, transfer.amount)?;
+                state.credit(transfer.destination, transfer.amount)?;
+            }
+            Transaction::Deposit(deposit) => {
+                if deposit.index != state.deposits {
+                    return Err(TokenDappRollupError::DepositOutOfOrder {
+                        expected: state.deposits,
+                        got: deposit.index,
+                    });
+                }
+                state.credit(deposit.recipient, deposit.amount)?;
+                state.deposits += 1;
+                state.deposits_hash = chain_deposit(&state.deposits_hash, deposit);
+            }
+            // The withdrawn amount is burnt, and released on L1 once the block is settled
+            Transaction::Withdrawal(signed) => {
+                let withdrawal = &signed.payload;
+                state.spend(signed.recover()?, withdrawal.nonce, withdrawal.amount)?;
+            }
+        }
+        state.update_state_root();
+        Ok(Receipt::success(TRANSACTION_GAS))
     }
 }
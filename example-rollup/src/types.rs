@@ -1,4 +1,6 @@
-use alloy::primitives::Address;
+use alloy::primitives::{Address, Signature, SignatureError};
+use alloy::signers::SignerSync;
+use anunaya_rollup_core::codec::{CodecError, Decode, Encode};
 use anunaya_rollup_core::traits::SignedTransactionT;
 use serde::{Deserialize, Serialize};
 
@@ -7,17 +9,233 @@ pub type Amount = u64;
 pub type Nonce = u64;
 pub type BlockNumber = u32;
 
-#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Account {
     balance: Amount,
     nonce: Nonce,
 }
 
-#[derive(Clone, Serialize, Deserialize, Debug)]
-pub struct Transaction {
+impl Account {
+    pub fn balance(&self) -> Amount {
+        self.balance
+    }
+
+    pub fn nonce(&self) -> Nonce {
+        self.nonce
+    }
+
+    pub fn credit(&mut self, amount: Amount) -> Option<()> {
+        self.balance = self.balance.checked_add(amount)?;
+        Some(())
+    }
+
+    pub fn debit(&mut self, amount: Amount) -> Option<()> {
+        self.balance = self.balance.checked_sub(amount)?;
+        Some(())
+    }
+
+    pub fn increment_nonce(&mut self) {
+        self.nonce += 1;
+    }
+}
+
+impl Encode for Account {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.balance.encode_to(out);
+        self.nonce.encode_to(out);
+    }
+}
+
+impl Decode for Account {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            balance: Amount::decode_from(input)?,
+            nonce: Nonce::decode_from(input)?,
+        })
+    }
+}
+
+/// Transfer from the rollup account signing it to `destination`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Transfer {
     pub amount: Amount,
     pub destination: Address,
     pub nonce: Nonce,
 }
 
+impl Encode for Transfer {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.amount.encode_to(out);
+        self.destination.encode_to(out);
+        self.nonce.encode_to(out);
+    }
+}
+
+impl Decode for Transfer {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            amount: Amount::decode_from(input)?,
+            destination: Address::decode_from(input)?,
+            nonce: Nonce::decode_from(input)?,
+        })
+    }
+}
+
+/// Deposit made on L1, credited to `recipient` on the rollup.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Deposit {
+    /// Position of the deposit among all deposits of the L1 bridge
+    pub index: u64,
+    /// L1 block the deposit was made in
+    pub l1_block: u64,
+    pub recipient: Address,
+    pub amount: Amount,
+}
+
+impl Encode for Deposit {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.index.encode_to(out);
+        self.l1_block.encode_to(out);
+        self.recipient.encode_to(out);
+        self.amount.encode_to(out);
+    }
+}
+
+impl Decode for Deposit {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            index: u64::decode_from(input)?,
+            l1_block: u64::decode_from(input)?,
+            recipient: Address::decode_from(input)?,
+            amount: Amount::decode_from(input)?,
+        })
+    }
+}
+
+/// Exit of `amount` from the rollup account signing it to `l1_recipient`.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Withdrawal {
+    pub amount: Amount,
+    pub l1_recipient: Address,
+    pub nonce: Nonce,
+}
+
+impl Encode for Withdrawal {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.amount.encode_to(out);
+        self.l1_recipient.encode_to(out);
+        self.nonce.encode_to(out);
+    }
+}
+
+impl Decode for Withdrawal {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            amount: Amount::decode_from(input)?,
+            l1_recipient: Address::decode_from(input)?,
+            nonce: Nonce::decode_from(input)?,
+        })
+    }
+}
+
+/// Transaction sent by a rollup account.
+pub trait Payload: Encode {
+    /// Tag of the transaction in the [`Transaction`] encoding
+    const TAG: u8;
+}
+
+impl Payload for Transfer {
+    const TAG: u8 = 0;
+}
+
+impl Payload for Withdrawal {
+    const TAG: u8 = 2;
+}
+
+/// Transaction signed by the account sending it.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub struct Signed<T> {
+    pub payload: T,
+    pub signature: Signature,
+}
+
+impl<T: Payload> Signed<T> {
+    /// Signs `payload` with the key of its sender.
+    pub fn sign(payload: T, signer: &impl SignerSync) -> alloy::signers::Result<Self> {
+        let signature = signer.sign_message_sync(&Self::message(&payload))?;
+        Ok(Self { payload, signature })
+    }
+
+    /// Returns the signed message: the payload preceded by its tag, so that a signature is
+    /// only valid for one kind of transaction.
+    pub fn message(payload: &T) -> Vec<u8> {
+        let mut message = vec![T::TAG];
+        payload.encode_to(&mut message);
+        message
+    }
+
+    /// Recovers the address of the sender.
+    pub fn recover(&self) -> Result<Address, SignatureError> {
+        self.signature
+            .recover_address_from_msg(Self::message(&self.payload))
+    }
+}
+
+impl<T: Encode> Encode for Signed<T> {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.payload.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl<T: Decode> Decode for Signed<T> {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            payload: T::decode_from(input)?,
+            signature: Signature::decode_from(input)?,
+        })
+    }
+}
+
+/// Transaction of the token rollup. Deposits are system transactions derived from L1, which
+/// no rollup account sends or signs.
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Eq)]
+pub enum Transaction {
+    Transfer(Signed<Transfer>),
+    Deposit(Deposit),
+    Withdrawal(Signed<Withdrawal>),
+}
+
+impl Encode for Transaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Transfer(transfer) => {
+                Transfer::TAG.encode_to(out);
+                transfer.encode_to(out);
+            }
+            Self::Deposit(deposit) => {
+                1u8.encode_to(out);
+                deposit.encode_to(out);
+            }
+            Self::Withdrawal(withdrawal) => {
+                Withdrawal::TAG.encode_to(out);
+                withdrawal.encode_to(out);
+            }
+        }
+    }
+}
+
+impl Decode for Transaction {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        match u8::decode_from(input)? {
+            Transfer::TAG => Ok(Self::Transfer(Signed::decode_from(input)?)),
+            1 => Ok(Self::Deposit(Deposit::decode_from(input)?)),
+            Withdrawal::TAG => Ok(Self::Withdrawal(Signed::decode_from(input)?)),
+            v => Err(CodecError::InvalidValue(format!(
+                "Invalid transaction tag {v}"
+            ))),
+        }
+    }
+}
+
 impl SignedTransactionT for Transaction {}
@@ -0,0 +1,64 @@
+//! HTTP client of the sequencer API.
+use crate::error::Result;
+use alloy::primitives::Address;
+use anunaya_rollup_core::signed_header::SignedHeader;
+use anunaya_rollup_core::smt::StateProof;
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
+
+#[derive(Debug, Clone)]
+pub struct SequencerClient {
+    client: reqwest::Client,
+    // Base URL of the API, e.g. `http://localhost:3033/api/v1`
+    url: String,
+}
+
+impl SequencerClient {
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: url.into().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// Fetches the signed header of block `number`.
+    pub async fn signed_header<H: DeserializeOwned>(
+        &self,
+        number: u64,
+    ) -> Result<Option<SignedHeader<H>>> {
+        self.get(&format!("headers/{number}")).await
+    }
+
+    /// Fetches the signed header of the latest block.
+    pub async fn latest_signed_header<H: DeserializeOwned>(
+        &self,
+    ) -> Result<Option<SignedHeader<H>>> {
+        self.get("headers/latest").await
+    }
+
+    /// Fetches the account of `address` in the latest state, with its proof.
+    pub async fn account_proof(&self, address: &Address) -> Result<StateProof<[u8; 32]>> {
+        let url = format!("{}/state/accounts/{address}", self.url);
+        Ok(self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+
+    // Fetches `path`, mapping a missing resource to `None`.
+    async fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>> {
+        let response = self
+            .client
+            .get(format!("{}/{path}", self.url))
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(response.error_for_status()?.json().await?))
+    }
+}
@@ -0,0 +1,32 @@
+use anunaya_rollup_core::signed_header::SignedHeaderError;
+use anunaya_rollup_core::validation::ValidationError;
+
+pub type Result<T> = std::result::Result<T, LightClientError>;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LightClientError {
+    /// Request to the sequencer failed
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// Header does not extend the verified chain
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+    /// Header is not signed by an allowed proposer
+    #[error(transparent)]
+    SignedHeader(#[from] SignedHeaderError),
+    /// Header served for the checkpoint does not hash to the trusted hash
+    #[error("Header #{} does not match the trusted checkpoint", _0)]
+    CheckpointMismatch(u64),
+    /// Sequencer does not serve the requested header
+    #[error("Header #{} not found", _0)]
+    HeaderNotFound(u64),
+    /// Proof refers to a block that is not part of the verified chain
+    #[error("Block #{} is not part of the verified chain", _0)]
+    UnknownBlock(u64),
+    /// Proof is not for the requested key
+    #[error("Proof is for another key")]
+    KeyMismatch,
+    /// Proof does not verify against the state root
+    #[error("Invalid state proof for block #{}", _0)]
+    InvalidProof(u64),
+}
@@ -0,0 +1,66 @@
+//! Chain of verified headers rooted at a trusted checkpoint.
+use crate::error::{LightClientError, Result};
+use anunaya_rollup_core::signed_header::{ProposerSet, SignedHeader};
+use anunaya_rollup_core::traits::BlockHeaderT;
+use anunaya_rollup_core::validation::validate_header;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Block trusted out of band, from which the chain is followed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint<Hash> {
+    pub number: u64,
+    pub hash: Hash,
+}
+
+/// Headers verified from a checkpoint, each extending the previous one and signed by an
+/// allowed proposer.
+#[derive(Clone, Debug)]
+pub struct HeaderChain<H: BlockHeaderT> {
+    proposers: ProposerSet,
+    headers: BTreeMap<u64, H>,
+}
+
+impl<H: BlockHeaderT> HeaderChain<H> {
+    /// Starts the chain at `header`, which must be the header of `checkpoint`.
+    pub fn new(
+        checkpoint: &Checkpoint<H::Hash>,
+        header: H,
+        proposers: ProposerSet,
+    ) -> Result<Self> {
+        if (*header.number()).into() != checkpoint.number || header.hash() != checkpoint.hash {
+            return Err(LightClientError::CheckpointMismatch(checkpoint.number));
+        }
+        Ok(Self {
+            proposers,
+            headers: BTreeMap::from([(checkpoint.number, header)]),
+        })
+    }
+
+    /// Returns the latest verified header.
+    pub fn head(&self) -> &H {
+        self.headers
+            .last_key_value()
+            .map(|(_, header)| header)
+            .expect("chain starts at the checkpoint")
+    }
+
+    /// Returns the verified header of block `number`.
+    pub fn header(&self, number: u64) -> Option<&H> {
+        self.headers.get(&number)
+    }
+
+    pub fn proposers(&self) -> &ProposerSet {
+        &self.proposers
+    }
+
+    /// Appends `signed` after verifying that it extends the head and is signed by an allowed
+    /// proposer.
+    pub fn append(&mut self, signed: SignedHeader<H>) -> Result<()> {
+        validate_header(self.head(), &signed.header)?;
+        signed.verify(&self.proposers)?;
+        let number = (*signed.header.number()).into();
+        self.headers.insert(number, signed.header);
+        Ok(())
+    }
+}
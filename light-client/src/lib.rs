@@ -0,0 +1,14 @@
+//! Light client following the header chain of a sequencer from a trusted checkpoint.
+//!
+//! Headers are only accepted when they extend the verified chain and are signed by an
+//! allowed proposer. State read from the sequencer is verified against the state root of a
+//! verified header.
+pub mod client;
+pub mod error;
+pub mod header_chain;
+pub mod light_client;
+
+pub use client::SequencerClient;
+pub use error::LightClientError;
+pub use header_chain::{Checkpoint, HeaderChain};
+pub use light_client::LightClient;
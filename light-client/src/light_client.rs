@@ -0,0 +1,230 @@
+//! Light client syncing headers and verifying state from the sequencer API.
+use crate::client::SequencerClient;
+use crate::error::{LightClientError, Result};
+use crate::header_chain::{Checkpoint, HeaderChain};
+use alloy::primitives::Address;
+use anunaya_rollup_core::block::BlockHeader;
+use anunaya_rollup_core::signed_header::ProposerSet;
+use anunaya_rollup_core::smt::{StateProof, hashed_key};
+use anunaya_rollup_core::traits::HasherT;
+use serde::de::DeserializeOwned;
+use std::fmt::Debug;
+
+pub type LightClientHeader<H> = BlockHeader<u64, H>;
+
+pub struct LightClient<H: HasherT<Output = [u8; 32]> + Debug + Clone + 'static> {
+    client: SequencerClient,
+    chain: HeaderChain<LightClientHeader<H>>,
+}
+
+impl<H> LightClient<H>
+where
+    H: HasherT<Output = [u8; 32]> + Debug + Clone + DeserializeOwned + 'static,
+{
+    /// Fetches the header of `checkpoint` from the sequencer and starts following the chain
+    /// from it.
+    pub async fn new(
+        client: SequencerClient,
+        checkpoint: Checkpoint<[u8; 32]>,
+        proposers: ProposerSet,
+    ) -> Result<Self> {
+        let signed = client
+            .signed_header(checkpoint.number)
+            .await?
+            .ok_or(LightClientError::HeaderNotFound(checkpoint.number))?;
+        let chain = HeaderChain::new(&checkpoint, signed.header, proposers)?;
+        Ok(Self { client, chain })
+    }
+
+    pub fn chain(&self) -> &HeaderChain<LightClientHeader<H>> {
+        &self.chain
+    }
+
+    /// Verifies every header up to the latest block of the sequencer. Returns the number of
+    /// the new head.
+    pub async fn sync(&mut self) -> Result<u64> {
+        let Some(latest) = self
+            .client
+            .latest_signed_header::<LightClientHeader<H>>()
+            .await?
+        else {
+            return Ok(self.chain.head().number);
+        };
+        for number in self.chain.head().number + 1..latest.header.number {
+            let signed = self
+                .client
+                .signed_header(number)
+                .await?
+                .ok_or(LightClientError::HeaderNotFound(number))?;
+            self.chain.append(signed)?;
+        }
+        if latest.header.number > self.chain.head().number {
+            self.chain.append(latest)?;
+        }
+        Ok(self.chain.head().number)
+    }
+
+    /// Verifies `proof` against the state root of a verified header.
+    pub fn verify_state(&self, proof: &StateProof<[u8; 32]>) -> Result<()> {
+        let header = self
+            .chain
+            .header(proof.block_number)
+            .ok_or(LightClientError::UnknownBlock(proof.block_number))?;
+        if !proof.verify::<H>(&header.state_root) {
+            return Err(LightClientError::InvalidProof(proof.block_number));
+        }
+        Ok(())
+    }
+
+    /// Fetches and verifies the account of `address` in the latest state, syncing the header
+    /// the proof refers to if needed. Returns the account value, `None` if it does not exist.
+    pub async fn verify_account(&mut self, address: &Address) -> Result<Option<Vec<u8>>> {
+        let proof = self.client.account_proof(address).await?;
+        if proof.key != hashed_key::<H>(address.as_slice()) {
+            return Err(LightClientError::KeyMismatch);
+        }
+        if proof.block_number > self.chain.head().number {
+            self.sync().await?;
+        }
+        self.verify_state(&proof)?;
+        Ok(proof.value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::signers::Signer;
+    use alloy::signers::local::PrivateKeySigner;
+    use anunaya_rollup_core::hasher::KeccakHasher;
+    use anunaya_rollup_core::traits::BlockHeaderT;
+    use sequencer::routes::api_router;
+    use sequencer::sequencer::{SequencerConfig, SequencerContext, SequencerRpcMethods};
+    use sequencer::store::TransactionStore;
+    use sequencer::transaction::{SignedTransaction, Transaction};
+
+    fn sequencer() -> SequencerContext<KeccakHasher> {
+        SequencerContext::new(SequencerConfig::default(), TransactionStore::new(10)).unwrap()
+    }
+
+    // Serves the API of `ctx` on a local port.
+    async fn serve(ctx: SequencerContext<KeccakHasher>) -> SequencerClient {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, api_router(ctx)).await.unwrap() });
+        SequencerClient::new(format!("http://{addr}/api/v1"))
+    }
+
+    fn genesis(ctx: &SequencerContext<KeccakHasher>) -> Checkpoint<[u8; 32]> {
+        let header = ctx.blocks.block_by_number(0).unwrap().unwrap().header;
+        Checkpoint {
+            number: 0,
+            hash: header.hash(),
+        }
+    }
+
+    async fn send(ctx: &SequencerContext<KeccakHasher>, signer: &PrivateKeySigner, nonce: u64) {
+        let transaction = Transaction {
+            amount: 1,
+            destination: signer.address(),
+            nonce,
+        };
+        let signature = signer.sign_message(&transaction.encode()).await.unwrap();
+        let tx = SignedTransaction {
+            transaction,
+            signature,
+        };
+        ctx.accept_tx(tx.encode()).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_sync_and_verify_accounts() {
+        let ctx = sequencer();
+        let proposers = ProposerSet::new([ctx.config.signer.address()]);
+        let client = serve(ctx.clone()).await;
+        let alice = PrivateKeySigner::random();
+        for nonce in 0..3 {
+            send(&ctx, &alice, nonce).await;
+            ctx.publish_batch().unwrap();
+        }
+
+        let mut light_client = LightClient::<KeccakHasher>::new(client, genesis(&ctx), proposers)
+            .await
+            .unwrap();
+        assert_eq!(light_client.sync().await.unwrap(), 3);
+        let head = ctx.chain.lock().unwrap().head.hash();
+        assert_eq!(light_client.chain().head().hash(), head);
+
+        let nonce = light_client.verify_account(&alice.address()).await.unwrap();
+        assert_eq!(nonce, Some(2u64.to_le_bytes().to_vec()));
+        let unknown = PrivateKeySigner::random().address();
+        assert_eq!(light_client.verify_account(&unknown).await.unwrap(), None);
+
+        // Proofs of blocks produced after the last sync pull in their headers
+        send(&ctx, &alice, 3).await;
+        ctx.publish_batch().unwrap();
+        let nonce = light_client.verify_account(&alice.address()).await.unwrap();
+        assert_eq!(nonce, Some(3u64.to_le_bytes().to_vec()));
+        assert_eq!(light_client.chain().head().number, 4);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_invalid_state_proofs() {
+        let ctx = sequencer();
+        let proposers = ProposerSet::new([ctx.config.signer.address()]);
+        let client = serve(ctx.clone()).await;
+        let alice = PrivateKeySigner::random();
+        send(&ctx, &alice, 0).await;
+        ctx.publish_batch().unwrap();
+
+        let mut light_client =
+            LightClient::<KeccakHasher>::new(client.clone(), genesis(&ctx), proposers)
+                .await
+                .unwrap();
+        light_client.sync().await.unwrap();
+
+        let mut proof = client.account_proof(&alice.address()).await.unwrap();
+        assert!(light_client.verify_state(&proof).is_ok());
+        proof.value = Some(7u64.to_le_bytes().to_vec());
+        assert!(matches!(
+            light_client.verify_state(&proof),
+            Err(LightClientError::InvalidProof(1))
+        ));
+        proof.block_number = 5;
+        assert!(matches!(
+            light_client.verify_state(&proof),
+            Err(LightClientError::UnknownBlock(5))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unknown_proposer() {
+        let ctx = sequencer();
+        let client = serve(ctx.clone()).await;
+        ctx.publish_batch().unwrap();
+
+        let proposers = ProposerSet::new([PrivateKeySigner::random().address()]);
+        let mut light_client = LightClient::<KeccakHasher>::new(client, genesis(&ctx), proposers)
+            .await
+            .unwrap();
+        assert!(matches!(
+            light_client.sync().await,
+            Err(LightClientError::SignedHeader(_))
+        ));
+        assert_eq!(light_client.chain().head().number, 0);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_wrong_checkpoint() {
+        let ctx = sequencer();
+        let client = serve(ctx.clone()).await;
+        let checkpoint = Checkpoint {
+            number: 0,
+            hash: [1; 32],
+        };
+        assert!(matches!(
+            LightClient::<KeccakHasher>::new(client, checkpoint, ProposerSet::default()).await,
+            Err(LightClientError::CheckpointMismatch(0))
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+//! Compiles the rollup contract with `solc`, taken from `SOLC` or found on `PATH`.
+//!
+//! Without a compiler the crate still builds, but cannot deploy the contract.
+use std::path::PathBuf;
+use std::process::Command;
+use std::{env, fs};
+
+const SOURCE: &str = "contracts/Rollup.sol";
+
+fn main() {
+    println!("cargo:rerun-if-changed={SOURCE}");
+    println!("cargo:rerun-if-env-changed=SOLC");
+
+    let out = PathBuf::from(env::var_os("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    let solc = env::var("SOLC").unwrap_or_else(|_| "solc".to_string());
+    let bytecode = match Command::new(&solc)
+        .args(["--optimize", "--bin", "--overwrite", "-o"])
+        .arg(&out)
+        .arg(SOURCE)
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            fs::read_to_string(out.join("Rollup.bin")).expect("solc wrote Rollup.bin")
+        }
+        Ok(output) => panic!(
+            "{solc} failed to compile {SOURCE}:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(err) => {
+            println!(
+                "cargo:warning=could not run {solc} ({err}), the rollup contract cannot be deployed"
+            );
+            String::new()
+        }
+    };
+    fs::write(out.join("Rollup.bin"), bytecode).expect("OUT_DIR is writable");
+}
@@ -0,0 +1,61 @@
+// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.24;
+
+/// Rollup contract the sequencer settles its batches on.
+///
+/// Keeps the latest committed batch and only accepts commitments from the account that
+/// deployed it, for consecutive block numbers. The withdrawals root of every batch is kept
+/// for the withdrawals to be proven against.
+contract Rollup {
+    /// Emitted for every accepted batch
+    event BatchCommitted(
+        uint64 indexed blockNumber,
+        bytes32 stateRoot,
+        bytes32 daCommitment,
+        bytes32 withdrawalsRoot,
+        bytes32 depositsHash
+    );
+
+    /// Caller is not the sequencer
+    error Unauthorized();
+    /// Batch is not the one following the latest committed one
+    error UnexpectedBatch(uint64 latest, uint64 blockNumber);
+
+    address public immutable sequencer = msg.sender;
+
+    /// Withdrawals root of every committed batch, by block number
+    mapping(uint64 => bytes32) public withdrawalsRoots;
+
+    uint64 private _blockNumber;
+    bytes32 private _stateRoot;
+    bytes32 private _daCommitment;
+    bytes32 private _depositsHash;
+
+    function latestBatch()
+        external
+        view
+        returns (
+            uint64 blockNumber,
+            bytes32 stateRoot,
+            bytes32 daCommitment,
+            bytes32 withdrawalsRoot,
+            bytes32 depositsHash
+        )
+    {
+        return (_blockNumber, _stateRoot, _daCommitment, withdrawalsRoots[_blockNumber], _depositsHash);
+    }
+
+    function commitBatch(
+        uint64 blockNumber,
+        bytes32 stateRoot,
+        bytes32 daCommitment,
+        bytes32 withdrawalsRoot,
+        bytes32 depositsHash
+    ) external {
+        if (msg.sender != sequencer) revert Unauthorized();
+        if (blockNumber != _blockNumber + 1) revert UnexpectedBatch(_blockNumber, blockNumber);
+        (_blockNumber, _stateRoot, _daCommitment, _depositsHash) = (blockNumber, stateRoot, daCommitment, depositsHash);
+        withdrawalsRoots[blockNumber] = withdrawalsRoot;
+        emit BatchCommitted(blockNumber, stateRoot, daCommitment, withdrawalsRoot, depositsHash);
+    }
+}
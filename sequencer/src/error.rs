@@ -1,4 +1,17 @@
-use alloy::primitives::SignatureError;
+use crate::settlement::contract::Rollup::RollupErrors;
+use alloy::primitives::{Address, SignatureError, TxHash};
+use alloy::providers::PendingTransactionError;
+use alloy::transports::TransportError;
+use anunaya_rollup_core::block_store::BlockStoreError;
+use anunaya_rollup_core::builder::BlockBuilderError;
+use anunaya_rollup_core::codec::CodecError;
+use anunaya_rollup_core::data_availability::DataAvailabilityError;
+use anunaya_rollup_core::forced_inclusion::ForcedInclusionError;
+use anunaya_rollup_core::genesis::GenesisError;
+use anunaya_rollup_core::metering::OutOfGas;
+use anunaya_rollup_core::signed_header::SignedHeaderError;
+use anunaya_rollup_core::snapshot::SnapshotError;
+use anunaya_rollup_core::validation::ValidationError;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 
@@ -18,6 +31,90 @@ pub enum SequencerError {
     /// Transaction store error
     #[error(transparent)]
     TxStoreError(#[from] TxStoreError),
+    /// Decoding error
+    #[error(transparent)]
+    CodecError(#[from] CodecError),
+    /// Block production error
+    #[error(transparent)]
+    BlockBuilderError(#[from] BlockBuilderError<StateError>),
+    /// Block storage error
+    #[error(transparent)]
+    BlockStoreError(#[from] BlockStoreError),
+    /// Block data publication error
+    #[error(transparent)]
+    DataAvailabilityError(#[from] DataAvailabilityError),
+    /// State transition error
+    #[error(transparent)]
+    StateError(#[from] StateError),
+    /// Block does not extend its parent
+    #[error(transparent)]
+    ValidationError(#[from] ValidationError),
+    /// Header signing error
+    #[error(transparent)]
+    SignedHeaderError(#[from] SignedHeaderError),
+    /// Genesis specification error
+    #[error(transparent)]
+    GenesisError(#[from] GenesisError),
+    /// Stored chain was started from another genesis
+    #[error(
+        "Stored genesis block 0x{} differs from the genesis block 0x{}",
+        hex::encode(.stored),
+        hex::encode(.expected)
+    )]
+    GenesisMismatch {
+        stored: [u8; 32],
+        expected: [u8; 32],
+    },
+    /// State snapshot error
+    #[error(transparent)]
+    SnapshotError(#[from] SnapshotError),
+    /// Snapshot was not taken at a block of the stored chain, or the one before it
+    #[error("Snapshot of block #{} is not part of the stored chain", _0)]
+    SnapshotMismatch(u64),
+    /// Forced inclusion error
+    #[error(transparent)]
+    ForcedInclusionError(#[from] ForcedInclusionError),
+    /// Publication record encoding error
+    #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum StateError {
+    /// Transaction signature is invalid
+    #[error(transparent)]
+    InvalidSignature(#[from] SignatureError),
+    /// Transaction exceeds the gas left in the block
+    #[error(transparent)]
+    OutOfGas(#[from] OutOfGas),
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettlementError {
+    /// L1 node request failed
+    #[error(transparent)]
+    Transport(#[from] TransportError),
+    /// Rollup contract call failed
+    #[error(transparent)]
+    Contract(#[from] alloy::contract::Error),
+    /// Sent transaction could not be tracked
+    #[error(transparent)]
+    PendingTransaction(#[from] PendingTransactionError),
+    /// Crate was built without a Solidity compiler
+    #[error("Rollup contract was not compiled, set SOLC and rebuild")]
+    MissingBytecode,
+    /// Deployment transaction did not create the contract
+    #[error("Deployment transaction {} failed", _0)]
+    DeploymentFailed(TxHash),
+    /// No contract is deployed at the address
+    #[error("No rollup contract at {}", _0)]
+    MissingContract(Address),
+    /// Rollup contract refused the batch
+    #[error("Batch rejected by the rollup contract: {:?}", _0)]
+    Rejected(RollupErrors),
+    /// Batch commitment reverted once included
+    #[error("Batch commitment {} reverted", _0)]
+    Reverted(TxHash),
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -39,6 +136,12 @@ impl From<SequencerError> for ApiError {
     }
 }
 
+impl From<BlockStoreError> for ApiError {
+    fn from(value: BlockStoreError) -> Self {
+        SequencerError::from(value).into()
+    }
+}
+
 /// Error type for HTTP handlers
 pub struct ApiError(
     /// HTTP status code for response
@@ -0,0 +1,9 @@
+pub mod error;
+pub mod logger;
+pub mod publication;
+pub mod routes;
+pub mod sequencer;
+pub mod settlement;
+pub mod state;
+pub mod store;
+pub mod transaction;
@@ -1,25 +1,189 @@
-mod error;
-mod logger;
-mod routes;
-mod sequencer;
-mod store;
-mod transaction;
-
-use logger::setup_logger;
-use routes::build_api_services;
-use sequencer::{SequencerConfig, SequencerContext};
-use store::TransactionStore;
+use alloy::primitives::Address;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::signers::local::PrivateKeySigner;
+use anunaya_rollup_core::genesis::Genesis;
+use anunaya_rollup_core::hasher::{
+    Blake3Hasher, HasherKind, KeccakHasher, PoseidonHasher, Sha256Hasher,
+};
+use sequencer::error::SettlementError;
+use sequencer::logger::setup_logger;
+use sequencer::routes::build_api_services;
+use sequencer::sequencer::{SequencerConfig, SequencerContext, SequencerRpcMethods};
+use sequencer::settlement::{self, BatchCommitment, Settlement};
+use sequencer::state::SequencerHasher;
+use sequencer::store::TransactionStore;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     setup_logger(2, "Sequencer")?;
 
-    // Sequencer configuration
-    let config = SequencerConfig::default();
+    // Chain specification, read from the JSON or TOML file at `SEQUENCER_GENESIS`. Without
+    // it a default chain is started, hashed with `SEQUENCER_HASHER` if set
+    let mut genesis = match std::env::var("SEQUENCER_GENESIS") {
+        Ok(path) => Genesis::load(path)?,
+        Err(_) => Genesis::default(),
+    };
+    if let Ok(name) = std::env::var("SEQUENCER_HASHER") {
+        let hasher: HasherKind = name.parse()?;
+        if std::env::var("SEQUENCER_GENESIS").is_ok() && hasher != genesis.parameters.hasher {
+            anyhow::bail!("SEQUENCER_HASHER differs from the hasher of the genesis file");
+        }
+        genesis.parameters.hasher = hasher;
+    }
+    // Headers are signed with `SEQUENCER_PRIVATE_KEY`, or a fresh key if it is unset
+    let signer = match std::env::var("SEQUENCER_PRIVATE_KEY") {
+        Ok(key) => key.parse::<PrivateKeySigner>()?,
+        Err(_) => {
+            tracing::warn!("SEQUENCER_PRIVATE_KEY is unset, signing headers with a random key");
+            PrivateKeySigner::random()
+        }
+    };
+    let config = SequencerConfig {
+        data_dir: Some("sequencer-data".into()),
+        // Blocks are published to a local data availability layer
+        da_dir: Some("sequencer-da".into()),
+        genesis,
+        // Start from the state snapshot at `SEQUENCER_SNAPSHOT` instead of replaying all blocks,
+        // or without the earlier blocks on a new node
+        snapshot: std::env::var("SEQUENCER_SNAPSHOT").ok().map(Into::into),
+        signer,
+        ..Default::default()
+    };
+    tracing::info!(
+        "Starting chain {} with the {} hasher, proposer {}",
+        config.genesis.chain_id,
+        config.genesis.parameters.hasher,
+        config.signer.address()
+    );
+
+    // Batches are settled on the rollup contract of the L1 node at `SEQUENCER_L1_RPC`, sent
+    // from the proposer account
+    let settlement = match std::env::var("SEQUENCER_L1_RPC") {
+        Ok(url) => {
+            let provider = ProviderBuilder::new()
+                .wallet(config.signer.clone())
+                .connect_http(url.parse()?);
+            Some(connect_settlement(provider).await?)
+        }
+        Err(_) => None,
+    };
+
+    match config.genesis.parameters.hasher {
+        HasherKind::Keccak => run::<KeccakHasher, _>(config, settlement).await,
+        HasherKind::Sha256 => run::<Sha256Hasher, _>(config, settlement).await,
+        HasherKind::Blake3 => run::<Blake3Hasher, _>(config, settlement).await,
+        HasherKind::Poseidon => run::<PoseidonHasher, _>(config, settlement).await,
+    }
+}
+
+/// Binds to the rollup contract at `SEQUENCER_ROLLUP_CONTRACT`, or deploys one if it is unset.
+/// Batches are settled after `SEQUENCER_L1_CONFIRMATIONS` blocks, 6 by default.
+async fn connect_settlement<P: Provider>(provider: P) -> anyhow::Result<Settlement<P>> {
+    let confirmations = match std::env::var("SEQUENCER_L1_CONFIRMATIONS") {
+        Ok(confirmations) => confirmations.parse()?,
+        Err(_) => 6,
+    };
+    let settlement = match std::env::var("SEQUENCER_ROLLUP_CONTRACT") {
+        Ok(address) => {
+            Settlement::bind(address.parse::<Address>()?, provider, confirmations).await?
+        }
+        Err(_) => Settlement::deploy(provider, confirmations).await?,
+    };
+    match settlement.latest_settled_batch().await? {
+        Some(batch) => tracing::info!(
+            "Settling on rollup contract {}, latest settled block #{}",
+            settlement.address(),
+            batch.block_number
+        ),
+        None => tracing::info!("Settling on rollup contract {}", settlement.address()),
+    }
+    Ok(settlement)
+}
+
+/// Number of the block following the latest batch committed to the contract.
+async fn batch_after_latest<P: Provider>(settlement: &Settlement<P>) -> settlement::Result<u64> {
+    Ok(settlement
+        .latest_batch()
+        .await?
+        .map_or(1, |batch| batch.block_number + 1))
+}
+
+/// Posts the batches of the blocks from `next` on whose data is available, in order, and
+/// advances `next` past the posted ones.
+async fn post_batches<H: SequencerHasher, P: Provider>(
+    ctx: &SequencerContext<H>,
+    settlement: &Settlement<P>,
+    next: &mut u64,
+) -> anyhow::Result<()> {
+    while let Some(block) = ctx.blocks.block_by_number(*next)? {
+        let Some(receipt) = ctx
+            .publications
+            .get(*next)?
+            .and_then(|publication| publication.da)
+        else {
+            break;
+        };
+        settlement
+            .post_batch(BatchCommitment::from_publication(&block, &receipt))
+            .await?;
+        *next += 1;
+    }
+    Ok(())
+}
+
+async fn run<H: SequencerHasher, P: Provider + 'static>(
+    config: SequencerConfig,
+    settlement: Option<Settlement<P>>,
+) -> anyhow::Result<()> {
     // Transaction storage for sequencer
     let store = TransactionStore::new(100);
     // SequencerContext takes a configuration and populates object that are necessary througout the lifetme of sequencer
-    let ctx = SequencerContext::new(config, store)?;
+    let ctx = SequencerContext::<H>::new(config, store)?;
+
+    // Batches are posted from the block following the latest committed one
+    let mut next_batch = match &settlement {
+        Some(settlement) => batch_after_latest(settlement).await?,
+        None => 1,
+    };
+
+    // Produce a block every `block_time`
+    let producer = ctx.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(producer.config.block_time);
+        loop {
+            interval.tick().await;
+            if let Err(e) = producer.publish_batch() {
+                tracing::error!("Failed to publish batch: {e}");
+            }
+            let Some(settlement) = &settlement else {
+                continue;
+            };
+            // Batches that failed to post or reverted are posted again on the next tick, from
+            // the block following the latest committed one
+            let mut resync = false;
+            if let Err(e) = post_batches(&producer, settlement, &mut next_batch).await {
+                tracing::error!("Failed to post batch #{next_batch}: {e}");
+                resync = true;
+            }
+            match settlement.poll_settled().await {
+                Ok(settled) => {
+                    for posted in settled {
+                        tracing::info!("Settled block #{}", posted.batch.block_number);
+                    }
+                }
+                Err(e) => {
+                    tracing::error!("Failed to track settlement: {e}");
+                    resync |= matches!(e, SettlementError::Reverted(_));
+                }
+            }
+            if resync {
+                match batch_after_latest(settlement).await {
+                    Ok(next) => next_batch = next,
+                    Err(e) => tracing::error!("Failed to read the latest batch: {e}"),
+                }
+            }
+        }
+    });
 
     // Build API services
     build_api_services(ctx.clone()).await?;
@@ -0,0 +1,145 @@
+//! Publication records of the produced blocks.
+//!
+//! A block is signed once, when it is produced, and its data is made available once it is
+//! persisted. The record of each block keeps the signature, so that the header is always
+//! served with it, whatever key the sequencer runs with later, and the inclusion of the
+//! block in the data availability layer, so that a block whose submission failed is
+//! submitted again.
+use crate::error::{Result, TxStoreError};
+use alloy::primitives::Signature;
+use anunaya_rollup_core::data_availability::BlobReceipt;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Extension of the record files inside the store directory.
+const RECORD_EXTENSION: &str = "json";
+
+/// How a block was published.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Publication {
+    /// Signature of the proposer over the header hash, made when the block was produced
+    pub signature: Signature,
+    /// Inclusion of the block data in the data availability layer, once submitted
+    pub da: Option<BlobReceipt>,
+}
+
+/// Publication records by block number, kept in memory and optionally persisted to a
+/// directory with one file per block. Files are written under a temporary name and renamed
+/// once complete, so a crash never leaves a partial record behind.
+#[derive(Debug, Default)]
+pub struct PublicationStore {
+    dir: Option<PathBuf>,
+    records: RwLock<BTreeMap<u64, Publication>>,
+}
+
+impl PublicationStore {
+    /// Store kept in memory.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Opens the store in `dir`, creating it if needed.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self> {
+        fs::create_dir_all(dir.as_ref())?;
+        let mut records = BTreeMap::new();
+        for entry in fs::read_dir(dir.as_ref())? {
+            let path = entry?.path();
+            if path.extension().is_none_or(|ext| ext != RECORD_EXTENSION) {
+                continue;
+            }
+            let Some(number) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            records.insert(number, serde_json::from_slice(&fs::read(&path)?)?);
+        }
+        Ok(Self {
+            dir: Some(dir.as_ref().to_path_buf()),
+            records: RwLock::new(records),
+        })
+    }
+
+    /// Returns the record of block `number`.
+    pub fn get(&self, number: u64) -> Result<Option<Publication>> {
+        let records = self.records.read().map_err(|_| TxStoreError::LockError)?;
+        Ok(records.get(&number).cloned())
+    }
+
+    /// Records how block `number` was published, replacing any earlier record.
+    pub fn record(&self, number: u64, publication: Publication) -> Result<()> {
+        let mut records = self.records.write().map_err(|_| TxStoreError::LockError)?;
+        if let Some(dir) = &self.dir {
+            let path = dir
+                .join(number.to_string())
+                .with_extension(RECORD_EXTENSION);
+            let partial = path.with_extension("partial");
+            let mut file = File::create(&partial)?;
+            file.write_all(&serde_json::to_vec(&publication)?)?;
+            file.sync_all()?;
+            fs::rename(&partial, &path)?;
+        }
+        records.insert(number, publication);
+        Ok(())
+    }
+
+    /// Numbers of the recorded blocks not made available yet, in ascending order.
+    pub fn unavailable(&self) -> Result<Vec<u64>> {
+        let records = self.records.read().map_err(|_| TxStoreError::LockError)?;
+        Ok(records
+            .iter()
+            .filter(|(_, publication)| publication.da.is_none())
+            .map(|(number, _)| *number)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::signers::SignerSync;
+    use alloy::signers::local::PrivateKeySigner;
+
+    #[test]
+    fn test_records_persist() {
+        let dir = tempfile::tempdir().unwrap();
+        let signature = PrivateKeySigner::random()
+            .sign_message_sync(b"header")
+            .unwrap();
+        let unavailable = Publication {
+            signature,
+            da: None,
+        };
+        let available = Publication {
+            da: Some(BlobReceipt {
+                commitment: [1; 32],
+                height: 4,
+            }),
+            ..unavailable.clone()
+        };
+
+        let store = PublicationStore::open(dir.path()).unwrap();
+        store.record(1, available.clone()).unwrap();
+        store.record(2, unavailable.clone()).unwrap();
+        assert_eq!(store.unavailable().unwrap(), vec![2]);
+
+        let store = PublicationStore::open(dir.path()).unwrap();
+        assert_eq!(store.get(1).unwrap(), Some(available.clone()));
+        assert_eq!(store.get(3).unwrap(), None);
+        store.record(2, available).unwrap();
+        assert!(store.unavailable().unwrap().is_empty());
+        assert!(
+            PublicationStore::open(dir.path())
+                .unwrap()
+                .unavailable()
+                .unwrap()
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,102 @@
+use crate::error::{ApiError, SequencerError, TxStoreError};
+use crate::sequencer::SequencerContext;
+use crate::sequencer::SequencerRpcMethods;
+use crate::state::{SequencerBlock, SequencerHasher, SequencerHeader};
+use anunaya_rollup_core::mmr::MmrProof;
+use anunaya_rollup_core::receipt::Receipt;
+use anunaya_rollup_core::signed_header::SignedHeader;
+use axum::Json;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Maximum number of blocks returned by a range query
+const MAX_BLOCK_RANGE: u64 = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct RangeQuery {
+    from: u64,
+    to: u64,
+}
+
+fn not_found() -> ApiError {
+    ApiError(StatusCode::NOT_FOUND, "Block not found".to_string())
+}
+
+pub async fn handle_latest_block<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+) -> Result<Json<SequencerBlock<H>>, ApiError> {
+    let block = ctx.blocks.head()?;
+    block.map(Json).ok_or_else(not_found)
+}
+
+pub async fn handle_block_by_number<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(number): Path<u64>,
+) -> Result<Json<SequencerBlock<H>>, ApiError> {
+    let block = ctx.blocks.block_by_number(number)?;
+    block.map(Json).ok_or_else(not_found)
+}
+
+pub async fn handle_block_by_hash<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(hash): Path<String>,
+) -> Result<Json<SequencerBlock<H>>, ApiError> {
+    let hash: [u8; 32] = hex::decode(hash.trim_start_matches("0x"))
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| ApiError(StatusCode::BAD_REQUEST, "Invalid block hash".to_string()))?;
+    let block = ctx.blocks.block_by_hash(&hash)?;
+    block.map(Json).ok_or_else(not_found)
+}
+
+/// Returns the blocks numbered `from..to`, at most `MAX_BLOCK_RANGE` of them.
+pub async fn handle_block_range<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Vec<SequencerBlock<H>>>, ApiError> {
+    let to = query.to.min(query.from.saturating_add(MAX_BLOCK_RANGE));
+    let blocks = ctx.blocks.range(query.from..to)?;
+    Ok(Json(blocks))
+}
+
+pub async fn handle_signed_header<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(number): Path<u64>,
+) -> Result<Json<SignedHeader<SequencerHeader<H>>>, ApiError> {
+    let header = ctx.signed_header(number)?;
+    header.map(Json).ok_or_else(not_found)
+}
+
+pub async fn handle_latest_signed_header<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+) -> Result<Json<SignedHeader<SequencerHeader<H>>>, ApiError> {
+    let Some(head) = ctx.blocks.head()? else {
+        return Err(not_found());
+    };
+    let header = ctx.signed_header(head.header.number)?;
+    header.map(Json).ok_or_else(not_found)
+}
+
+pub async fn handle_block_receipts<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(number): Path<u64>,
+) -> Result<Json<Vec<Receipt>>, ApiError> {
+    let receipts = ctx.blocks.receipts(number)?;
+    receipts.map(Json).ok_or_else(not_found)
+}
+
+/// Returns the proof that block `number` is committed to by the history root of the latest
+/// block.
+pub async fn handle_history_proof<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(number): Path<u64>,
+) -> Result<Json<MmrProof<[u8; 32]>>, ApiError> {
+    let chain = ctx
+        .chain
+        .lock()
+        .map_err(|_| SequencerError::from(TxStoreError::LockError))?;
+    let proof = chain.history.prove_at(number, chain.head.number);
+    proof.map(Json).ok_or_else(not_found)
+}
@@ -1,15 +1,24 @@
+use alloy::primitives::Address;
+use anunaya_rollup_core::hasher::HasherKind;
 use axum::Json;
 use axum::extract::State;
 use serde::Serialize;
 use std::sync::Arc;
 
 use crate::sequencer::SequencerContext;
+use crate::state::SequencerHasher;
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct SequencerInfo {
     /// Sequencer version
     pub version: String,
+    /// Chain identifier set at genesis
+    pub chain_id: u64,
+    /// Hashing algorithm of the chain
+    pub hasher: HasherKind,
+    /// Address signing the block headers
+    pub proposer: Address,
 }
 
 #[derive(Debug, Serialize)]
@@ -19,9 +28,14 @@ pub struct Response {
     sequencer_info: SequencerInfo,
 }
 
-pub async fn handle_sequencer_info(State(ctx): State<Arc<SequencerContext>>) -> Json<Response> {
+pub async fn handle_sequencer_info<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+) -> Json<Response> {
     let sequencer_info = SequencerInfo {
         version: "v0.0.1-rc1".to_string(),
+        chain_id: ctx.config.genesis.chain_id,
+        hasher: ctx.config.genesis.parameters.hasher,
+        proposer: ctx.config.signer.address(),
     };
     Json(Response { sequencer_info })
 }
@@ -1,31 +1,51 @@
+mod blocks;
 mod info;
+mod state;
 mod submit_transaction;
 
-use std::net::SocketAddr;
 use std::sync::Arc;
 
 use crate::error::Result;
 use crate::sequencer::SequencerContext;
+use crate::state::SequencerHasher;
 use axum::Router;
 use axum::routing::{get, post};
+use blocks::{
+    handle_block_by_hash, handle_block_by_number, handle_block_range, handle_block_receipts,
+    handle_history_proof, handle_latest_block, handle_latest_signed_header, handle_signed_header,
+};
 use info::handle_sequencer_info;
+use state::handle_account_proof;
 use submit_transaction::handle_submit_transaction;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
-pub async fn build_api_services(ctx: SequencerContext) -> Result<()> {
-    // Todo get api port from sequencer config.
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:3033").await.unwrap();
+pub async fn build_api_services<H: SequencerHasher>(ctx: SequencerContext<H>) -> Result<()> {
+    let listener = tokio::net::TcpListener::bind(ctx.config.api_addr).await?;
+    tracing::info!("Starting the server on {}", listener.local_addr()?);
+    axum::serve(listener, api_router(ctx)).await?;
+    Ok(())
+}
 
+/// Builds the HTTP API of the sequencer, served under `/api/v1`.
+pub fn api_router<H: SequencerHasher>(ctx: SequencerContext<H>) -> Router {
     let api = Router::new()
-        .route("/info", get(handle_sequencer_info))
-        .route("/submit_transaction", post(handle_submit_transaction))
+        .route("/info", get(handle_sequencer_info::<H>))
+        .route("/submit_transaction", post(handle_submit_transaction::<H>))
+        .route("/blocks", get(handle_block_range::<H>))
+        .route("/blocks/latest", get(handle_latest_block::<H>))
+        .route("/blocks/{number}", get(handle_block_by_number::<H>))
+        .route("/blocks/hash/{hash}", get(handle_block_by_hash::<H>))
+        .route("/headers/latest", get(handle_latest_signed_header::<H>))
+        .route("/headers/{number}", get(handle_signed_header::<H>))
+        .route("/blocks/{number}/receipts", get(handle_block_receipts::<H>))
+        .route(
+            "/blocks/{number}/history_proof",
+            get(handle_history_proof::<H>),
+        )
+        .route("/state/accounts/{address}", get(handle_account_proof::<H>))
         .layer(TraceLayer::new_for_http())
         .layer(CorsLayer::permissive());
 
-    let app = Router::new().nest("/api/v1", api).with_state(Arc::new(ctx));
-
-    tracing::info!("Starting the server on {}", listener.local_addr()?);
-    axum::serve(listener, app).await?;
-    Ok(())
+    Router::new().nest("/api/v1", api).with_state(Arc::new(ctx))
 }
@@ -0,0 +1,27 @@
+use crate::error::{ApiError, SequencerError, TxStoreError};
+use crate::sequencer::SequencerContext;
+use crate::state::{SequencerHasher, SequencerState};
+use alloy::primitives::Address;
+use anunaya_rollup_core::smt::StateProof;
+use axum::Json;
+use axum::extract::{Path, State};
+use std::sync::Arc;
+
+/// Returns the account of `address` in the state of the latest block, with its proof against
+/// the state root of that block.
+pub async fn handle_account_proof<H: SequencerHasher>(
+    State(ctx): State<Arc<SequencerContext<H>>>,
+    Path(address): Path<Address>,
+) -> Result<Json<StateProof<[u8; 32]>>, ApiError> {
+    let chain = ctx
+        .chain
+        .lock()
+        .map_err(|_| SequencerError::from(TxStoreError::LockError))?;
+    let (value, proof) = chain.state.prove_account(&address);
+    Ok(Json(StateProof {
+        block_number: chain.head.number,
+        key: SequencerState::<H>::account_key(&address),
+        value,
+        proof,
+    }))
+}
@@ -1,39 +1,1428 @@
-use crate::{error::Result, store::TransactionStore, transaction::SignedTransaction};
-
-#[derive(Debug, Clone, Default)]
-pub struct SequencerConfig;
+use crate::error::{Result, SequencerError, TxStoreError};
+use crate::publication::{Publication, PublicationStore};
+use crate::state::{
+    SequencerBlock, SequencerGenesis, SequencerHasher, SequencerHeader, SequencerState,
+    SequencerStf, TRANSACTION_GAS,
+};
+use crate::{store::TransactionStore, transaction::SignedTransaction};
+use alloy::primitives::Signature;
+use alloy::signers::local::PrivateKeySigner;
+use anunaya_rollup_core::block::HeaderExtension;
+use anunaya_rollup_core::block_store::{BlockStore, FileBlockStore, InMemoryBlockStore};
+use anunaya_rollup_core::builder::{BlockBuilder, BlockBuilderConfig, BuiltBlock};
+use anunaya_rollup_core::codec::Encode;
+use anunaya_rollup_core::data_availability::{
+    BlobReceipt, DataAvailabilityLayer, FileDataAvailability, InMemoryDataAvailability,
+};
+use anunaya_rollup_core::forced_inclusion::{
+    ForcedInclusionError, ForcedInclusionLimits, ForcedInclusionQueue, L1Inbox,
+};
+use anunaya_rollup_core::fraud_proof::trace_block;
+use anunaya_rollup_core::genesis::Genesis;
+use anunaya_rollup_core::hasher::KeccakHasher;
+use anunaya_rollup_core::metering::Gas;
+use anunaya_rollup_core::mmr::{MerkleMountainRange, MmrProof};
+use anunaya_rollup_core::receipt::Receipt;
+use anunaya_rollup_core::signed_header::SignedHeader;
+use anunaya_rollup_core::snapshot::{
+    DEFAULT_CHUNK_SIZE, SnapshotManifest, read_snapshot, write_snapshot,
+};
+use anunaya_rollup_core::traits::{AppState, BlockHeaderT, BlockT, RevertibleState};
+use anunaya_rollup_core::validation::{
+    validate_block, validate_intermediate_roots, validate_receipts,
+};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug, Clone)]
-pub struct SequencerContext {
+pub struct SequencerConfig {
+    /// Interval between two produced blocks
+    pub block_time: Duration,
+    /// Limits applied when building blocks
+    pub block_builder: BlockBuilderConfig,
+    /// Directory of the block store, blocks are kept in memory if unset
+    pub data_dir: Option<PathBuf>,
+    /// Directory of the local data availability layer the blocks are published to, block
+    /// data is kept in memory if unset
+    pub da_dir: Option<PathBuf>,
+    /// Specification of the chain, whose hasher must be the one the sequencer runs with
+    pub genesis: Genesis<SequencerGenesis>,
+    /// Snapshot of the state at a stored block, to start from instead of replaying the
+    /// chain from genesis. A new node also starts from it without the earlier blocks.
+    pub snapshot: Option<PathBuf>,
+    /// Key signing the produced block headers, once when they are produced
+    pub signer: PrivateKeySigner,
+    /// Address the HTTP API listens on
+    pub api_addr: SocketAddr,
+}
+
+impl Default for SequencerConfig {
+    fn default() -> Self {
+        Self {
+            block_time: Duration::from_secs(2),
+            block_builder: BlockBuilderConfig::default(),
+            data_dir: None,
+            da_dir: None,
+            genesis: Genesis::default(),
+            snapshot: None,
+            signer: PrivateKeySigner::random(),
+            api_addr: SocketAddr::from(([0, 0, 0, 0], 3033)),
+        }
+    }
+}
+
+/// Directory of the publication records inside the data directory.
+const PUBLICATIONS_DIR: &str = "publications";
+
+pub type SequencerBlockStore<H = KeccakHasher> = dyn BlockStore<Block = SequencerBlock<H>>;
+
+pub type SequencerL1Inbox = dyn L1Inbox<SignedTransaction>;
+
+/// Forced transactions that fail to recover their sender can never be applied, and are
+/// dropped by every node.
+fn is_forceable(transaction: &SignedTransaction) -> bool {
+    transaction.recover().is_ok()
+}
+
+/// Number of admitted forced transactions any block has room for. Forced transactions come
+/// first in a block and each uses `TRANSACTION_GAS`, so they are never rejected.
+fn forced_capacity(
+    builder: &BlockBuilderConfig,
+    limits: ForcedInclusionLimits,
+    block_gas_limit: Gas,
+) -> usize {
+    let by_gas = usize::try_from(block_gas_limit / TRANSACTION_GAS).unwrap_or(usize::MAX);
+    builder
+        .max_transactions
+        .min(builder.max_block_bytes / limits.max_transaction_bytes.max(1))
+        .min(by_gas)
+}
+
+/// Checks `block` against the forced transactions submitted to `l1`, dequeuing those it
+/// includes. Its origin must be the L1 block of its number and, given the L1 head
+/// `l1_head`, keep up with it.
+fn process_forced<H: SequencerHasher>(
+    queue: &mut ForcedInclusionQueue<SignedTransaction>,
+    block: &SequencerBlock<H>,
+    l1: &SequencerL1Inbox,
+    l1_head: Option<u64>,
+) -> Result<()> {
+    let origin = block
+        .header()
+        .l1_origin()
+        .ok_or(ForcedInclusionError::MissingL1Origin)?;
+    if l1.block(origin.number)?.as_ref() != Some(origin) {
+        return Err(ForcedInclusionError::L1OriginMismatch(origin.number).into());
+    }
+    queue.sync(l1, origin.number, is_forceable)?;
+    if let Some(l1_head) = l1_head {
+        queue.check_lag(origin.number, l1_head, block.transactions())?;
+    }
+    queue.process_block(origin.number, block.transactions())?;
+    Ok(())
+}
+
+/// Replays `block` on `state`, the state of `parent`, checking the roots its header commits
+/// to, and returns its receipts.
+fn replay_block<H: SequencerHasher>(
+    state: &mut SequencerState<H>,
+    parent: &SequencerHeader<H>,
+    block: &SequencerBlock<H>,
+) -> Result<Vec<Receipt>> {
+    let trace = trace_block::<SequencerStf<H>>(state, block)?;
+    if state.state_root() != block.header().state_root {
+        return Err(SequencerError::Generic("Block state root mismatch"));
+    }
+    validate_receipts(block.header(), &trace.receipts)?;
+    validate_intermediate_roots(parent, block.header(), &trace.intermediate_roots)?;
+    Ok(trace.receipts)
+}
+
+/// Latest sealed header together with the state it commits to.
+#[derive(Debug)]
+pub struct ChainState<H: SequencerHasher = KeccakHasher> {
+    pub head: SequencerHeader<H>,
+    pub state: SequencerState<H>,
+    /// Accumulator of the hashes of every block up to `head`
+    pub history: MerkleMountainRange<H>,
+    /// Proof of the genesis block against `history` at the size it was resumed at, if the
+    /// chain started from a snapshot
+    pub genesis_proof: Option<MmrProof<[u8; 32]>>,
+    /// Forced transactions not included up to `head`, if the chain enables forced inclusion
+    pub forced: Option<ForcedInclusionQueue<SignedTransaction>>,
+}
+
+impl<H: SequencerHasher> ChainState<H> {
+    /// Restores the chain of `genesis` from `blocks`, replaying every stored block on top of
+    /// the genesis state. An empty store is initialized with the genesis block, otherwise
+    /// the stored genesis block must be the one built from `genesis`.
+    ///
+    /// With a `snapshot` of the state at a stored block, only the blocks after it are
+    /// replayed. A snapshot, whose header must descend from the genesis block, also starts a
+    /// chain without the blocks before it: an empty store then holds the blocks produced
+    /// after the snapshot, which is needed on every start.
+    ///
+    /// If the chain enables forced inclusion, every stored block is checked against the
+    /// transactions submitted to `l1`, and must follow a block of L1 and not leave out an
+    /// overdue transaction.
+    pub fn load(
+        blocks: &SequencerBlockStore<H>,
+        genesis: &Genesis<SequencerGenesis>,
+        snapshot: Option<&Path>,
+        l1: Option<&SequencerL1Inbox>,
+    ) -> Result<Self> {
+        let mut forced = genesis.parameters.forced_inclusion_window.map(|window| {
+            ForcedInclusionQueue::new(window, genesis.parameters.forced_inclusion_limits)
+        });
+        if forced.is_some() && l1.is_none() {
+            return Err(SequencerError::Generic(
+                "Forced inclusion requires an L1 inbox",
+            ));
+        }
+        let (mut state, genesis) = genesis.build::<SequencerBlock<H>, SequencerState<H>>()?;
+        let mut snapshot = snapshot
+            .map(read_snapshot::<SequencerHeader<H>, SequencerState<H>>)
+            .transpose()?;
+        if let Some((manifest, _)) = &snapshot {
+            manifest.verify_genesis(&genesis.header().hash())?;
+        }
+        let stored = match blocks.head()? {
+            Some(head) => blocks.range(0..head.header().number + 1)?,
+            None => Vec::new(),
+        };
+        if stored.is_empty() && snapshot.is_none() {
+            blocks.insert(genesis.clone(), vec![])?;
+            let mut history = MerkleMountainRange::new();
+            history.push(genesis.header.hash());
+            return Ok(Self {
+                head: genesis.header,
+                state,
+                history,
+                genesis_proof: None,
+                forced,
+            });
+        };
+
+        let mut history = MerkleMountainRange::new();
+        let mut genesis_proof = None;
+        let mut parent: Option<SequencerHeader<H>> = None;
+        match stored.first() {
+            Some(first) if first.header().number == 0 => {
+                if first.header().hash() != genesis.header().hash() {
+                    return Err(SequencerError::GenesisMismatch {
+                        stored: first.header().hash(),
+                        expected: genesis.header().hash(),
+                    });
+                }
+            }
+            // The chain starts from the snapshot, which the first stored block follows
+            _ => {
+                let Some((manifest, restored)) = snapshot.take() else {
+                    return Err(SequencerError::Generic(
+                        "Stored chain starts after genesis without its snapshot",
+                    ));
+                };
+                let number = manifest.header.number;
+                if forced.is_some() {
+                    return Err(SequencerError::Generic(
+                        "Forced inclusion requires the stored blocks from genesis",
+                    ));
+                }
+                if stored
+                    .first()
+                    .is_some_and(|first| first.header().number != number + 1)
+                {
+                    return Err(SequencerError::SnapshotMismatch(number));
+                }
+                history = MerkleMountainRange::from_peaks(number, &manifest.history_peaks)
+                    .ok_or(SequencerError::SnapshotMismatch(number))?;
+                history.push(manifest.header.hash());
+                genesis_proof = manifest.genesis_proof;
+                state = restored;
+                parent = Some(manifest.header);
+                tracing::info!("Started chain from the snapshot of block #{number}");
+            }
+        }
+        for block in stored {
+            let number = block.header().number;
+            // Stored blocks are not checked against the current L1 head
+            if parent.is_some()
+                && let (Some(queue), Some(l1)) = (&mut forced, l1)
+            {
+                process_forced(queue, &block, l1, None)?;
+            }
+            match &snapshot {
+                // Blocks up to the snapshot are only checked to link up
+                Some((manifest, _)) if number <= manifest.header.number => {
+                    if let Some(parent) = &parent {
+                        validate_block(parent, &block)?;
+                    }
+                    if number == manifest.header.number {
+                        if block.header().hash() != manifest.header.hash() {
+                            return Err(SequencerError::SnapshotMismatch(number));
+                        }
+                        let (_, restored) = snapshot.take().expect("snapshot matched");
+                        state = restored;
+                        tracing::info!("Restored state from the snapshot of block #{number}");
+                    }
+                }
+                _ => {
+                    if let Some(parent) = &parent {
+                        validate_block(parent, &block)?;
+                        replay_block(&mut state, parent, &block)?;
+                    }
+                }
+            }
+            history.push(block.header().hash());
+            parent = Some(block.header);
+        }
+        if let Some((manifest, _)) = snapshot {
+            return Err(SequencerError::SnapshotMismatch(manifest.header.number));
+        }
+        let head = parent.expect("chain has a head");
+        tracing::info!("Restored chain at block #{}", head.number);
+        Ok(Self {
+            head,
+            state,
+            history,
+            genesis_proof,
+            forced,
+        })
+    }
+
+    /// Imports `block`, received from the sequencer, on top of the head. It is checked like a
+    /// stored block, and its L1 origin must also keep up with the head of `l1`. `persist`
+    /// stores the block with its receipts before the chain moves to it.
+    pub fn import(
+        &mut self,
+        block: SequencerBlock<H>,
+        l1: Option<&SequencerL1Inbox>,
+        persist: impl FnOnce(&SequencerBlock<H>, Vec<Receipt>) -> Result<()>,
+    ) -> Result<()> {
+        validate_block(&self.head, &block)?;
+        let mut forced = self.forced.clone();
+        if let Some(queue) = &mut forced {
+            let l1 = l1.ok_or(SequencerError::Generic(
+                "Forced inclusion requires an L1 inbox",
+            ))?;
+            process_forced(queue, &block, l1, Some(l1.head()?.number))?;
+        }
+        let checkpoint = self.state.checkpoint();
+        let persisted = replay_block(&mut self.state, &self.head, &block)
+            .and_then(|receipts| persist(&block, receipts));
+        if let Err(e) = persisted {
+            self.state.revert(checkpoint);
+            return Err(e);
+        }
+        self.state.commit(checkpoint);
+        self.history.push(block.header().hash());
+        self.head = block.header;
+        self.forced = forced;
+        Ok(())
+    }
+}
+
+pub struct SequencerContext<H: SequencerHasher = KeccakHasher> {
     pub config: SequencerConfig,
     pub store: TransactionStore,
+    pub blocks: Arc<SequencerBlockStore<H>>,
+    /// Signatures and data availability receipts of the produced blocks
+    pub publications: Arc<PublicationStore>,
+    /// Layer every published block is submitted to
+    pub da: Arc<dyn DataAvailabilityLayer>,
+    pub chain: Arc<Mutex<ChainState<H>>>,
+    /// L1 inbox of the forced transactions, if the chain enables forced inclusion
+    pub l1: Option<Arc<SequencerL1Inbox>>,
 }
 
-pub trait SequencerRpcMethods {
+impl<H: SequencerHasher> Clone for SequencerContext<H> {
+    fn clone(&self) -> Self {
+        Self {
+            config: self.config.clone(),
+            store: self.store.clone(),
+            blocks: self.blocks.clone(),
+            publications: self.publications.clone(),
+            da: self.da.clone(),
+            chain: self.chain.clone(),
+            l1: self.l1.clone(),
+        }
+    }
+}
+
+pub trait SequencerRpcMethods<H: SequencerHasher = KeccakHasher> {
     fn accept_tx(&self, tx: Vec<u8>) -> Result<()>;
-    fn publish_batch(&self) -> Result<()>;
+    fn publish_batch(&self) -> Result<SequencerBlock<H>>;
+    fn signed_header(&self, number: u64) -> Result<Option<SignedHeader<SequencerHeader<H>>>>;
 }
 
-impl SequencerContext {
+impl<H: SequencerHasher> SequencerContext<H> {
+    /// Opens the chain of `config`, whose genesis must specify `H` as the hasher.
     pub fn new(config: SequencerConfig, store: TransactionStore) -> Result<Self> {
-        Ok(Self { config, store })
+        Self::with_l1_inbox(config, store, None)
+    }
+
+    /// Opens the chain of `config`, reading forced transactions from the L1 inbox `l1`,
+    /// which chains enabling forced inclusion require.
+    pub fn with_l1_inbox(
+        config: SequencerConfig,
+        store: TransactionStore,
+        l1: Option<Arc<SequencerL1Inbox>>,
+    ) -> Result<Self> {
+        let proposers = &config.genesis.parameters.proposers;
+        if !proposers.is_empty() && !proposers.contains(&config.signer.address()) {
+            return Err(SequencerError::Generic(
+                "Sequencer key is not an allowed proposer of the chain",
+            ));
+        }
+        let blocks: Arc<SequencerBlockStore<H>> = match &config.data_dir {
+            Some(dir) => Arc::new(FileBlockStore::open(dir)?),
+            None => Arc::new(InMemoryBlockStore::new()),
+        };
+        let publications = match &config.data_dir {
+            Some(dir) => PublicationStore::open(dir.join(PUBLICATIONS_DIR))?,
+            None => PublicationStore::new(),
+        };
+        let da: Arc<dyn DataAvailabilityLayer> = match &config.da_dir {
+            Some(dir) => Arc::new(FileDataAvailability::open(dir)?),
+            None => Arc::new(InMemoryDataAvailability::new()),
+        };
+        let chain = ChainState::load(
+            blocks.as_ref(),
+            &config.genesis,
+            config.snapshot.as_deref(),
+            l1.as_deref(),
+        )?;
+        // Every block must have room for the forced transactions of a full L1 block, so that
+        // the queue drains
+        if let Some(queue) = &chain.forced {
+            let capacity = forced_capacity(
+                &config.block_builder,
+                queue.limits(),
+                chain.state.block_gas_limit(),
+            );
+            if capacity < queue.limits().max_per_l1_block {
+                return Err(SequencerError::Generic(
+                    "Blocks have no room for the forced transactions of an L1 block",
+                ));
+            }
+        }
+        // The genesis block is not produced, so its header is signed once the chain is first
+        // opened. Every node derives it from the genesis specification, so it is never
+        // submitted to the data availability layer.
+        if let (None, Some(block)) = (publications.get(0)?, blocks.block_by_number(0)?) {
+            let signed = SignedHeader::sign(block.header, &config.signer)?;
+            let publication = Publication {
+                signature: signed.signature,
+                da: None,
+            };
+            publications.record(0, publication)?;
+        }
+        Ok(Self {
+            config,
+            store,
+            blocks,
+            publications: Arc::new(publications),
+            da,
+            chain: Arc::new(Mutex::new(chain)),
+            l1,
+        })
+    }
+
+    /// Submits the data of the persisted `block` to the data availability layer, and records
+    /// its inclusion.
+    fn make_available(
+        &self,
+        block: &SequencerBlock<H>,
+        signature: Signature,
+    ) -> Result<BlobReceipt> {
+        let receipt = self.da.submit(&block.to_bytes())?;
+        let publication = Publication {
+            signature,
+            da: Some(receipt),
+        };
+        self.publications
+            .record(block.header().number, publication)?;
+        Ok(receipt)
+    }
+
+    /// Submits the blocks of the chain up to `head` whose data is not available yet. Records
+    /// left by blocks that failed to persist are past the head, and replaced once their number
+    /// is produced.
+    fn make_stored_available(&self, head: u64) -> Result<()> {
+        for number in self.publications.unavailable()? {
+            if number == 0 {
+                continue;
+            }
+            if number > head {
+                break;
+            }
+            let (Some(block), Some(publication)) = (
+                self.blocks.block_by_number(number)?,
+                self.publications.get(number)?,
+            ) else {
+                continue;
+            };
+            let receipt = self.make_available(&block, publication.signature)?;
+            tracing::info!(
+                "Published stored block #{number} at DA height {}",
+                receipt.height
+            );
+        }
+        Ok(())
+    }
+
+    /// Imports `block` produced by the sequencer of the chain, for nodes following it.
+    pub fn import_block(&self, block: SequencerBlock<H>) -> Result<()> {
+        let mut chain = self.chain.lock().map_err(|_| TxStoreError::LockError)?;
+        chain.import(block, self.l1.as_deref(), |block, receipts| {
+            Ok(self.blocks.insert(block.clone(), receipts)?)
+        })?;
+        tracing::info!("Imported block #{}", chain.head.number);
+        Ok(())
+    }
+
+    /// Writes the snapshot of the state at the current head to `path`.
+    pub fn write_snapshot(
+        &self,
+        path: impl AsRef<Path>,
+    ) -> Result<SnapshotManifest<SequencerHeader<H>>> {
+        let chain = self.chain.lock().map_err(|_| TxStoreError::LockError)?;
+        Ok(write_snapshot(
+            path,
+            &chain.head,
+            &chain.state,
+            &chain.history,
+            chain.genesis_proof.as_ref(),
+            DEFAULT_CHUNK_SIZE,
+        )?)
     }
 }
 
-impl SequencerRpcMethods for SequencerContext {
+impl<H: SequencerHasher> SequencerRpcMethods<H> for SequencerContext<H> {
     fn accept_tx(&self, tx: Vec<u8>) -> Result<()> {
         tracing::info!("Accepting tx: 0x{}", hex::encode(&tx));
 
         // Decode the transaction
-        let signed_tx = SignedTransaction::decode(&tx)
-            .ok_or_else(|| crate::error::SequencerError::Generic("Failed to decode transaction"))?;
+        let signed_tx = SignedTransaction::decode(&tx)?;
+        // Reject transactions that can never be applied
+        signed_tx.recover()?;
 
         // Push to mempool
         self.store.push(signed_tx)?;
         Ok(())
     }
 
-    fn publish_batch(&self) -> Result<()> {
-        todo!()
+    fn publish_batch(&self) -> Result<SequencerBlock<H>> {
+        let mut chain = self.chain.lock().map_err(|_| TxStoreError::LockError)?;
+        self.make_stored_available(chain.head.number)?;
+        let builder = BlockBuilder::<SequencerStf<H>>::new(self.config.block_builder.clone());
+
+        // Due forced transactions come first, and the block follows the latest L1 block at
+        // which the overdue ones fit in it
+        let mut queue = chain.forced.clone();
+        let (l1_origin, l1_head, forced) = match (&mut queue, &self.l1) {
+            (Some(queue), Some(l1)) => {
+                let head = l1.head()?;
+                let mut origin = head.clone();
+                queue.sync(l1.as_ref(), origin.number, is_forceable)?;
+                let capacity = forced_capacity(
+                    &self.config.block_builder,
+                    queue.limits(),
+                    chain.state.block_gas_limit(),
+                );
+                if let Some(max) = queue.max_l1_origin(capacity)
+                    && max < origin.number
+                {
+                    origin = l1
+                        .block(max)?
+                        .ok_or(SequencerError::Generic("L1 block not found"))?;
+                }
+                let forced = queue
+                    .includable(origin.number)
+                    .map(|forced| forced.transaction.clone())
+                    .collect::<Vec<_>>();
+                (Some(origin), head.number, forced)
+            }
+            _ => (None, 0, Vec::new()),
+        };
+        let candidates = forced
+            .iter()
+            .cloned()
+            .chain(
+                self.store
+                    .pending(self.config.block_builder.max_transactions)?,
+            )
+            .collect::<Vec<_>>();
+        // Timestamps must increase even when blocks are produced within the same second
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+        let timestamp = match chain.head.timestamp() {
+            Some(parent) => now.max(parent + 1),
+            None => now,
+        };
+        let extension = HeaderExtension {
+            timestamp: Some(timestamp),
+            proposer: Some(self.config.signer.address()),
+            history_root: Some(chain.history.root().into()),
+            l1_origin: l1_origin.clone(),
+            ..Default::default()
+        };
+
+        // The block is built directly on the state, which is rolled back unless it is persisted
+        let ChainState { head, state, .. } = &mut *chain;
+        let checkpoint = state.checkpoint();
+        let BuiltBlock {
+            block,
+            receipts,
+            rejected,
+            consumed,
+            ..
+        } = match builder.build(head, state, &candidates, Some(extension)) {
+            Ok(built) => built,
+            Err(e) => {
+                state.revert(checkpoint);
+                return Err(e.into());
+            }
+        };
+        for (index, error) in rejected {
+            tracing::warn!(
+                "Dropping transaction 0x{}: {}",
+                hex::encode(candidates[index].hash()),
+                error
+            );
+        }
+        // A block leaving out an overdue forced transaction, or lagging L1, would be refused
+        // by followers
+        if let (Some(queue), Some(l1)) = (&mut queue, &self.l1)
+            && let Err(e) = process_forced(queue, &block, l1.as_ref(), Some(l1_head))
+        {
+            state.revert(checkpoint);
+            return Err(e);
+        }
+        // The header is signed once, and always served with this signature
+        let signature = match SignedHeader::sign(block.header().clone(), &self.config.signer) {
+            Ok(signed) => signed.signature,
+            Err(e) => {
+                state.revert(checkpoint);
+                return Err(e.into());
+            }
+        };
+        // Included transactions leave the mempool, and are put back if the block is not
+        // persisted
+        let included = match self
+            .store
+            .remove_front(consumed.saturating_sub(forced.len()))
+        {
+            Ok(included) => included,
+            Err(e) => {
+                state.revert(checkpoint);
+                return Err(e);
+            }
+        };
+        // The block is persisted before its data is made available, so that a block number
+        // is only ever published with one block
+        let unavailable = Publication {
+            signature,
+            da: None,
+        };
+        let persisted = self
+            .publications
+            .record(block.header().number, unavailable)
+            .and_then(|()| Ok(self.blocks.insert(block.clone(), receipts)?));
+        if let Err(e) = persisted {
+            state.revert(checkpoint);
+            self.store.restore_front(included)?;
+            return Err(e);
+        }
+        state.commit(checkpoint);
+        chain.head = block.header().clone();
+        chain.forced = queue;
+        chain.history.push(block.header().hash());
+
+        // A failed submission is retried before the next block is built
+        let receipt = self.make_available(&block, signature)?;
+
+        tracing::info!(
+            "Published block #{} 0x{} with {} transactions at DA height {}",
+            block.header().number,
+            hex::encode(block.header().hash()),
+            block.transactions().len(),
+            receipt.height
+        );
+        Ok(block)
+    }
+
+    /// Returns the header of block `number` with the signature made when it was produced.
+    /// The genesis block is not produced, its header is signed when the chain is first
+    /// opened instead.
+    fn signed_header(&self, number: u64) -> Result<Option<SignedHeader<SequencerHeader<H>>>> {
+        let Some(publication) = self.publications.get(number)? else {
+            return Ok(None);
+        };
+        let Some(block) = self.blocks.block_by_number(number)? else {
+            return Ok(None);
+        };
+        Ok(Some(SignedHeader {
+            header: block.header,
+            signature: publication.signature,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Transaction;
+    use alloy::signers::Signer;
+    use anunaya_rollup_core::data_availability::{BlobCommitment, DataAvailabilityError};
+    use anunaya_rollup_core::snapshot::SnapshotError;
+
+    async fn signed_transaction(signer: &PrivateKeySigner, nonce: u64) -> SignedTransaction {
+        let transaction = Transaction {
+            amount: 10,
+            destination: signer.address(),
+            nonce,
+        };
+        let signature = signer.sign_message(&transaction.encode()).await.unwrap();
+        SignedTransaction {
+            transaction,
+            signature,
+        }
+    }
+
+    /// Layer refusing submissions while it is down.
+    #[derive(Default)]
+    struct FlakyDataAvailability {
+        layer: InMemoryDataAvailability,
+        down: std::sync::atomic::AtomicBool,
+    }
+
+    impl DataAvailabilityLayer for FlakyDataAvailability {
+        fn submit(&self, blob: &[u8]) -> std::result::Result<BlobReceipt, DataAvailabilityError> {
+            if self.down.load(std::sync::atomic::Ordering::SeqCst) {
+                return Err(std::io::Error::other("layer is down").into());
+            }
+            self.layer.submit(blob)
+        }
+
+        fn retrieve(
+            &self,
+            commitment: &BlobCommitment,
+        ) -> std::result::Result<Option<Vec<u8>>, DataAvailabilityError> {
+            self.layer.retrieve(commitment)
+        }
+
+        fn availability(
+            &self,
+            commitment: &BlobCommitment,
+        ) -> std::result::Result<Option<u64>, DataAvailabilityError> {
+            self.layer.availability(commitment)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_batch() {
+        let config = SequencerConfig {
+            block_builder: BlockBuilderConfig {
+                max_transactions: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let ctx = SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)).unwrap();
+        let alice = PrivateKeySigner::random();
+        for nonce in 0..3 {
+            ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                .unwrap();
+        }
+        let genesis = ctx.chain.lock().unwrap().head.clone();
+
+        let first = ctx.publish_batch().unwrap();
+        assert_eq!(first.header().number, 1);
+        assert_eq!(first.header().parent_hash, genesis.hash());
+        assert_eq!(first.transactions().len(), 2);
+        assert!(first.header().timestamp().is_some());
+        assert_eq!(ctx.store.size().unwrap(), 1);
+        let receipts = ctx.blocks.receipts(1).unwrap().unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert!(receipts.iter().all(|r| r.is_success()));
+        assert!(
+            receipts
+                .iter()
+                .all(|r| r.resources_used == crate::state::TRANSACTION_GAS)
+        );
+        assert_eq!(
+            receipts[1].logs[0].topics,
+            vec![alice.address().into_word()]
+        );
+        assert_eq!(validate_receipts(first.header(), &receipts), Ok(()));
+
+        let second = ctx.publish_batch().unwrap();
+        assert_eq!(second.header().number, 2);
+        assert_eq!(second.header().parent_hash, first.header().hash());
+        assert_eq!(ctx.store.size().unwrap(), 0);
+
+        let chain = ctx.chain.lock().unwrap();
+        assert_eq!(chain.head.hash(), second.header().hash());
+        assert_eq!(chain.state.nonce(&alice.address()), Some(2));
+        assert_eq!(second.header().state_root, chain.state.state_root());
+        assert_eq!(
+            ctx.blocks.head().unwrap().unwrap().header().hash(),
+            second.header().hash()
+        );
+
+        // The history root of a block commits to every block before it
+        let history_root = second.header().history_root().unwrap();
+        let proof = chain.history.prove_at(1, 2).unwrap();
+        assert!(proof.verify::<KeccakHasher>(&history_root.0, &first.header().hash()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_publish_keeps_transactions() {
+        let ctx = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig::default(),
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        let alice = PrivateKeySigner::random();
+        for nonce in 0..2 {
+            ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                .unwrap();
+        }
+        // Another block #1 in the store makes the next block fail to persist
+        let genesis = ctx.chain.lock().unwrap().head.clone();
+        let other = SequencerBlock::new(
+            SequencerHeader::new(1, [1; 32], genesis.hash(), genesis.transactions_root),
+            vec![],
+        );
+        ctx.blocks.insert(other, vec![]).unwrap();
+
+        assert!(ctx.publish_batch().is_err());
+        assert_eq!(ctx.store.size().unwrap(), 2);
+        let chain = ctx.chain.lock().unwrap();
+        assert_eq!(chain.head.hash(), genesis.hash());
+        assert_eq!(chain.state.nonce(&alice.address()), None);
+    }
+
+    #[tokio::test]
+    async fn test_publish_to_data_availability() {
+        use anunaya_rollup_core::codec::Decode;
+        use anunaya_rollup_core::data_availability::blob_commitment;
+
+        let alice = PrivateKeySigner::random();
+        let dir = tempfile::tempdir().unwrap();
+        for da_dir in [None, Some(dir.path().join("da"))] {
+            let config = SequencerConfig {
+                da_dir: da_dir.clone(),
+                ..Default::default()
+            };
+            let ctx =
+                SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)).unwrap();
+            ctx.accept_tx(signed_transaction(&alice, 0).await.encode())
+                .unwrap();
+            let first = ctx.publish_batch().unwrap();
+            let second = ctx.publish_batch().unwrap();
+
+            for (height, block) in [first, second].iter().enumerate() {
+                let commitment = blob_commitment(&block.to_bytes());
+                assert_eq!(
+                    ctx.da.availability(&commitment).unwrap(),
+                    Some(height as u64)
+                );
+                let blob = ctx.da.retrieve(&commitment).unwrap().unwrap();
+                let published = SequencerBlock::<KeccakHasher>::from_bytes(&blob).unwrap();
+                assert_eq!(published.header().hash(), block.header().hash());
+            }
+        }
+
+        // The file layer keeps the published blocks across restarts
+        let da = FileDataAvailability::open(dir.path().join("da")).unwrap();
+        assert_eq!(da.submit(b"next").unwrap().height, 2);
+    }
+
+    #[tokio::test]
+    async fn test_failed_submission_is_retried() {
+        use anunaya_rollup_core::data_availability::blob_commitment;
+        use std::sync::atomic::Ordering;
+
+        let da = Arc::new(FlakyDataAvailability::default());
+        let mut ctx = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig::default(),
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        ctx.da = da.clone();
+        let alice = PrivateKeySigner::random();
+        ctx.accept_tx(signed_transaction(&alice, 0).await.encode())
+            .unwrap();
+
+        // The block is kept even though its data could not be made available
+        da.down.store(true, Ordering::SeqCst);
+        assert!(ctx.publish_batch().is_err());
+        let first = ctx.blocks.block_by_number(1).unwrap().unwrap();
+        assert_eq!(ctx.chain.lock().unwrap().head.hash(), first.header().hash());
+        assert_eq!(ctx.store.size().unwrap(), 0);
+        assert_eq!(ctx.publications.get(1).unwrap().unwrap().da, None);
+
+        // Its submission is retried before the next block
+        da.down.store(false, Ordering::SeqCst);
+        let second = ctx.publish_batch().unwrap();
+        assert_eq!(second.header().number, 2);
+        for (height, block) in [first, second].iter().enumerate() {
+            let receipt = ctx
+                .publications
+                .get(block.header().number)
+                .unwrap()
+                .unwrap()
+                .da;
+            assert_eq!(
+                receipt,
+                Some(BlobReceipt {
+                    commitment: blob_commitment(&block.to_bytes()),
+                    height: height as u64,
+                })
+            );
+        }
+        // The genesis block is the only one never submitted
+        assert_eq!(ctx.publications.unavailable().unwrap(), vec![0]);
+    }
+
+    #[tokio::test]
+    async fn test_restart_restores_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = SequencerConfig {
+            data_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        let alice = PrivateKeySigner::random();
+
+        let head = {
+            let ctx =
+                SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                    .unwrap();
+            for nonce in 0..2 {
+                ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                    .unwrap();
+                ctx.publish_batch().unwrap();
+            }
+            ctx.chain.lock().unwrap().head.clone()
+        };
+
+        // Headers keep the signature they were produced with, whatever the key after restart
+        let proposer = config.signer.address();
+        let config = SequencerConfig {
+            signer: PrivateKeySigner::random(),
+            ..config
+        };
+        let ctx = SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)).unwrap();
+        let signed = ctx.signed_header(2).unwrap().unwrap();
+        assert_eq!(signed.header.hash(), head.hash());
+        assert_eq!(signed.signer().unwrap(), proposer);
+        let chain = ctx.chain.lock().unwrap();
+        assert_eq!(chain.head.hash(), head.hash());
+        assert_eq!(chain.history.len(), 3);
+        assert_eq!(
+            chain.history.root_at(2).map(Into::into),
+            head.history_root()
+        );
+        assert_eq!(chain.state.nonce(&alice.address()), Some(1));
+        assert_eq!(ctx.blocks.range(0..10).unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_sequencer_with_other_hasher() {
+        use anunaya_rollup_core::hasher::{HasherKind, Sha256Hasher};
+        use anunaya_rollup_core::traits::HasherT;
+
+        let mut config = SequencerConfig::default();
+        config.genesis.parameters.hasher = HasherKind::Sha256;
+        assert!(
+            SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                .is_err()
+        );
+
+        let ctx = SequencerContext::<Sha256Hasher>::new(config, TransactionStore::new(10)).unwrap();
+        let alice = PrivateKeySigner::random();
+        ctx.accept_tx(signed_transaction(&alice, 0).await.encode())
+            .unwrap();
+        let block = ctx.publish_batch().unwrap();
+        assert_eq!(
+            block.header().hash(),
+            Sha256Hasher::hash(&block.header().encode())
+        );
+        assert_eq!(
+            block.header().state_root,
+            ctx.chain.lock().unwrap().state.state_root()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_signed_headers() {
+        use anunaya_rollup_core::signed_header::{ProposerSet, SignedHeaderError};
+
+        let ctx = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig::default(),
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        let block = ctx.publish_batch().unwrap();
+        let proposer = ctx.config.signer.address();
+        assert_eq!(block.header().proposer(), Some(proposer));
+
+        let signed = ctx.signed_header(1).unwrap().unwrap();
+        assert_eq!(signed.header.hash(), block.header().hash());
+        assert_eq!(
+            signed.verify(&ProposerSet::new([proposer])).unwrap(),
+            proposer
+        );
+        assert!(matches!(
+            signed.verify(&ProposerSet::default()),
+            Err(SignedHeaderError::UnknownProposer(_))
+        ));
+        assert!(ctx.signed_header(2).unwrap().is_none());
+
+        // The genesis header is signed by the sequencer that first opened the chain
+        let genesis = ctx.signed_header(0).unwrap().unwrap();
+        assert_eq!(genesis.header.hash(), block.header().parent_hash);
+        assert_eq!(
+            genesis.verify(&ProposerSet::new([proposer])).unwrap(),
+            proposer
+        );
+    }
+
+    #[tokio::test]
+    async fn test_genesis() {
+        let alice = PrivateKeySigner::random();
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = SequencerConfig {
+            data_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        config.genesis = Genesis {
+            chain_id: 7,
+            timestamp: 1_700_000_000,
+            state: SequencerGenesis {
+                accounts: [(alice.address(), 41)].into(),
+            },
+            ..Default::default()
+        };
+        config.genesis.parameters.block_gas_limit = Some(2 * crate::state::TRANSACTION_GAS);
+
+        let genesis_hash = {
+            let ctx =
+                SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                    .unwrap();
+            let genesis = ctx.blocks.block_by_number(0).unwrap().unwrap();
+            assert_eq!(genesis.header().parent_hash, [0; 32]);
+            assert_eq!(genesis.header().timestamp(), Some(1_700_000_000));
+            {
+                let chain = ctx.chain.lock().unwrap();
+                assert_eq!(genesis.header().state_root, chain.state.state_root());
+                assert_eq!(chain.state.nonce(&alice.address()), Some(41));
+            }
+
+            // The block gas limit set at genesis bounds the block size
+            for nonce in 42..45 {
+                ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                    .unwrap();
+            }
+            assert_eq!(ctx.publish_batch().unwrap().transactions().len(), 2);
+            assert_eq!(ctx.store.size().unwrap(), 1);
+            genesis.header().hash()
+        };
+
+        // Restarting with the same genesis restores the chain
+        let ctx = SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+            .unwrap();
+        assert_eq!(
+            ctx.blocks
+                .block_by_number(0)
+                .unwrap()
+                .unwrap()
+                .header()
+                .hash(),
+            genesis_hash
+        );
+        assert_eq!(
+            ctx.chain.lock().unwrap().state.nonce(&alice.address()),
+            Some(43)
+        );
+
+        // A chain started from another genesis is refused
+        config.genesis.chain_id = 8;
+        assert!(matches!(
+            SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)),
+            Err(SequencerError::GenesisMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_genesis_proposers() {
+        let mut config = SequencerConfig::default();
+        config.genesis.parameters.proposers = vec![PrivateKeySigner::random().address()];
+        assert!(
+            SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                .is_err()
+        );
+
+        config
+            .genesis
+            .parameters
+            .proposers
+            .push(config.signer.address());
+        assert!(SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_restore_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("state.snapshot");
+        let mut config = SequencerConfig {
+            data_dir: Some(dir.path().join("blocks")),
+            ..Default::default()
+        };
+        let alice = PrivateKeySigner::random();
+
+        let head = {
+            let ctx =
+                SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                    .unwrap();
+            for nonce in 0..3 {
+                ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                    .unwrap();
+                ctx.publish_batch().unwrap();
+            }
+            let manifest = ctx.write_snapshot(&snapshot).unwrap();
+            assert_eq!(manifest.header.number, 3);
+            // Blocks after the snapshot are replayed on top of it
+            ctx.accept_tx(signed_transaction(&alice, 3).await.encode())
+                .unwrap();
+            ctx.publish_batch().unwrap().header
+        };
+
+        config.snapshot = Some(snapshot.clone());
+        let ctx = SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+            .unwrap();
+        {
+            let chain = ctx.chain.lock().unwrap();
+            assert_eq!(chain.head.hash(), head.hash());
+            assert_eq!(chain.state.nonce(&alice.address()), Some(3));
+            assert_eq!(chain.state.state_root(), head.state_root);
+            assert_eq!(chain.history.len(), 5);
+        }
+
+        // The snapshot of another chain is refused
+        config.data_dir = Some(dir.path().join("other"));
+        let other = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig {
+                snapshot: None,
+                ..config.clone()
+            },
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        for _ in 0..3 {
+            other.publish_batch().unwrap();
+        }
+        drop(other);
+        assert!(matches!(
+            SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)),
+            Err(SequencerError::SnapshotMismatch(3))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_start_from_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshot = dir.path().join("state.snapshot");
+        let alice = PrivateKeySigner::random();
+        let ctx = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig::default(),
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        for nonce in 0..3 {
+            ctx.accept_tx(signed_transaction(&alice, nonce).await.encode())
+                .unwrap();
+            ctx.publish_batch().unwrap();
+        }
+        let manifest = ctx.write_snapshot(&snapshot).unwrap();
+
+        // A node without any block starts from the snapshot, and extends the same history
+        let config = SequencerConfig {
+            data_dir: Some(dir.path().join("blocks")),
+            snapshot: Some(snapshot),
+            ..Default::default()
+        };
+        let node = SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+            .unwrap();
+        assert_eq!(
+            node.chain.lock().unwrap().head.hash(),
+            manifest.header.hash()
+        );
+        node.accept_tx(signed_transaction(&alice, 3).await.encode())
+            .unwrap();
+        let block = node.publish_batch().unwrap();
+        assert_eq!(block.header().parent_hash, manifest.header.hash());
+        assert_eq!(
+            block.header().history_root(),
+            ctx.chain.lock().unwrap().history.root_at(4).map(Into::into)
+        );
+        assert!(node.blocks.block_by_number(3).unwrap().is_none());
+        assert!(node.chain.lock().unwrap().history.prove_at(2, 4).is_none());
+        drop(node);
+
+        // The blocks produced since are replayed on top of it
+        let node = SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+            .unwrap();
+        {
+            let chain = node.chain.lock().unwrap();
+            assert_eq!(chain.head.hash(), block.header().hash());
+            assert_eq!(chain.state.nonce(&alice.address()), Some(3));
+            assert_eq!(chain.history.len(), 5);
+        }
+
+        // Its own snapshots still prove the genesis block
+        let resnapshot = dir.path().join("resumed.snapshot");
+        let manifest = node.write_snapshot(&resnapshot).unwrap();
+        assert_eq!(
+            manifest.genesis_proof,
+            ctx.chain.lock().unwrap().history.prove_at(0, 4)
+        );
+        let resumed = SequencerContext::<KeccakHasher>::new(
+            SequencerConfig {
+                data_dir: None,
+                snapshot: Some(resnapshot.clone()),
+                ..Default::default()
+            },
+            TransactionStore::new(10),
+        )
+        .unwrap();
+        assert_eq!(
+            resumed.chain.lock().unwrap().head.hash(),
+            block.header().hash()
+        );
+        drop(node);
+        assert!(
+            SequencerContext::<KeccakHasher>::new(
+                SequencerConfig {
+                    snapshot: None,
+                    ..config
+                },
+                TransactionStore::new(10),
+            )
+            .is_err()
+        );
+
+        // The snapshot of a chain with another genesis block is refused
+        let mut config = SequencerConfig {
+            snapshot: Some(resnapshot),
+            ..Default::default()
+        };
+        config.genesis.chain_id = 7;
+        assert!(matches!(
+            SequencerContext::<KeccakHasher>::new(config, TransactionStore::new(10)),
+            Err(SequencerError::SnapshotError(
+                SnapshotError::GenesisMismatch(4)
+            ))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_follower_imports_blocks() {
+        use anunaya_rollup_core::block::L1Origin;
+        use anunaya_rollup_core::forced_inclusion::MockL1;
+
+        let mut config = SequencerConfig::default();
+        config.genesis.parameters.forced_inclusion_window = Some(2);
+        let l1 = Arc::new(MockL1::new());
+        let forced = signed_transaction(&PrivateKeySigner::random(), 0).await;
+        l1.submit(forced.clone()).unwrap();
+        let sequencer = SequencerContext::<KeccakHasher>::with_l1_inbox(
+            config.clone(),
+            TransactionStore::new(10),
+            Some(l1.clone()),
+        )
+        .unwrap();
+        let follower = SequencerContext::<KeccakHasher>::with_l1_inbox(
+            config,
+            TransactionStore::new(10),
+            Some(l1.clone()),
+        )
+        .unwrap();
+
+        let first = sequencer.publish_batch().unwrap();
+        assert_eq!(first.transactions()[0].hash(), forced.hash());
+        follower.import_block(first.clone()).unwrap();
+        assert_eq!(
+            follower.blocks.head().unwrap().unwrap().header().hash(),
+            first.header().hash()
+        );
+        assert!(follower.import_block(first).is_err());
+
+        // The origin must be the L1 block of its number
+        let second = sequencer.publish_batch().unwrap();
+        let mut forged = second.clone();
+        forged.header.extension.l1_origin = Some(L1Origin {
+            number: 1,
+            hash: Default::default(),
+        });
+        assert!(matches!(
+            follower.import_block(forged),
+            Err(SequencerError::ForcedInclusionError(
+                ForcedInclusionError::L1OriginMismatch(1)
+            ))
+        ));
+
+        // A block lagging L1 by more than the window is refused
+        l1.mine(3).unwrap();
+        assert!(matches!(
+            follower.import_block(second),
+            Err(SequencerError::ForcedInclusionError(
+                ForcedInclusionError::StaleL1Origin { origin: 1, head: 4 }
+            ))
+        ));
+        assert_eq!(follower.chain.lock().unwrap().head.number, 1);
+    }
+
+    #[tokio::test]
+    async fn test_forced_inclusion_backlog() {
+        use anunaya_rollup_core::forced_inclusion::MockL1;
+
+        let mut config = SequencerConfig {
+            block_builder: BlockBuilderConfig {
+                max_transactions: 2,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        config.genesis.parameters.forced_inclusion_window = Some(1);
+        config
+            .genesis
+            .parameters
+            .forced_inclusion_limits
+            .max_per_l1_block = 3;
+        let l1 = Arc::new(MockL1::new());
+        // Blocks must have room for the forced transactions of an L1 block
+        assert!(
+            SequencerContext::<KeccakHasher>::with_l1_inbox(
+                config.clone(),
+                TransactionStore::new(10),
+                Some(l1.clone()),
+            )
+            .is_err()
+        );
+        config
+            .genesis
+            .parameters
+            .forced_inclusion_limits
+            .max_per_l1_block = 2;
+
+        // More forced transactions are overdue at the L1 head than a block holds
+        let alice = PrivateKeySigner::random();
+        for nonce in 0..4 {
+            l1.submit(signed_transaction(&alice, nonce).await).unwrap();
+        }
+        l1.mine(2).unwrap();
+        let ctx = SequencerContext::<KeccakHasher>::with_l1_inbox(
+            config,
+            TransactionStore::new(10),
+            Some(l1),
+        )
+        .unwrap();
+
+        // The origin is held back until the backlog drains
+        let first = ctx.publish_batch().unwrap();
+        assert_eq!(first.header().l1_origin().unwrap().number, 3);
+        assert_eq!(first.transactions().len(), 2);
+        let second = ctx.publish_batch().unwrap();
+        assert_eq!(second.header().l1_origin().unwrap().number, 6);
+        assert_eq!(second.transactions().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_forced_inclusion() {
+        use anunaya_rollup_core::forced_inclusion::MockL1;
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = SequencerConfig {
+            data_dir: Some(dir.path().to_path_buf()),
+            ..Default::default()
+        };
+        config.genesis.parameters.forced_inclusion_window = Some(2);
+        assert!(
+            SequencerContext::<KeccakHasher>::new(config.clone(), TransactionStore::new(10))
+                .is_err()
+        );
+
+        // Alice is censored and submits her transaction to L1
+        let alice = PrivateKeySigner::random();
+        let l1 = Arc::new(MockL1::new());
+        let forced = signed_transaction(&alice, 0).await;
+        l1.submit(forced.clone()).unwrap();
+        l1.mine(1).unwrap();
+
+        let ctx = SequencerContext::<KeccakHasher>::with_l1_inbox(
+            config.clone(),
+            TransactionStore::new(10),
+            Some(l1.clone()),
+        )
+        .unwrap();
+        ctx.accept_tx(signed_transaction(&alice, 1).await.encode())
+            .unwrap();
+        let block = ctx.publish_batch().unwrap();
+        assert_eq!(block.header().l1_origin().unwrap().number, 2);
+        assert_eq!(block.transactions().len(), 2);
+        assert_eq!(block.transactions()[0].hash(), forced.hash());
+        assert_eq!(ctx.store.size().unwrap(), 0);
+        l1.mine(2).unwrap();
+        let head = ctx.publish_batch().unwrap();
+        assert_eq!(head.header().l1_origin().unwrap().number, 4);
+        assert_eq!(
+            ctx.chain
+                .lock()
+                .unwrap()
+                .forced
+                .as_ref()
+                .unwrap()
+                .pending()
+                .count(),
+            0
+        );
+        drop(ctx);
+
+        // Followers seeing the same L1 accept the chain
+        let ctx = SequencerContext::<KeccakHasher>::with_l1_inbox(
+            config.clone(),
+            TransactionStore::new(10),
+            Some(l1),
+        )
+        .unwrap();
+        assert_eq!(ctx.chain.lock().unwrap().head.hash(), head.header().hash());
+        drop(ctx);
+
+        // A forced transaction the chain left out past its window makes it invalid
+        let censored = Arc::new(MockL1::new());
+        censored
+            .submit(signed_transaction(&PrivateKeySigner::random(), 0).await)
+            .unwrap();
+        censored.mine(3).unwrap();
+        assert!(matches!(
+            SequencerContext::<KeccakHasher>::with_l1_inbox(
+                config,
+                TransactionStore::new(10),
+                Some(censored),
+            ),
+            Err(SequencerError::ForcedInclusionError(
+                ForcedInclusionError::Overdue {
+                    index: 0,
+                    l1_block: 1
+                }
+            ))
+        ));
     }
 }
@@ -0,0 +1,27 @@
+//! Rollup contract the sequencer settles its batches on.
+//!
+//! The contract keeps the latest committed batch and only accepts commitments from the
+//! account that deployed it, for consecutive block numbers. Its source is
+//! `contracts/Rollup.sol`; the bindings below are generated from it, and the build script
+//! compiles it with `solc` (taken from `SOLC`, or found on `PATH`).
+use alloy::primitives::Bytes;
+use alloy::sol;
+
+sol!(
+    #[sol(rpc, extra_derives(Debug, PartialEq, Eq))]
+    "contracts/Rollup.sol"
+);
+
+/// Hex encoded creation code written by the build script, empty if `solc` was not found.
+const CREATION_CODE: &str = include_str!(concat!(env!("OUT_DIR"), "/Rollup.bin"));
+
+/// Creation code of the contract, making the deployer the sequencer.
+///
+/// Returns `None` if the crate was built without a Solidity compiler.
+pub fn creation_code() -> Option<Bytes> {
+    let code = CREATION_CODE.trim();
+    if code.is_empty() {
+        return None;
+    }
+    Some(hex::decode(code).expect("solc emits hex bytecode").into())
+}
@@ -0,0 +1,344 @@
+//! Settlement of the produced blocks on an Ethereum rollup contract.
+//!
+//! Every batch is committed to the contract with its block number, state root, the
+//! commitment to its data on the data availability layer and the bridge roots of its header,
+//! in block order without gaps. A batch is settled once the L1
+//! block including its commitment is buried under the required number of confirmations.
+pub mod contract;
+
+use crate::error::SettlementError;
+use alloy::eips::BlockId;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{Address, B256, TxHash};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use anunaya_rollup_core::data_availability::BlobReceipt;
+use anunaya_rollup_core::traits::{BlockHeaderT, BlockT};
+use contract::Rollup::{self, RollupErrors, RollupInstance};
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use tokio::sync::Mutex;
+
+pub type Result<T> = std::result::Result<T, SettlementError>;
+
+/// Commitment of a batch posted to the rollup contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BatchCommitment {
+    pub block_number: u64,
+    pub state_root: B256,
+    /// Commitment to the block data on the data availability layer
+    pub da_commitment: B256,
+    /// Merkle root of the withdrawals to L1 made by the block, zero if it has none
+    pub withdrawals_root: B256,
+    /// Hash chain of the L1 deposits applied up to the block, zero if it has none
+    pub deposits_hash: B256,
+}
+
+impl BatchCommitment {
+    /// Commitment of `block`, whose data was included in the data availability layer with
+    /// `receipt`.
+    pub fn from_publication<B>(block: &B, receipt: &BlobReceipt) -> Self
+    where
+        B: BlockT,
+        B::BlockHeader: BlockHeaderT<Hash = [u8; 32]>,
+    {
+        let header = block.header();
+        let extension = header.extension();
+        Self {
+            block_number: (*header.number()).into(),
+            state_root: (*header.state_root()).into(),
+            da_commitment: receipt.commitment.into(),
+            withdrawals_root: extension.withdrawals_root.unwrap_or_default(),
+            deposits_hash: extension.deposits_hash.unwrap_or_default(),
+        }
+    }
+}
+
+/// Batch committed by an L1 transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PostedBatch {
+    pub batch: BatchCommitment,
+    pub tx_hash: TxHash,
+}
+
+/// Client of a rollup contract, posting batches from the sequencer account of the provider
+/// and tracking their confirmations.
+pub struct Settlement<P: Provider> {
+    contract: RollupInstance<P>,
+    confirmations: u64,
+    // Batches posted and not settled yet, in posting order
+    pending: Mutex<VecDeque<PostedBatch>>,
+}
+
+impl<P: Provider> Settlement<P> {
+    /// Deploys a rollup contract whose sequencer is the default sender of `provider`. Batches
+    /// are settled after `confirmations` L1 blocks, at least one.
+    pub async fn deploy(provider: P, confirmations: u64) -> Result<Self> {
+        let code = contract::creation_code().ok_or(SettlementError::MissingBytecode)?;
+        let request = TransactionRequest::default().with_deploy_code(code);
+        let receipt = provider
+            .send_transaction(request)
+            .await?
+            .get_receipt()
+            .await?;
+        let address = receipt
+            .contract_address
+            .filter(|_| receipt.status())
+            .ok_or(SettlementError::DeploymentFailed(receipt.transaction_hash))?;
+        Ok(Self::new(
+            RollupInstance::new(address, provider),
+            confirmations,
+        ))
+    }
+
+    /// Binds to the rollup contract deployed at `address`.
+    pub async fn bind(address: Address, provider: P, confirmations: u64) -> Result<Self> {
+        if provider.get_code_at(address).await?.is_empty() {
+            return Err(SettlementError::MissingContract(address));
+        }
+        Ok(Self::new(
+            RollupInstance::new(address, provider),
+            confirmations,
+        ))
+    }
+
+    fn new(contract: RollupInstance<P>, confirmations: u64) -> Self {
+        Self {
+            contract,
+            confirmations: confirmations.max(1),
+            pending: Mutex::new(VecDeque::new()),
+        }
+    }
+
+    /// Address of the rollup contract.
+    pub fn address(&self) -> Address {
+        *self.contract.address()
+    }
+
+    /// Account allowed to commit batches.
+    pub async fn sequencer(&self) -> Result<Address> {
+        Ok(self.contract.sequencer().call().await?)
+    }
+
+    /// Sends the transaction committing `batch`, without waiting for its inclusion. Batches
+    /// rejected by the contract fail with [`SettlementError::Rejected`].
+    pub async fn post_batch(&self, batch: BatchCommitment) -> Result<TxHash> {
+        let pending = self
+            .contract
+            .commitBatch(
+                batch.block_number,
+                batch.state_root,
+                batch.da_commitment,
+                batch.withdrawals_root,
+                batch.deposits_hash,
+            )
+            .send()
+            .await
+            .map_err(|e| match e.as_decoded_interface_error::<RollupErrors>() {
+                Some(error) => SettlementError::Rejected(error),
+                None => e.into(),
+            })?;
+        let tx_hash = *pending.tx_hash();
+        self.pending
+            .lock()
+            .await
+            .push_back(PostedBatch { batch, tx_hash });
+        Ok(tx_hash)
+    }
+
+    /// Number of L1 blocks including and built on the transaction `tx_hash`, or `None` if it
+    /// is not included yet.
+    pub async fn confirmations(&self, tx_hash: TxHash) -> Result<Option<u64>> {
+        let provider = self.contract.provider();
+        let Some(included) = provider
+            .get_transaction_receipt(tx_hash)
+            .await?
+            .and_then(|receipt| receipt.block_number)
+        else {
+            return Ok(None);
+        };
+        let latest = provider.get_block_number().await?;
+        Ok(Some((latest + 1).saturating_sub(included)))
+    }
+
+    /// Batches posted and not settled yet.
+    pub async fn pending(&self) -> Vec<PostedBatch> {
+        self.pending.lock().await.iter().copied().collect()
+    }
+
+    /// Returns the posted batches settled since the last call, in posting order. A reverted
+    /// commitment is dropped and reported once the batches settled before it are returned.
+    pub async fn poll_settled(&self) -> Result<Vec<PostedBatch>> {
+        let mut pending = self.pending.lock().await;
+        let provider = self.contract.provider();
+        let latest = provider.get_block_number().await?;
+        let mut settled = Vec::new();
+        while let Some(posted) = pending.front().copied() {
+            let Some(receipt) = provider.get_transaction_receipt(posted.tx_hash).await? else {
+                break;
+            };
+            let included = receipt.block_number.unwrap_or(latest);
+            if latest + 1 < included + self.confirmations {
+                break;
+            }
+            if !receipt.status() {
+                if settled.is_empty() {
+                    pending.pop_front();
+                    return Err(SettlementError::Reverted(posted.tx_hash));
+                }
+                break;
+            }
+            settled.push(posted);
+            pending.pop_front();
+        }
+        Ok(settled)
+    }
+
+    /// Withdrawals root committed for the block `block_number`, zero if it is not committed.
+    pub async fn withdrawals_root(&self, block_number: u64) -> Result<B256> {
+        Ok(self.contract.withdrawalsRoots(block_number).call().await?)
+    }
+
+    /// Latest batch committed to the contract, settled or not.
+    pub async fn latest_batch(&self) -> Result<Option<BatchCommitment>> {
+        self.latest_batch_at(BlockId::latest()).await
+    }
+
+    /// Latest batch committed to the contract that is settled.
+    pub async fn latest_settled_batch(&self) -> Result<Option<BatchCommitment>> {
+        let latest = self.contract.provider().get_block_number().await?;
+        let Some(settled) = (latest + 1).checked_sub(self.confirmations) else {
+            return Ok(None);
+        };
+        self.latest_batch_at(BlockId::number(settled)).await
+    }
+
+    async fn latest_batch_at(&self, block: BlockId) -> Result<Option<BatchCommitment>> {
+        let Rollup::latestBatchReturn {
+            blockNumber,
+            stateRoot,
+            daCommitment,
+            withdrawalsRoot,
+            depositsHash,
+        } = self.contract.latestBatch().block(block).call().await?;
+        // Block numbers start at 1, the genesis block is never committed
+        Ok((blockNumber != 0).then_some(BatchCommitment {
+            block_number: blockNumber,
+            state_root: stateRoot,
+            da_commitment: daCommitment,
+            withdrawals_root: withdrawalsRoot,
+            deposits_hash: depositsHash,
+        }))
+    }
+}
+
+// The tests run against a local anvil node and need the contract compiled with solc, with
+// `cargo test -p sequencer -- --ignored`
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::node_bindings::{Anvil, AnvilInstance};
+    use alloy::providers::ProviderBuilder;
+    use alloy::providers::ext::AnvilApi;
+    use alloy::signers::local::PrivateKeySigner;
+
+    fn provider(anvil: &AnvilInstance, key: usize) -> impl Provider + Clone {
+        let signer = PrivateKeySigner::from(anvil.keys()[key].clone());
+        ProviderBuilder::new()
+            .wallet(signer)
+            .connect_http(anvil.endpoint_url())
+    }
+
+    fn batch(block_number: u64) -> BatchCommitment {
+        BatchCommitment {
+            block_number,
+            state_root: B256::repeat_byte(block_number as u8),
+            da_commitment: B256::repeat_byte(!block_number as u8),
+            withdrawals_root: B256::repeat_byte(0x10 | block_number as u8),
+            deposits_hash: B256::repeat_byte(0x20 | block_number as u8),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "requires anvil and solc"]
+    async fn test_settle_batches() {
+        let anvil = Anvil::new().spawn();
+        let provider = provider(&anvil, 0);
+        let settlement = Settlement::deploy(provider.clone(), 3).await.unwrap();
+        assert_eq!(settlement.sequencer().await.unwrap(), anvil.addresses()[0]);
+        assert_eq!(settlement.latest_batch().await.unwrap(), None);
+
+        // Anvil mines every transaction in its own block
+        let first = settlement.post_batch(batch(1)).await.unwrap();
+        settlement.post_batch(batch(2)).await.unwrap();
+        assert_eq!(settlement.pending().await.len(), 2);
+        let mut confirmations = None;
+        while confirmations.is_none() {
+            confirmations = settlement.confirmations(first).await.unwrap();
+        }
+        assert_eq!(settlement.latest_batch().await.unwrap(), Some(batch(2)));
+        assert_eq!(settlement.latest_settled_batch().await.unwrap(), None);
+
+        provider.anvil_mine(Some(1), None).await.unwrap();
+        let settled = settlement.poll_settled().await.unwrap();
+        assert_eq!(settled.len(), 1);
+        assert_eq!(settled[0].batch, batch(1));
+        assert_eq!(settled[0].tx_hash, first);
+        assert_eq!(
+            settlement.latest_settled_batch().await.unwrap(),
+            Some(batch(1))
+        );
+
+        provider.anvil_mine(Some(1), None).await.unwrap();
+        assert_eq!(settlement.poll_settled().await.unwrap()[0].batch, batch(2));
+        assert!(settlement.pending().await.is_empty());
+        assert_eq!(
+            settlement.latest_settled_batch().await.unwrap(),
+            Some(batch(2))
+        );
+
+        // The commitment is read back by a client bound to the deployed contract
+        let bound = Settlement::bind(settlement.address(), provider, 1)
+            .await
+            .unwrap();
+        assert_eq!(bound.latest_settled_batch().await.unwrap(), Some(batch(2)));
+        // Withdrawals are proven against the root of any committed batch
+        assert_eq!(
+            bound.withdrawals_root(1).await.unwrap(),
+            batch(1).withdrawals_root
+        );
+        assert_eq!(bound.withdrawals_root(3).await.unwrap(), B256::ZERO);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires anvil and solc"]
+    async fn test_rejected_batches() {
+        let anvil = Anvil::new().spawn();
+        let settlement = Settlement::deploy(provider(&anvil, 0), 1).await.unwrap();
+        settlement.post_batch(batch(1)).await.unwrap();
+
+        // Batches follow each other without gaps
+        for number in [0, 1, 3] {
+            assert!(matches!(
+                settlement.post_batch(batch(number)).await,
+                Err(SettlementError::Rejected(RollupErrors::UnexpectedBatch(error)))
+                    if error.latest == 1 && error.blockNumber == number
+            ));
+        }
+        settlement.post_batch(batch(2)).await.unwrap();
+
+        // Only the deployer commits batches
+        let other = Settlement::bind(settlement.address(), provider(&anvil, 1), 1)
+            .await
+            .unwrap();
+        assert!(matches!(
+            other.post_batch(batch(3)).await,
+            Err(SettlementError::Rejected(RollupErrors::Unauthorized(_)))
+        ));
+        assert!(matches!(
+            Settlement::bind(anvil.addresses()[1], provider(&anvil, 0), 1).await,
+            Err(SettlementError::MissingContract(_))
+        ));
+        assert_eq!(settlement.latest_batch().await.unwrap(), Some(batch(2)));
+    }
+}
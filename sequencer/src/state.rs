@@ -0,0 +1,213 @@
+use crate::error::StateError;
+use crate::transaction::SignedTransaction;
+use alloy::primitives::Address;
+use anunaya_rollup_core::block::{Block, BlockHeader};
+use anunaya_rollup_core::genesis::{Genesis, GenesisError};
+use anunaya_rollup_core::hasher::KeccakHasher;
+use anunaya_rollup_core::journal::{Checkpoint, Journaled};
+use anunaya_rollup_core::metering::{Gas, GasMeter};
+use anunaya_rollup_core::receipt::{Log, Receipt};
+use anunaya_rollup_core::smt::{Key, SparseMerkleProof, SparseMerkleTree, hashed_key};
+use anunaya_rollup_core::snapshot::{SnapshotError, StateEntry};
+use anunaya_rollup_core::traits::{
+    AppState, BlockT, GenesisState, HasherT, RevertibleState, SnapshotState,
+    StateTransitionFunction,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::marker::PhantomData;
+
+pub type SequencerHeader<H = KeccakHasher> = BlockHeader<u64, H>;
+pub type SequencerBlock<H = KeccakHasher> = Block<SequencerHeader<H>, SignedTransaction>;
+
+/// Gas charged for every transaction
+pub const TRANSACTION_GAS: Gas = 21_000;
+/// Gas available to the transactions of a block, unless set at genesis
+pub const BLOCK_GAS_LIMIT: Gas = 30_000_000;
+
+/// State key of the block gas limit set at genesis
+const BLOCK_GAS_LIMIT_KEY: &[u8] = b"parameters/block_gas_limit";
+
+/// Hashers the sequencer can be run with.
+pub trait SequencerHasher: HasherT<Output = [u8; 32]> + std::fmt::Debug + Clone + 'static {}
+
+impl<H: HasherT<Output = [u8; 32]> + std::fmt::Debug + Clone + 'static> SequencerHasher for H {}
+
+/// State kept by the sequencer: the latest nonce used by every sender.
+///
+/// Nonces are stored little-endian in a sparse Merkle tree keyed by the hash of the sender
+/// address, so that they can be proven against the state root.
+#[derive(Debug, Clone)]
+pub struct SequencerState<H: SequencerHasher = KeccakHasher> {
+    accounts: Journaled<SparseMerkleTree<H>>,
+    prev_state_root: Option<[u8; 32]>,
+}
+
+impl<H: SequencerHasher> Default for SequencerState<H> {
+    fn default() -> Self {
+        Self {
+            accounts: Journaled::default(),
+            prev_state_root: None,
+        }
+    }
+}
+
+impl<H: SequencerHasher> SequencerState<H> {
+    /// Key of the account of `address` in the state tree.
+    pub fn account_key(address: &Address) -> Key {
+        hashed_key::<H>(address.as_slice())
+    }
+
+    pub fn nonce(&self, address: &Address) -> Option<u64> {
+        self.read_u64(&Self::account_key(address))
+    }
+
+    /// Returns the block gas limit set at genesis, `BLOCK_GAS_LIMIT` otherwise.
+    pub fn block_gas_limit(&self) -> Gas {
+        self.read_u64(&hashed_key::<H>(BLOCK_GAS_LIMIT_KEY))
+            .unwrap_or(BLOCK_GAS_LIMIT)
+    }
+
+    fn read_u64(&self, key: &Key) -> Option<u64> {
+        let value = self.accounts.get(key)?;
+        Some(u64::from_le_bytes(value.try_into().ok()?))
+    }
+
+    /// Returns the stored account value of `address` with its proof against the state root.
+    pub fn prove_account(
+        &self,
+        address: &Address,
+    ) -> (Option<Vec<u8>>, SparseMerkleProof<[u8; 32]>) {
+        let key = Self::account_key(address);
+        (
+            self.accounts.get(&key).map(<[u8]>::to_vec),
+            self.accounts.prove(&key),
+        )
+    }
+}
+
+/// Initial sequencer state of the genesis specification.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SequencerGenesis {
+    /// Latest nonce of each sender
+    #[serde(default)]
+    pub accounts: BTreeMap<Address, u64>,
+}
+
+impl<H: SequencerHasher> GenesisState for SequencerState<H> {
+    type Genesis = SequencerGenesis;
+
+    // Chain parameters are stored in the state tree next to the accounts, so that they are
+    // committed to by the state root
+    fn from_genesis(genesis: &Genesis<SequencerGenesis>) -> Result<Self, GenesisError> {
+        let mut state = Self::default();
+        for (address, nonce) in &genesis.state.accounts {
+            state.accounts.write(
+                Self::account_key(address),
+                Some(nonce.to_le_bytes().to_vec()),
+            );
+        }
+        if let Some(limit) = genesis.parameters.block_gas_limit {
+            state.accounts.write(
+                hashed_key::<H>(BLOCK_GAS_LIMIT_KEY),
+                Some(limit.to_le_bytes().to_vec()),
+            );
+        }
+        Ok(state)
+    }
+}
+
+impl<H: SequencerHasher> SnapshotState for SequencerState<H> {
+    fn entries(&self) -> Vec<StateEntry> {
+        self.accounts
+            .iter()
+            .map(|(key, value)| (key.to_vec(), value.clone()))
+            .collect()
+    }
+
+    fn from_entries(entries: Vec<StateEntry>) -> Result<Self, SnapshotError> {
+        let mut state = Self::default();
+        for (key, value) in entries {
+            let key = Key::try_from(key.as_slice())
+                .map_err(|_| SnapshotError::InvalidEntry(format!("Key 0x{}", hex::encode(&key))))?;
+            state.accounts.write(key, Some(value));
+        }
+        Ok(state)
+    }
+}
+
+impl<H: SequencerHasher> RevertibleState for SequencerState<H> {
+    type Checkpoint = (Checkpoint, Option<[u8; 32]>);
+
+    fn checkpoint(&mut self) -> Self::Checkpoint {
+        (self.accounts.checkpoint(), self.prev_state_root)
+    }
+
+    fn revert(&mut self, (checkpoint, prev_state_root): Self::Checkpoint) {
+        self.accounts.revert(checkpoint);
+        self.prev_state_root = prev_state_root;
+    }
+
+    fn commit(&mut self, (checkpoint, _): Self::Checkpoint) {
+        self.accounts.commit(checkpoint);
+    }
+}
+
+impl<H: SequencerHasher> AppState for SequencerState<H> {
+    fn state_root(&self) -> [u8; 32] {
+        self.accounts.root()
+    }
+
+    fn previous_state_root(&self) -> Option<[u8; 32]> {
+        self.prev_state_root
+    }
+}
+
+pub struct SequencerStf<H = KeccakHasher>(PhantomData<H>);
+
+impl<H: SequencerHasher> StateTransitionFunction for SequencerStf<H> {
+    type State = SequencerState<H>;
+    type Error = StateError;
+    type BlockHeader = SequencerHeader<H>;
+    type Block = SequencerBlock<H>;
+
+    fn validate_block(_state: &Self::State, block: &Self::Block) -> Result<(), Self::Error> {
+        for tx in block.transactions() {
+            tx.signature
+                .recover_address_from_msg(tx.transaction.encode())?;
+        }
+        Ok(())
+    }
+
+    fn begin_block(
+        state: &mut Self::State,
+        _header: &Self::BlockHeader,
+    ) -> Result<(), Self::Error> {
+        state.prev_state_root = Some(state.state_root());
+        Ok(())
+    }
+
+    fn block_gas_limit(state: &Self::State) -> Gas {
+        state.block_gas_limit()
+    }
+
+    fn apply_transaction(
+        state: &mut Self::State,
+        transaction: &SignedTransaction,
+        meter: &mut GasMeter,
+    ) -> Result<Receipt, Self::Error> {
+        meter.consume(TRANSACTION_GAS)?;
+        let sender = transaction
+            .signature
+            .recover_address_from_msg(transaction.transaction.encode())?;
+        state.accounts.write(
+            SequencerState::<H>::account_key(&sender),
+            Some(transaction.transaction.nonce.to_le_bytes().to_vec()),
+        );
+        // Each transaction reports its sender and nonce
+        Ok(Receipt::success(TRANSACTION_GAS).with_log(Log {
+            topics: vec![sender.into_word()],
+            data: transaction.transaction.nonce.to_le_bytes().to_vec(),
+        }))
+    }
+}
@@ -58,6 +58,33 @@ impl TransactionStore {
         let mut mempool = self.mempool.lock().map_err(|_| TxStoreError::LockError)?;
         Ok(mempool.pop_front())
     }
+
+    /// Get up to `limit` transactions from the front of the mempool without removing them
+    pub fn pending(&self, limit: usize) -> Result<Vec<SignedTransaction>> {
+        let mempool = self.mempool.lock().map_err(|_| TxStoreError::LockError)?;
+        Ok(mempool.iter().take(limit).cloned().collect())
+    }
+
+    /// Remove and return `count` transactions from the front of the mempool
+    pub fn remove_front(&self, count: usize) -> Result<Vec<SignedTransaction>> {
+        let mut mempool = self.mempool.lock().map_err(|_| TxStoreError::LockError)?;
+
+        if count > mempool.len() {
+            return Err(TxStoreError::IndexOutOfBounds.into());
+        }
+
+        Ok(mempool.drain(..count).collect())
+    }
+
+    /// Put back transactions removed from the front of the mempool, ahead of the others
+    pub fn restore_front(&self, transactions: Vec<SignedTransaction>) -> Result<()> {
+        let mut mempool = self.mempool.lock().map_err(|_| TxStoreError::LockError)?;
+
+        for transaction in transactions.into_iter().rev() {
+            mempool.push_front(transaction);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -208,4 +235,38 @@ mod tests {
         // Test pop front on empty mempool
         assert!(store.pop_front().unwrap().is_none());
     }
+
+    #[tokio::test]
+    async fn test_pending_and_remove_front() {
+        let store = TransactionStore::new(3);
+        let mut hashes = vec![];
+        for _ in 0..3 {
+            let tx = create_test_transaction().await;
+            hashes.push(tx.hash());
+            store.push(tx).unwrap();
+        }
+
+        // Pending does not remove transactions
+        let pending = store.pending(2).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].hash(), hashes[0]);
+        assert_eq!(pending[1].hash(), hashes[1]);
+        assert_eq!(store.size().unwrap(), 3);
+
+        let removed = store.remove_front(2).unwrap();
+        assert_eq!(removed.len(), 2);
+        assert_eq!(store.size().unwrap(), 1);
+        assert_eq!(store.peek_front().unwrap().unwrap().hash(), hashes[2]);
+
+        // Restored transactions keep their order, ahead of the others
+        store.restore_front(removed).unwrap();
+        let pending = store.pending(3).unwrap();
+        let restored: Vec<_> = pending.iter().map(SignedTransaction::hash).collect();
+        assert_eq!(restored, hashes);
+        store.remove_front(2).unwrap();
+        assert!(matches!(
+            store.remove_front(2),
+            Err(SequencerError::TxStoreError(TxStoreError::IndexOutOfBounds))
+        ));
+    }
 }
@@ -1,6 +1,9 @@
 use crate::error::SequencerError;
 use ::serde::{Deserialize, Serialize};
 use alloy::primitives::{Address, Signature, TxNonce as Nonce};
+use anunaya_rollup_core::codec::{CodecError, Decode, Encode};
+use anunaya_rollup_core::hasher::KeccakHasher;
+use anunaya_rollup_core::traits::{HasherT, SignedTransactionT};
 
 type Amount = u64;
 
@@ -12,11 +15,27 @@ pub struct Transaction {
 }
 
 impl Transaction {
+    /// Returns the canonical encoding of the transaction, which is the signed message.
     pub fn encode(&self) -> Vec<u8> {
-        serde_json::to_string(&self)
-            .expect("Serialization should not fail")
-            .as_bytes()
-            .to_vec()
+        self.to_bytes()
+    }
+}
+
+impl Encode for Transaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.amount.encode_to(out);
+        self.destination.encode_to(out);
+        self.nonce.encode_to(out);
+    }
+}
+
+impl Decode for Transaction {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            amount: Amount::decode_from(input)?,
+            destination: Address::decode_from(input)?,
+            nonce: Nonce::decode_from(input)?,
+        })
     }
 }
 
@@ -28,24 +47,44 @@ pub struct SignedTransaction {
 
 impl SignedTransaction {
     pub fn encode(&self) -> Vec<u8> {
-        serde_json::to_string(&self)
-            .expect("Serialization should not fail")
-            .as_bytes()
-            .to_vec()
+        self.to_bytes()
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, CodecError> {
+        Self::from_bytes(bytes)
     }
 
-    pub fn decode(bytes: &[u8]) -> Option<Self> {
-        serde_json::from_slice(bytes).ok()
+    /// Returns the hash of the canonical encoding of the signed transaction.
+    pub fn hash(&self) -> [u8; 32] {
+        KeccakHasher::hash(&self.encode())
     }
 
     pub fn recover(&self) -> Result<Address, SequencerError> {
         let bytes = self.transaction.encode();
         self.signature
             .recover_address_from_msg(bytes)
-            .map_err(|e| SequencerError::SignatureError(e))
+            .map_err(SequencerError::SignatureError)
     }
 }
 
+impl Encode for SignedTransaction {
+    fn encode_to(&self, out: &mut Vec<u8>) {
+        self.transaction.encode_to(out);
+        self.signature.encode_to(out);
+    }
+}
+
+impl Decode for SignedTransaction {
+    fn decode_from(input: &mut &[u8]) -> Result<Self, CodecError> {
+        Ok(Self {
+            transaction: Transaction::decode_from(input)?,
+            signature: Signature::decode_from(input)?,
+        })
+    }
+}
+
+impl SignedTransactionT for SignedTransaction {}
+
 #[cfg(test)]
 mod tests {
     use crate::transaction::Transaction;
@@ -73,4 +112,30 @@ mod tests {
             .expect("Should recover address");
         assert_eq!(recovered_address, alice.address());
     }
+
+    #[tokio::test]
+    async fn test_signed_transaction_codec() {
+        let alice = PrivateKeySigner::random();
+        let transaction = Transaction {
+            amount: u64::MAX,
+            destination: alice.address(),
+            nonce: 7,
+        };
+        let signature = alice.sign_message(&transaction.encode()).await.unwrap();
+        let signed_transaction = SignedTransaction {
+            transaction,
+            signature,
+        };
+
+        let mut bytes = signed_transaction.encode();
+        let decoded = SignedTransaction::decode(&bytes).expect("Should decode");
+        assert_eq!(decoded.hash(), signed_transaction.hash());
+        assert_eq!(decoded.recover().unwrap(), alice.address());
+
+        bytes.push(0);
+        assert_eq!(
+            SignedTransaction::decode(&bytes).err(),
+            Some(CodecError::TrailingBytes(1))
+        );
+    }
 }